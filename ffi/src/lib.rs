@@ -0,0 +1,3 @@
+//! Builds the C interface of `mlx5fw::ffi` as a library C tools can link
+
+pub use mlx5fw_core::ffi::*;
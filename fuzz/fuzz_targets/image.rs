@@ -0,0 +1,33 @@
+#![no_main]
+
+//! Whole images: the HW pointers, both TOCs and every section parser reachable from them
+
+use libfuzzer_sys::fuzz_target;
+use mlx5fw::firmware::Firmware;
+use mlx5fw::verify::{self, Profile};
+
+fuzz_target!(|data: &[u8]| {
+    let firmware = Firmware::from_bytes(data.to_vec());
+    let _ = firmware.hwpointers();
+    let _ = firmware.boot2();
+    let _ = firmware.itoc_entries().lenient().count();
+    let _ = firmware.dtoc();
+    if let Ok(code_sections) = firmware.code_sections() {
+        for entry in code_sections {
+            let _ = firmware.section_code(&entry);
+        }
+    }
+    if let Ok(image_info) = firmware.image_info() {
+        let _ = (image_info.psid(), image_info.release_date(), image_info.vsd());
+    }
+    let _ = firmware.dev_info();
+    let _ = firmware.mfg_info();
+    let _ = firmware.vpd();
+    let _ = firmware.nv_log();
+    let _ = firmware.forbidden_versions();
+    let _ = firmware.dbg_fw_ini();
+    let _ = firmware.programmable_hw_fw();
+    let _ = firmware.hashes_table();
+    let _ = firmware.crdump_mask();
+    let _ = verify::verify(&firmware, Profile::Strict);
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+//! IMAGE_INFO decoding and the strings derived from it
+
+use deku::prelude::*;
+use libfuzzer_sys::fuzz_target;
+use mlx5fw::structures::imageinfo::ImageInfo;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, image_info)) = ImageInfo::from_bytes((data, 0)) {
+        let _ = image_info.psid();
+        let _ = image_info.release_date();
+        let _ = image_info.vsd();
+        let _ = image_info.security_attributes();
+        let _ = image_info.fw_version.to_string();
+        let _ = image_info.to_bytes();
+    }
+});
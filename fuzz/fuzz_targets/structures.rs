@@ -0,0 +1,45 @@
+#![no_main]
+
+//! Deku decoding of the individual structures, the first byte selects which one. Everything that
+//! decodes must also encode again.
+
+use deku::prelude::*;
+use libfuzzer_sys::fuzz_target;
+use mlx5fw::structures::{
+    crdump::CrDumpMask,
+    devinfo::{DevInfo, MfgInfo},
+    forbidden::ForbiddenVersions,
+    hashes::HashesTable,
+    hwpointers::{Boot2, HwPointers},
+    itoc::ItocEntry,
+    programmable::ProgrammableHwFw,
+    vpd::Vpd,
+};
+
+fn decode<'a, T: DekuContainerRead<'a> + DekuContainerWrite>(data: &'a [u8]) {
+    if let Ok((_, value)) = T::from_bytes((data, 0)) {
+        let _ = value.to_bytes();
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, data)) = data.split_first() else {
+        return;
+    };
+    match selector % 9 {
+        0 => decode::<ItocEntry>(data),
+        1 => decode::<HwPointers>(data),
+        2 => decode::<Boot2>(data),
+        3 => decode::<CrDumpMask>(data),
+        4 => decode::<DevInfo>(data),
+        5 => decode::<MfgInfo>(data),
+        6 => decode::<ForbiddenVersions>(data),
+        7 => decode::<HashesTable>(data),
+        _ => {
+            decode::<ProgrammableHwFw>(data);
+            if let Ok(vpd) = Vpd::parse(data) {
+                let _ = vpd.to_bytes();
+            }
+        }
+    }
+});
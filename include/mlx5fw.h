@@ -0,0 +1,99 @@
+#ifndef MLX5FW_H
+#define MLX5FW_H
+
+/* Generated with cbindgen --config cbindgen.toml --output include/mlx5fw.h, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * An image opened with `mlx5fw_open`
+ */
+typedef struct Mlx5fwImage Mlx5fwImage;
+
+/**
+ * An ITOC entry, as filled in by `mlx5fw_section_info`
+ */
+typedef struct Mlx5fwSectionInfo {
+  /**
+   * Section type ID
+   */
+  uint8_t entry_type;
+  /**
+   * NUL terminated section type name, e.g. "MAIN_CODE"
+   */
+  char type_name[32];
+  uint32_t flash_addr;
+  uint32_t size;
+  uint32_t load_address;
+  uint32_t entry_point;
+  bool cache_line_crc;
+  bool encrypted;
+  bool zipped;
+} Mlx5fwSectionInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Description of the last failure on this thread, NULL if nothing failed yet. Valid until the
+ * next failing call on this thread.
+ */
+const char *mlx5fw_last_error(void);
+
+/**
+ * Open an image file and read its ITOC. Close it with `mlx5fw_close`.
+ *
+ * # Safety
+ *
+ * `path` must be a NUL terminated string
+ */
+Mlx5fwImage *mlx5fw_open(const char *path);
+
+/**
+ * # Safety
+ *
+ * `image` must be NULL or returned by `mlx5fw_open` and not closed yet
+ */
+void mlx5fw_close(Mlx5fwImage *image);
+
+/**
+ * Number of ITOC entries
+ *
+ * # Safety
+ *
+ * `image` must be returned by `mlx5fw_open` and not closed
+ */
+ptrdiff_t mlx5fw_itoc_count(const Mlx5fwImage *image);
+
+/**
+ * Fill `info` with ITOC entry `index`. Returns 0, or -1 on failure.
+ *
+ * # Safety
+ *
+ * `image` must be returned by `mlx5fw_open` and not closed, `info` must point to writable
+ * memory for one `Mlx5fwSectionInfo`
+ */
+int32_t mlx5fw_section_info(const Mlx5fwImage *image, size_t index, Mlx5fwSectionInfo *info);
+
+/**
+ * Copy up to `len` bytes of the content of section `index`, as stored on flash, to `buffer`.
+ * Returns the size of the section, which may exceed `len`, or -1 on failure. `buffer` may be
+ * NULL with `len` 0 to query the size.
+ *
+ * # Safety
+ *
+ * `image` must be returned by `mlx5fw_open` and not closed, `buffer` must point to `len`
+ * writable bytes
+ */
+ptrdiff_t mlx5fw_read_section(const Mlx5fwImage *image, size_t index, uint8_t *buffer, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MLX5FW_H */
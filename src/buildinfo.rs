@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::fmt;
+
+/// What a string embedded in code looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StringKind {
+    /// Dotted version number, e.g. 22.41.1000
+    Version,
+    /// `__DATE__`/`__TIME__` or ISO style date or time
+    Date,
+    /// Source or build path
+    Path,
+    Other,
+}
+
+impl fmt::Display for StringKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            StringKind::Version => "version",
+            StringKind::Date => "date",
+            StringKind::Path => "path",
+            StringKind::Other => "other",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeString {
+    /// Offset within the code
+    pub offset: usize,
+    pub kind: StringKind,
+    pub text: String,
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Whether `text` matches `pattern`, where `9` stands for any digit
+fn matches_digits(text: &[u8], pattern: &str) -> bool {
+    text.len() == pattern.len()
+        && text.iter().zip(pattern.bytes()).all(|(&c, p)| match p {
+            b'9' => c.is_ascii_digit(),
+            _ => c == p,
+        })
+}
+
+fn contains_digits(text: &str, pattern: &str) -> bool {
+    text.as_bytes()
+        .windows(pattern.len())
+        .any(|window| matches_digits(window, pattern))
+}
+
+fn is_date(text: &str) -> bool {
+    // __DATE__ is "Mmm dd yyyy", days below 10 padded with a space
+    let date = MONTHS.iter().any(|month| {
+        text.match_indices(month).any(|(i, _)| {
+            let rest = &text.as_bytes()[i + month.len()..];
+            rest.len() >= 8
+                && (matches_digits(&rest[..8], " 99 9999")
+                    || matches_digits(&rest[..8], "  9 9999"))
+        })
+    });
+    date || contains_digits(text, "9999-99-99") || contains_digits(text, "99:99:99")
+}
+
+fn is_version(text: &str) -> bool {
+    text.split(|c: char| !c.is_ascii_digit() && c != '.')
+        .any(|word| {
+            let parts: Vec<&str> = word.split('.').collect();
+            parts.len() >= 3 && parts.iter().all(|part| !part.is_empty())
+        })
+}
+
+fn is_path(text: &str) -> bool {
+    text.split_whitespace().any(|word| {
+        let segments = word.split(['/', '\\']).count();
+        segments >= 3
+            || (segments == 2
+                && word
+                    .rsplit(['/', '\\'])
+                    .next()
+                    .is_some_and(|name| name.contains('.')))
+    })
+}
+
+pub fn classify(text: &str) -> StringKind {
+    if is_date(text) {
+        StringKind::Date
+    } else if is_version(text) {
+        StringKind::Version
+    } else if is_path(text) {
+        StringKind::Path
+    } else {
+        StringKind::Other
+    }
+}
+
+/// Runs of at least `min_len` printable ASCII characters in `code`, like strings(1)
+pub fn strings(code: &[u8], min_len: usize) -> Vec<CodeString> {
+    let mut strings = vec![];
+    let mut start = 0;
+    for (i, byte) in code.iter().chain(&[0]).enumerate() {
+        if byte.is_ascii_graphic() || *byte == b' ' || *byte == b'\t' {
+            continue;
+        }
+        if i - start >= min_len {
+            let text = String::from_utf8_lossy(&code[start..i]).into_owned();
+            strings.push(CodeString {
+                offset: start,
+                kind: classify(&text),
+                text,
+            });
+        }
+        start = i + 1;
+    }
+    strings
+}
@@ -0,0 +1,136 @@
+use crate::chip::Layout;
+use crate::crc::calc_hwcrc;
+use crate::error::{FirmwareError, Result};
+
+/// Layout of a cache line in code sections: `data_size` bytes of code followed by two bytes
+/// of padding and a little-endian HW CRC over data and padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheLineGeometry {
+    pub data_size: usize,
+}
+
+impl Default for CacheLineGeometry {
+    fn default() -> Self {
+        Layout::FS4.cache_line_geometry
+    }
+}
+
+/// One framed cache line as stored on flash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheLine<'a> {
+    /// Offset of the line within the framed content
+    pub offset: usize,
+    pub data: &'a [u8],
+    /// Data and padding, what the CRC covers
+    pub covered: &'a [u8],
+    pub crc: u16,
+}
+
+impl CacheLine<'_> {
+    pub fn calc_crc(&self) -> u16 {
+        calc_hwcrc(0x0000, self.covered)
+    }
+
+    pub fn crc_valid(&self) -> bool {
+        self.crc == self.calc_crc()
+    }
+}
+
+impl CacheLineGeometry {
+    pub const TRAILER_SIZE: usize = 4;
+
+    /// Panics if `data_size` is 0
+    pub const fn new(data_size: usize) -> Self {
+        assert!(data_size > 0, "cache line data size must be non-zero");
+        Self { data_size }
+    }
+
+    /// Size of a cache line on flash
+    pub fn stride(&self) -> usize {
+        self.data_size + Self::TRAILER_SIZE
+    }
+
+    /// Size of the part covered by the CRC
+    pub fn crc_covered_size(&self) -> usize {
+        self.data_size + 2
+    }
+
+    /// Amount of code held by `size` bytes of cache lines on flash
+    pub fn code_size(&self, size: usize) -> usize {
+        size / self.stride() * self.data_size
+    }
+
+    /// Frame `code`, which must fill whole cache lines
+    pub fn encode(&self, code: &[u8]) -> Result<Vec<u8>> {
+        if !code.len().is_multiple_of(self.data_size) {
+            return Err(FirmwareError::SizeMismatch {
+                what: "Code to frame into cache lines".to_string(),
+                expected: code.len().next_multiple_of(self.data_size),
+                actual: code.len(),
+            });
+        }
+        let mut content = vec![];
+        for data in code.chunks(self.data_size) {
+            let mut cache_line = data.to_vec();
+            cache_line.extend_from_slice(&[0x00, 0x00]);
+            cache_line.extend_from_slice(&calc_hwcrc(0x0000, &cache_line).to_le_bytes());
+            content.extend_from_slice(&cache_line);
+        }
+        Ok(content)
+    }
+
+    /// The complete cache lines in `content`, a trailing partial line is ignored
+    pub fn lines<'a>(&self, content: &'a [u8]) -> impl Iterator<Item = CacheLine<'a>> + 'a {
+        let geometry = *self;
+        content
+            .chunks_exact(self.stride())
+            .enumerate()
+            .map(move |(i, line)| {
+                let covered = &line[..geometry.crc_covered_size()];
+                CacheLine {
+                    offset: i * geometry.stride(),
+                    data: &line[..geometry.data_size],
+                    covered,
+                    crc: u16::from_le_bytes([line[covered.len()], line[covered.len() + 1]]),
+                }
+            })
+    }
+
+    /// Strip the framing off `content`, which must consist of whole cache lines. CRCs are not
+    /// checked, see `verify`.
+    pub fn decode(&self, content: &[u8]) -> Result<Vec<u8>> {
+        if !content.len().is_multiple_of(self.stride()) {
+            return Err(FirmwareError::SizeMismatch {
+                what: "Cache-line framed content".to_string(),
+                expected: content.len() / self.stride() * self.stride(),
+                actual: content.len(),
+            });
+        }
+        Ok(self.strip(content))
+    }
+
+    /// Like `decode`, but drops a trailing partial line instead of failing
+    pub fn strip(&self, content: &[u8]) -> Vec<u8> {
+        self.lines(content)
+            .flat_map(|line| line.data)
+            .copied()
+            .collect()
+    }
+
+    /// Lines whose stored CRC does not match their content
+    pub fn verify<'a>(&self, content: &'a [u8]) -> Vec<CacheLine<'a>> {
+        self.lines(content)
+            .filter(|line| !line.crc_valid())
+            .collect()
+    }
+}
+
+/// Frame `code` with the default geometry
+pub fn encode(code: &[u8]) -> Result<Vec<u8>> {
+    CacheLineGeometry::default().encode(code)
+}
+
+/// Strip the default-geometry framing off `content`
+pub fn decode(content: &[u8]) -> Result<Vec<u8>> {
+    CacheLineGeometry::default().decode(content)
+}
@@ -0,0 +1,134 @@
+use crate::error::Result;
+use serde::Serialize;
+
+use crate::firmware::{Firmware, FirmwareStructure};
+use crate::structures::itoc::ItocEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionStatus {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SectionInfo {
+    pub index: usize,
+    pub flash_addr: usize,
+    pub size: usize,
+    pub version: u16,
+    pub section_crc: u16,
+    pub crc_ok: bool,
+}
+
+impl SectionInfo {
+    fn new(firmware: &Firmware, index: usize, entry: &ItocEntry) -> Self {
+        Self {
+            index,
+            flash_addr: entry.flash_addr,
+            size: entry.size,
+            version: entry.version,
+            section_crc: entry.section_crc,
+            crc_ok: entry
+                .stored_section_crc(firmware)
+                .is_none_or(|crc| crc == entry.calc_section_crc(firmware)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SectionChange {
+    pub section_type: String,
+    pub occurrence: usize,
+    pub status: SectionStatus,
+    pub size_delta: i64,
+    pub version_delta: i32,
+    pub old: Option<SectionInfo>,
+    pub new: Option<SectionInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Changelog {
+    pub sections: Vec<SectionChange>,
+}
+
+fn occurrences(toc: &[FirmwareStructure<ItocEntry>]) -> Vec<(usize, usize, &ItocEntry)> {
+    toc.iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let occurrence = toc[..i]
+                .iter()
+                .filter(|other| other.entry_type == entry.entry_type)
+                .count();
+            (i, occurrence, &entry.1)
+        })
+        .collect()
+}
+
+pub fn changelog(old: &Firmware, new: &Firmware) -> Result<Changelog> {
+    let old_itoc = old.itoc()?;
+    let new_itoc = new.itoc()?;
+    let old_sections = occurrences(&old_itoc);
+    let new_sections = occurrences(&new_itoc);
+
+    let mut sections = vec![];
+
+    for &(index, occurrence, old_entry) in &old_sections {
+        let old_info = SectionInfo::new(old, index, old_entry);
+        let new_match = new_sections
+            .iter()
+            .find(|(_, o, e)| *o == occurrence && e.entry_type == old_entry.entry_type);
+
+        let change = match new_match {
+            Some(&(new_index, _, new_entry)) => {
+                let new_info = SectionInfo::new(new, new_index, new_entry);
+                let status =
+                    if old_entry.content().read_bytes(old) == new_entry.content().read_bytes(new) {
+                        SectionStatus::Unchanged
+                    } else {
+                        SectionStatus::Changed
+                    };
+                SectionChange {
+                    section_type: old_entry.entry_type.to_string(),
+                    occurrence,
+                    status,
+                    size_delta: new_entry.size as i64 - old_entry.size as i64,
+                    version_delta: new_entry.version as i32 - old_entry.version as i32,
+                    old: Some(old_info),
+                    new: Some(new_info),
+                }
+            }
+            None => SectionChange {
+                section_type: old_entry.entry_type.to_string(),
+                occurrence,
+                status: SectionStatus::Removed,
+                size_delta: -(old_entry.size as i64),
+                version_delta: 0,
+                old: Some(old_info),
+                new: None,
+            },
+        };
+        sections.push(change);
+    }
+
+    for &(index, occurrence, new_entry) in &new_sections {
+        let in_old = old_sections
+            .iter()
+            .any(|(_, o, e)| *o == occurrence && e.entry_type == new_entry.entry_type);
+        if !in_old {
+            sections.push(SectionChange {
+                section_type: new_entry.entry_type.to_string(),
+                occurrence,
+                status: SectionStatus::Added,
+                size_delta: new_entry.size as i64,
+                version_delta: 0,
+                old: None,
+                new: Some(SectionInfo::new(new, index, new_entry)),
+            });
+        }
+    }
+
+    Ok(Changelog { sections })
+}
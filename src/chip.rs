@@ -0,0 +1,106 @@
+use crate::cacheline::CacheLineGeometry;
+
+/// Device families using the FS4 image format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ChipFamily {
+    Cx5,
+    Cx6,
+    Cx6dx,
+    Cx6lx,
+    Cx7,
+    Bluefield,
+    Bluefield2,
+    Bluefield3,
+}
+
+/// Where the format structures live in an image, per family, see `ChipFamily::layout`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub hwpointers_offset: usize,
+    /// The DTOC occupies the last `dtoc_size` bytes of a flash image
+    pub dtoc_size: usize,
+    /// Flash erase sector size, device data starts on a sector boundary
+    pub sector_size: usize,
+    /// Framing of code sections with cache-line CRCs
+    pub cache_line_geometry: CacheLineGeometry,
+}
+
+impl Layout {
+    /// The FS4 image format
+    pub const FS4: Self = Self {
+        hwpointers_offset: 0x18,
+        dtoc_size: 0x1000,
+        sector_size: 0x1000,
+        cache_line_geometry: CacheLineGeometry::new(0x40),
+    };
+}
+
+/// Images of unknown families are assumed to be FS4
+impl Default for Layout {
+    fn default() -> Self {
+        Self::FS4
+    }
+}
+
+impl ChipFamily {
+    pub fn from_pci_device_id(device_id: u16) -> Option<Self> {
+        Some(match device_id {
+            0x1017 | 0x1019 => Self::Cx5,
+            0x101b => Self::Cx6,
+            0x101d => Self::Cx6dx,
+            0x101f => Self::Cx6lx,
+            0x1021 => Self::Cx7,
+            0xa2d2 => Self::Bluefield,
+            0xa2d6 => Self::Bluefield2,
+            0xa2dc => Self::Bluefield3,
+            _ => return None,
+        })
+    }
+
+    /// Each family has its own FW branch, identified by the major version
+    pub fn from_fw_major(major: u16) -> Option<Self> {
+        Some(match major {
+            16 => Self::Cx5,
+            18 => Self::Bluefield,
+            20 => Self::Cx6,
+            22 => Self::Cx6dx,
+            24 => Self::Bluefield2,
+            26 => Self::Cx6lx,
+            28 => Self::Cx7,
+            32 => Self::Bluefield3,
+            _ => return None,
+        })
+    }
+
+    /// The layout of the family's images. A family with a different format gets its own
+    /// `Layout` constant here.
+    pub fn layout(&self) -> Layout {
+        match self {
+            Self::Cx5
+            | Self::Cx6
+            | Self::Cx6dx
+            | Self::Cx6lx
+            | Self::Cx7
+            | Self::Bluefield
+            | Self::Bluefield2
+            | Self::Bluefield3 => Layout::FS4,
+        }
+    }
+}
+
+impl std::fmt::Display for ChipFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Cx5 => "ConnectX-5",
+            Self::Cx6 => "ConnectX-6",
+            Self::Cx6dx => "ConnectX-6 Dx",
+            Self::Cx6lx => "ConnectX-6 Lx",
+            Self::Cx7 => "ConnectX-7",
+            Self::Bluefield => "BlueField",
+            Self::Bluefield2 => "BlueField-2",
+            Self::Bluefield3 => "BlueField-3",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,81 @@
+use aes::cipher::{KeyIvInit, StreamCipher};
+#[cfg(feature = "fs")]
+use serde::Deserialize;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::error::{FirmwareError, Result};
+use crate::structures::itoc::ItocEntry;
+
+/// Decrypts and encrypts the content of sections flagged `encrypted_section`. The content
+/// passed in and returned is the complete section as stored on flash, including cache-line
+/// framing.
+pub trait SectionCipher {
+    fn decrypt(&self, entry: &ItocEntry, data: &[u8]) -> Result<Vec<u8>>;
+    fn encrypt(&self, entry: &ItocEntry, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Deserialize)]
+struct KeyFile {
+    key: String,
+    iv: String,
+}
+
+/// AES-128 or AES-256 in CTR mode, restarting the counter at `iv` for every section. Keys are
+/// read from a JSON file `{"key": "<hex>", "iv": "<hex>"}`.
+#[derive(Debug, Clone)]
+pub struct AesCtrCipher {
+    key: Vec<u8>,
+    iv: [u8; 16],
+}
+
+impl AesCtrCipher {
+    pub fn new(key: Vec<u8>, iv: [u8; 16]) -> Result<Self> {
+        if key.len() != 16 && key.len() != 32 {
+            return Err(FirmwareError::InvalidKey(format!(
+                "expected 128 or 256 bits, got {} bits",
+                key.len() * 8
+            )));
+        }
+        Ok(Self { key, iv })
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_key_file(path: &Path) -> Result<Self> {
+        let key_file: KeyFile =
+            serde_json::from_slice(&std::fs::read(path).map_err(FirmwareError::file(path))?)?;
+        let iv = hex::decode(&key_file.iv)?;
+        Self::new(
+            hex::decode(&key_file.key)?,
+            iv.try_into()
+                .map_err(|_| FirmwareError::InvalidKey("expected a 128 bit IV".to_string()))?,
+        )
+    }
+
+    fn apply(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut data = data.to_vec();
+        match self.key.len() {
+            16 => ctr::Ctr128BE::<aes::Aes128>::new(self.key[..].into(), &self.iv.into())
+                .apply_keystream(&mut data),
+            32 => ctr::Ctr128BE::<aes::Aes256>::new(self.key[..].into(), &self.iv.into())
+                .apply_keystream(&mut data),
+            _ => {
+                return Err(FirmwareError::InvalidKey(
+                    "unsupported key size".to_string(),
+                ))
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl SectionCipher for AesCtrCipher {
+    fn decrypt(&self, _entry: &ItocEntry, data: &[u8]) -> Result<Vec<u8>> {
+        self.apply(data)
+    }
+
+    fn encrypt(&self, _entry: &ItocEntry, data: &[u8]) -> Result<Vec<u8>> {
+        self.apply(data)
+    }
+}
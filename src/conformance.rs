@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use mlx5fw::synthetic::images;
+
+const COMMANDS: &[&[&str]] = &[
+    &["show-sections"],
+    &["verify", "--profile", "strict"],
+    &["verify", "--profile", "boot-rom"],
+    &["verify", "--profile", "mstflint"],
+    &["show-dev-info"],
+    &["show-nv"],
+    &["show-forbidden"],
+    &["dump-boot2", "--info"],
+    &["dump-dbg-ini", "/dev/stdout"],
+    &["show-code-map"],
+    &["hashes"],
+];
+
+fn run_command(exe: &Path, image: &Path, command: &[&str]) -> Result<String> {
+    let output = Command::new(exe)
+        .arg(image)
+        .args(command)
+        .output()
+        .context("Could not run command")?;
+    Ok(format!(
+        "exit: {}\n{}",
+        output.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stdout)
+    ))
+}
+
+/// Runs every command against every conformance image and returns the deviations from the
+/// expected outputs in `expected_dir`. With `bless`, the expected outputs are rewritten instead.
+pub fn run(exe: &Path, expected_dir: &Path, bless: bool) -> Result<Vec<String>> {
+    let work_dir = std::env::temp_dir().join(format!("mlx5fw-conformance-{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir)?;
+    let mut deviations = vec![];
+
+    for (name, image) in images()? {
+        let image_path = work_dir.join(format!("{}.bin", name));
+        image.write(&image_path)?;
+
+        for command in COMMANDS {
+            let actual = run_command(exe, &image_path, command)?;
+            let command_name: Vec<String> = command
+                .iter()
+                .map(|arg| arg.trim_start_matches(['-', '/']).replace(['/', '-'], "_"))
+                .collect();
+            let expected_path = expected_dir
+                .join(name)
+                .join(format!("{}.txt", command_name.join("_")));
+            if bless {
+                std::fs::create_dir_all(expected_path.parent().unwrap())?;
+                std::fs::write(&expected_path, &actual)?;
+                continue;
+            }
+            let expected = std::fs::read_to_string(&expected_path).unwrap_or_default();
+            if expected != actual {
+                deviations.push(format!("{}: {}", name, command.join(" ")));
+            }
+        }
+    }
+
+    std::fs::remove_dir_all(&work_dir)?;
+    Ok(deviations)
+}
@@ -0,0 +1,128 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::Firmware;
+use crate::structures::version::FwVersion;
+
+pub const INDEX_FILE_NAME: &str = ".mlx5fw-index.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusEntry {
+    /// Path relative to the indexed directory
+    pub path: PathBuf,
+    pub size: usize,
+    pub sha256: String,
+    pub psid: Option<String>,
+    pub fw_version: Option<String>,
+    /// Why the file could not be parsed, if it could not
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusIndex {
+    pub entries: Vec<CorpusEntry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CorpusQuery {
+    pub fw_version: Option<FwVersion>,
+    pub psid: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl CorpusEntry {
+    fn from_file(dir: &Path, path: &Path) -> Result<Self> {
+        let firmware = Firmware::read(path)?;
+        let data = firmware.data();
+        let mut entry = Self {
+            path: path.strip_prefix(dir).unwrap_or(path).to_path_buf(),
+            size: data.len(),
+            sha256: hex::encode(Sha256::digest(data)),
+            psid: None,
+            fw_version: None,
+            error: None,
+        };
+
+        if Firmware::find_image_base(data).is_none() {
+            entry.error = Some("No image magic found".to_string());
+            return Ok(entry);
+        }
+        match firmware.image_info() {
+            Ok(image_info) => {
+                entry.psid = Some(image_info.psid());
+                entry.fw_version = Some(image_info.fw_version.to_string());
+            }
+            Err(err) => entry.error = Some(err.to_string()),
+        }
+        if entry.psid.as_deref().is_none_or(str::is_empty) {
+            if let Ok(mfg_info) = firmware.mfg_info() {
+                entry.psid = Some(mfg_info.psid());
+            }
+        }
+        Ok(entry)
+    }
+
+    pub fn matches(&self, query: &CorpusQuery) -> bool {
+        let fw_version = self
+            .fw_version
+            .as_deref()
+            .and_then(|version| version.parse::<FwVersion>().ok());
+        query
+            .fw_version
+            .is_none_or(|version| fw_version == Some(version))
+            && query
+                .psid
+                .as_deref()
+                .is_none_or(|psid| self.psid.as_deref() == Some(psid))
+            && query
+                .sha256
+                .as_deref()
+                .is_none_or(|sha256| self.sha256.eq_ignore_ascii_case(sha256))
+    }
+}
+
+pub(crate) fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).map_err(FirmwareError::file(dir))? {
+        let path = entry.map_err(FirmwareError::file(dir))?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.file_name() != Some(INDEX_FILE_NAME.as_ref()) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl CorpusIndex {
+    /// Index every file below `dir`, recursively
+    pub fn build(dir: &Path) -> Result<Self> {
+        let mut files = vec![];
+        collect_files(dir, &mut files)?;
+        files.sort();
+
+        let entries = files
+            .par_iter()
+            .map(|path| CorpusEntry::from_file(dir, path))
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(INDEX_FILE_NAME);
+        let index = std::fs::read(&path).map_err(FirmwareError::file(&path))?;
+        Ok(serde_json::from_slice(&index)?)
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(INDEX_FILE_NAME);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .map_err(FirmwareError::file(path))
+    }
+
+    pub fn find<'a>(&'a self, query: &'a CorpusQuery) -> impl Iterator<Item = &'a CorpusEntry> {
+        self.entries.iter().filter(|entry| entry.matches(query))
+    }
+}
@@ -0,0 +1,177 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+use crate::error::Result;
+use crate::firmware::Firmware;
+use crate::structures::itoc::{CrcMode, ItocEntryType};
+
+/// A mechanism that detects modification of the bytes it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protection {
+    /// The authenticated and FW window regions of the HW pointers
+    Signature,
+    /// A digest in the HASHES_TABLE
+    HashesTable,
+    /// A section CRC or the cache line CRCs of a code section
+    SectionCrc,
+    /// The CRC of a HW pointer, boot2 or a TOC entry
+    HeaderCrc,
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Protection::Signature => "signature",
+            Protection::HashesTable => "hashes table",
+            Protection::SectionCrc => "section CRC",
+            Protection::HeaderCrc => "header CRC",
+        })
+    }
+}
+
+/// Consecutive bytes covered by the same protections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoverageRange {
+    pub offset: usize,
+    pub end: usize,
+    /// Empty for unprotected bytes
+    pub protections: Vec<Protection>,
+    /// Whether all bytes are 0xff
+    pub erased: bool,
+}
+
+impl CoverageRange {
+    pub fn size(&self) -> usize {
+        self.end - self.offset
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoverageReport {
+    pub image_size: usize,
+    /// The whole image, in order
+    pub ranges: Vec<CoverageRange>,
+    /// The image has signature sections, but its HW pointers do not say what they sign. No
+    /// bytes are reported as signed then.
+    pub signature_range_unknown: bool,
+}
+
+impl CoverageReport {
+    /// Bytes covered by `protection`, or unprotected bytes for `None`
+    pub fn bytes(&self, protection: Option<Protection>) -> usize {
+        self.ranges
+            .iter()
+            .filter(|range| match protection {
+                Some(protection) => range.protections.contains(&protection),
+                None => range.protections.is_empty(),
+            })
+            .map(CoverageRange::size)
+            .sum()
+    }
+}
+
+/// Byte ranges and what protects them, possibly overlapping
+type ProtectedRanges = Vec<(Range<usize>, Protection)>;
+
+fn protected_ranges(firmware: &Firmware) -> Result<(ProtectedRanges, bool)> {
+    let itoc = firmware.itoc()?;
+    let mut ranges = vec![];
+
+    let hwpointers = firmware.hwpointers()?;
+    let signed_ranges = hwpointers.signed_ranges();
+    let signed = itoc.iter().any(|entry| {
+        matches!(
+            entry.entry_type,
+            ItocEntryType::ImageSignature256 | ItocEntryType::ImageSignature512
+        )
+    });
+    let signature_range_unknown = signed && signed_ranges.is_empty();
+    ranges.extend(
+        signed_ranges
+            .into_iter()
+            .map(|(_, range)| (range, Protection::Signature)),
+    );
+
+    if let Ok(table) = firmware.hashes_table() {
+        for hash_entry in &table.entries {
+            ranges.extend(
+                itoc.iter()
+                    .filter(|entry| entry.entry_type == hash_entry.section_type)
+                    .map(|entry| {
+                        (
+                            entry.flash_addr..entry.flash_addr + entry.size,
+                            Protection::HashesTable,
+                        )
+                    }),
+            );
+        }
+    }
+
+    let dtoc = firmware.dtoc().unwrap_or_default();
+    for entry in itoc.iter().chain(&dtoc) {
+        ranges.push((entry.0..entry.0 + 0x20, Protection::HeaderCrc));
+        let section_crc = matches!(entry.crc_mode(), CrcMode::InItocEntry | CrcMode::InSection);
+        if section_crc || entry.cache_line_crc {
+            ranges.push((
+                entry.flash_addr..entry.flash_addr + entry.size,
+                Protection::SectionCrc,
+            ));
+        }
+    }
+
+    for (i, (_, pointer)) in hwpointers.pointers().into_iter().enumerate() {
+        if !pointer.is_erased() {
+            let offset = hwpointers.0 + i * 8;
+            ranges.push((offset..offset + 8, Protection::HeaderCrc));
+        }
+    }
+    if let Some(boot2) = firmware.boot2().ok().filter(|_| !firmware.is_fs5()) {
+        ranges.push((boot2.0..boot2.0 + boot2.total_size(), Protection::HeaderCrc));
+    }
+
+    Ok((ranges, signature_range_unknown))
+}
+
+/// Which byte ranges of the image are protected by what, and which are not protected at all
+pub fn report(firmware: &Firmware) -> Result<CoverageReport> {
+    let (protected, signature_range_unknown) = protected_ranges(firmware)?;
+    let len = firmware.len();
+
+    let mut boundaries = BTreeSet::from([0, len]);
+    for (range, _) in &protected {
+        boundaries.insert(range.start.min(len));
+        boundaries.insert(range.end.min(len));
+    }
+    let boundaries: Vec<usize> = boundaries.into_iter().collect();
+
+    let mut ranges: Vec<CoverageRange> = vec![];
+    for window in boundaries.windows(2) {
+        let (offset, end) = (window[0], window[1]);
+        let protections: BTreeSet<Protection> = protected
+            .iter()
+            .filter(|(range, _)| range.contains(&offset))
+            .map(|(_, protection)| *protection)
+            .collect();
+        let range = CoverageRange {
+            offset,
+            end,
+            protections: protections.into_iter().collect(),
+            erased: firmware[offset..end].iter().all(|&byte| byte == 0xff),
+        };
+        match ranges.last_mut() {
+            Some(last) if last.protections == range.protections && last.erased == range.erased => {
+                last.end = end
+            }
+            _ => ranges.push(range),
+        }
+    }
+
+    Ok(CoverageReport {
+        image_size: len,
+        ranges,
+        signature_range_unknown,
+    })
+}
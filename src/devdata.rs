@@ -0,0 +1,147 @@
+use deku::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::Firmware;
+use crate::structures::{devinfo::Uids, itoc::ItocEntryType};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DevDataSection {
+    pub section_type: String,
+    pub occurrence: usize,
+    pub flash_addr: usize,
+    pub size: usize,
+    pub data: String,
+}
+
+/// Device-unique data of an image: every device-data section of the DTOC, stored verbatim
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DevDataBackup {
+    pub psid: Option<String>,
+    pub guids: Option<String>,
+    pub macs: Option<String>,
+    pub sections: Vec<DevDataSection>,
+}
+
+pub fn backup(firmware: &Firmware) -> Result<DevDataBackup> {
+    let dtoc = firmware.dtoc()?;
+    let mut sections: Vec<DevDataSection> = vec![];
+
+    for dtoc_entry in dtoc
+        .iter()
+        .filter(|entry| entry.entry_type.is_device_data())
+    {
+        let section_type = dtoc_entry.entry_type.to_string();
+        let occurrence = sections
+            .iter()
+            .filter(|section| section.section_type == section_type)
+            .count();
+        sections.push(DevDataSection {
+            section_type,
+            occurrence,
+            flash_addr: dtoc_entry.flash_addr,
+            size: dtoc_entry.size,
+            data: hex::encode(dtoc_entry.content().read_bytes(firmware)),
+        });
+    }
+
+    let dev_info = firmware.dev_info().ok();
+    Ok(DevDataBackup {
+        psid: firmware.mfg_info().ok().map(|mfg_info| mfg_info.psid()),
+        guids: dev_info
+            .as_ref()
+            .map(|dev_info| format!("{:#018x}", dev_info.uids.guids.uid)),
+        macs: dev_info.map(|dev_info| format!("{:#014x}", dev_info.uids.macs.uid)),
+        sections,
+    })
+}
+
+/// Write the sections of `backup` back into the DTOC sections they came from. A backup of
+/// another PSID is refused unless `any_psid`.
+pub fn restore(firmware: &mut Firmware, backup: &DevDataBackup, any_psid: bool) -> Result<()> {
+    let psid = firmware.mfg_info().ok().map(|mfg_info| mfg_info.psid());
+    if !any_psid && backup.psid != psid {
+        let show = |psid: Option<String>| psid.unwrap_or_else(|| "none".to_string());
+        return Err(FirmwareError::PsidMismatch {
+            backup: show(backup.psid.clone()),
+            image: show(psid),
+        });
+    }
+    let dtoc = firmware.dtoc()?;
+
+    for section in &backup.sections {
+        let mut dtoc_entry = dtoc
+            .iter()
+            .filter(|entry| entry.entry_type.to_string() == section.section_type)
+            .nth(section.occurrence)
+            .cloned()
+            .ok_or_else(|| FirmwareError::MissingSection {
+                section: section.section_type.clone(),
+                toc: "DTOC",
+            })?;
+        let data = hex::decode(&section.data)?;
+        if data.len() != dtoc_entry.size {
+            return Err(FirmwareError::SizeMismatch {
+                what: format!("{} section in backup", section.section_type),
+                expected: dtoc_entry.size,
+                actual: data.len(),
+            });
+        }
+
+        dtoc_entry.content().write_bytes(firmware, &data)?;
+        dtoc_entry.update_section_crc(firmware)?;
+    }
+
+    Ok(())
+}
+
+/// Keep the vendor OUI of a GUID, zero the device part
+const GUID_PLACEHOLDER_MASK: u64 = 0xffffff00_00000000;
+/// Keep the vendor OUI of a MAC address, zero the device part
+const MAC_PLACEHOLDER_MASK: u64 = 0xffff_ff000000;
+
+fn sanitize_uids(uids: &mut Uids) {
+    uids.guids.uid &= GUID_PLACEHOLDER_MASK;
+    uids.macs.uid &= MAC_PLACEHOLDER_MASK;
+}
+
+/// Replace the identifiers of the device data with placeholders: GUIDs and MACs keep only their
+/// vendor OUI, the VSD is cleared and the VPD serial number is replaced by zeros of the same
+/// length. CRCs and the VPD checksum are updated. Returns the sections that were sanitized.
+pub fn sanitize(firmware: &mut Firmware) -> Result<Vec<ItocEntryType>> {
+    if !firmware.has_dtoc() {
+        return Err(FirmwareError::MissingDtoc);
+    }
+    let mut sanitized = vec![];
+
+    if let Ok(mut dev_info) = firmware.dev_info() {
+        sanitize_uids(&mut dev_info.uids);
+        dev_info.vsd = [0x00; 208];
+        dev_info.update()?;
+        dev_info.write(firmware)?;
+        firmware
+            .dtoc_entry(ItocEntryType::DevInfo)?
+            .update_section_crc(firmware)?;
+        sanitized.push(ItocEntryType::DevInfo);
+    }
+
+    if let Ok(mut mfg_info) = firmware.mfg_info() {
+        sanitize_uids(&mut mfg_info.uids);
+        mfg_info.write(firmware)?;
+        firmware
+            .dtoc_entry(ItocEntryType::MfgInfo)?
+            .update_section_crc(firmware)?;
+        sanitized.push(ItocEntryType::MfgInfo);
+    }
+
+    if let Ok(mut vpd) = firmware.vpd() {
+        if let Some(serial) = vpd.keyword("SN") {
+            let placeholder = vec![b'0'; serial.data.len()];
+            vpd.set("SN", placeholder)?;
+            firmware.write_vpd(&vpd)?;
+            sanitized.push(ItocEntryType::VpdR0);
+        }
+    }
+
+    Ok(sanitized)
+}
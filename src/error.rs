@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+/// Errors of the library layer
+#[derive(Debug, thiserror::Error)]
+pub enum FirmwareError {
+    #[error("{what} at {offset:#x} extends past the end of the image")]
+    TruncatedImage { what: String, offset: usize },
+    #[error("{what} at {offset:#x} extends past the end of the {section} section")]
+    TruncatedSection {
+        what: String,
+        offset: usize,
+        section: String,
+    },
+    #[error("{section} CRC mismatch: expected {expected:#06x}, actual {actual:#06x}")]
+    BadCrc {
+        section: String,
+        expected: u16,
+        actual: u16,
+    },
+    #[error("Unknown section type {0}")]
+    UnknownSectionType(String),
+    #[error("No {section} section in {toc}")]
+    MissingSection { section: String, toc: &'static str },
+    #[error("DTOC not found, image does not contain device data")]
+    MissingDtoc,
+    #[error("No code sections found")]
+    NoCodeSections,
+    #[error("{what} is {actual:#x} bytes, expected {expected:#x}")]
+    SizeMismatch {
+        what: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("{field} {value:#x} does not fit its field, at most {max:#x}")]
+    FieldOverflow {
+        field: &'static str,
+        value: usize,
+        max: usize,
+    },
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Invalid version {0:?}, expected MAJOR.MINOR.SUBMINOR")]
+    InvalidVersion(String),
+    #[error("Invalid VPD at {offset:#x}: {what}")]
+    InvalidVpd { offset: usize, what: String },
+    #[error("{section} at {offset:#x}..{end:#x} would overlap {other}")]
+    SectionOverlap {
+        section: String,
+        offset: usize,
+        end: usize,
+        other: String,
+    },
+    #[error("No {size:#x} bytes of erased space left for {what}")]
+    NoSpace { what: String, size: usize },
+    #[error(
+        "{toc} entry {index} ({entry_type}) at {offset:#x}: section {flash_addr:#x}+{size:#x} \
+         lies outside the {image_size:#x} byte image"
+    )]
+    SectionOutOfBounds {
+        toc: String,
+        index: usize,
+        entry_type: String,
+        offset: usize,
+        flash_addr: usize,
+        size: usize,
+        image_size: usize,
+    },
+    #[error("{section} has unknown CRC mode {mode}, its CRC cannot be updated")]
+    UnknownCrcMode { section: String, mode: u8 },
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Refusing to write an image failing lint:\n{0}")]
+    Lint(String),
+    #[error("Refusing to write a binary image to a terminal")]
+    TerminalOutput,
+    #[error("Backup is of PSID {backup}, the image of PSID {image}")]
+    PsidMismatch { backup: String, image: String },
+    #[error("Bytes at {offset:#x} differ from the patch record")]
+    PatchMismatch { offset: usize },
+    #[error("Could not decompress {section}: {source}")]
+    Decompress {
+        section: String,
+        source: std::io::Error,
+    },
+    #[error("Could not parse structure: {0}")]
+    Deku(#[from] deku::DekuError),
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Hex(#[from] hex::FromHexError),
+    #[error(transparent)]
+    Fmt(#[from] std::fmt::Error),
+}
+
+pub type Result<T, E = FirmwareError> = std::result::Result<T, E>;
+
+impl FirmwareError {
+    /// Wrap an I/O error with the path it occurred on
+    pub fn file(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.into();
+        move |source| Self::File { path, source }
+    }
+}
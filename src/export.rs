@@ -0,0 +1,228 @@
+use serde::Serialize;
+use std::fmt::Write;
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::Firmware;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ExportFormat {
+    /// Segment description for custom loaders
+    Json,
+    /// Ghidra Python script
+    Ghidra,
+    /// IDA IDC script
+    Idc,
+}
+
+/// A code section as it appears in the address space, backed by a dump-code file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Segment {
+    pub name: String,
+    pub section_type: String,
+    pub file: String,
+    pub flash_addr: usize,
+    pub load_address: u32,
+    pub size: usize,
+    pub entry_point: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeLayout {
+    pub segments: Vec<Segment>,
+}
+
+impl CodeLayout {
+    pub fn from_firmware(firmware: &Firmware) -> Result<Self> {
+        let mut segments: Vec<Segment> = vec![];
+        for entry in firmware.code_sections()? {
+            let section_type = entry.entry_type.to_string();
+            let occurrence = segments
+                .iter()
+                .filter(|segment| segment.section_type == section_type)
+                .count();
+            segments.push(Segment {
+                name: match occurrence {
+                    0 => section_type.clone(),
+                    n => format!("{}_{}", section_type, n),
+                },
+                section_type,
+                file: entry.code_file_name(),
+                flash_addr: entry.flash_addr,
+                load_address: entry.load_address,
+                size: firmware.decoded_code(&entry).len(),
+                entry_point: entry.entry_point,
+            });
+        }
+        if segments.is_empty() {
+            return Err(FirmwareError::NoCodeSections);
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn export(&self, format: ExportFormat) -> Result<String> {
+        Ok(match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            ExportFormat::Ghidra => self.ghidra_script()?,
+            ExportFormat::Idc => self.idc_script()?,
+        })
+    }
+
+    fn ghidra_script(&self) -> Result<String> {
+        let mut script = String::new();
+        writeln!(script, "# Maps the files written by `mlx5fw dump-code`")?;
+        writeln!(script, "# @category mlx5fw")?;
+        writeln!(script, "import os")?;
+        writeln!(script, "import jarray")?;
+        writeln!(script, "from ghidra.program.model.symbol import SourceType")?;
+        writeln!(script)?;
+        writeln!(script, "SEGMENTS = [")?;
+        for segment in &self.segments {
+            writeln!(
+                script,
+                "    ({:?}, {:?}, {:#x}, {:#x}),",
+                segment.name, segment.file, segment.load_address, segment.entry_point
+            )?;
+        }
+        writeln!(script, "]")?;
+        writeln!(script)?;
+        writeln!(
+            script,
+            "dump_dir = askDirectory(\"dump-code directory\", \"Load\").getAbsolutePath()"
+        )?;
+        writeln!(script, "memory = currentProgram.getMemory()")?;
+        writeln!(
+            script,
+            "for name, file_name, load_address, entry_point in SEGMENTS:"
+        )?;
+        writeln!(
+            script,
+            "    with open(os.path.join(dump_dir, file_name), \"rb\") as f:"
+        )?;
+        writeln!(
+            script,
+            "        data = jarray.array([(ord(b) ^ 0x80) - 0x80 for b in f.read()], \"b\")"
+        )?;
+        writeln!(script, "    start = toAddr(load_address)")?;
+        writeln!(
+            script,
+            "    block = memory.createInitializedBlock(name, start, len(data), 0, monitor, False)"
+        )?;
+        writeln!(script, "    memory.setBytes(start, data)")?;
+        writeln!(script, "    block.setRead(True)")?;
+        writeln!(script, "    block.setExecute(True)")?;
+        writeln!(script, "    entry = toAddr(entry_point)")?;
+        writeln!(script, "    if block.contains(entry):")?;
+        writeln!(script, "        addEntryPoint(entry)")?;
+        writeln!(
+            script,
+            "        createLabel(entry, name + \"_entry\", True, SourceType.IMPORTED)"
+        )?;
+        Ok(script)
+    }
+
+    fn idc_script(&self) -> Result<String> {
+        let mut script = String::new();
+        writeln!(script, "// Maps the files written by `mlx5fw dump-code`")?;
+        writeln!(script, "#include <idc.idc>")?;
+        writeln!(script)?;
+        writeln!(script, "static main() {{")?;
+        writeln!(script, "    auto dir, f;")?;
+        writeln!(
+            script,
+            "    dir = ask_str(\"\", 0, \"dump-code directory\");"
+        )?;
+        for segment in &self.segments {
+            let end = segment.load_address as u64 + segment.size as u64;
+            writeln!(script)?;
+            writeln!(
+                script,
+                "    add_segm_ex({:#x}, {:#x}, 0, 1, saRelByte, scPub, ADDSEG_NOSREG);",
+                segment.load_address, end
+            )?;
+            writeln!(
+                script,
+                "    set_segm_name({:#x}, \"{}\");",
+                segment.load_address, segment.name
+            )?;
+            writeln!(
+                script,
+                "    set_segm_type({:#x}, SEG_CODE);",
+                segment.load_address
+            )?;
+            writeln!(
+                script,
+                "    f = fopen(dir + \"/{}\", \"rb\");",
+                segment.file
+            )?;
+            writeln!(
+                script,
+                "    loadfile(f, 0, {:#x}, {:#x});",
+                segment.load_address, segment.size
+            )?;
+            writeln!(script, "    fclose(f);")?;
+            if (segment.load_address as u64..end).contains(&(segment.entry_point as u64)) {
+                writeln!(
+                    script,
+                    "    add_entry({0:#x}, {0:#x}, \"{1}_entry\", 1);",
+                    segment.entry_point, segment.name
+                )?;
+            }
+        }
+        writeln!(script, "}}")?;
+        Ok(script)
+    }
+
+    /// GNU ld script with a memory region and output section per code section, so replacement
+    /// code placed in input section `.<name>` is linked to its load address and fails to link
+    /// if it outgrows the section
+    pub fn linker_script(&self) -> Result<String> {
+        let mut script = String::new();
+        writeln!(script, "/* Code sections, written by `mlx5fw export-ld` */")?;
+        writeln!(script)?;
+        writeln!(script, "MEMORY")?;
+        writeln!(script, "{{")?;
+        for segment in &self.segments {
+            writeln!(
+                script,
+                "    {} (rx) : ORIGIN = {:#010x}, LENGTH = {:#x}",
+                segment.name, segment.load_address, segment.size
+            )?;
+        }
+        writeln!(script, "}}")?;
+        writeln!(script)?;
+        writeln!(script, "SECTIONS")?;
+        writeln!(script, "{{")?;
+        for segment in &self.segments {
+            writeln!(
+                script,
+                "    .{0} : {{ KEEP(*(.{0} .{0}.*)) }} > {0}",
+                segment.name
+            )?;
+        }
+        writeln!(script, "}}")?;
+        writeln!(script)?;
+        script.push_str(&self.symbols()?);
+        Ok(script)
+    }
+
+    /// Start, end and entry point of each code section as ld symbol assignments
+    pub fn symbols(&self) -> Result<String> {
+        let mut symbols = String::new();
+        for segment in &self.segments {
+            let end = segment.load_address as u64 + segment.size as u64;
+            writeln!(
+                symbols,
+                "{}_start = {:#010x};",
+                segment.name, segment.load_address
+            )?;
+            writeln!(symbols, "{}_end = {:#010x};", segment.name, end)?;
+            writeln!(
+                symbols,
+                "{}_entry = {:#010x};",
+                segment.name, segment.entry_point
+            )?;
+        }
+        Ok(symbols)
+    }
+}
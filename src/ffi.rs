@@ -0,0 +1,190 @@
+//! C interface to the parser. Functions returning a pointer return NULL on failure, functions
+//! returning a number return -1, and `mlx5fw_last_error` then describes the failure. The header
+//! is generated with `cbindgen --config cbindgen.toml --output include/mlx5fw.h`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use deku::DekuContainerWrite;
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::{Firmware, FirmwareStructure};
+use crate::structures::itoc::ItocEntry;
+
+/// An image opened with `mlx5fw_open`
+pub struct Mlx5fwImage {
+    firmware: Firmware,
+    itoc: Vec<FirmwareStructure<ItocEntry>>,
+}
+
+/// An ITOC entry, as filled in by `mlx5fw_section_info`
+#[repr(C)]
+pub struct Mlx5fwSectionInfo {
+    /// Section type ID
+    pub entry_type: u8,
+    /// NUL terminated section type name, e.g. "MAIN_CODE"
+    pub type_name: [c_char; 32],
+    pub flash_addr: u32,
+    pub size: u32,
+    pub load_address: u32,
+    pub entry_point: u32,
+    pub cache_line_crc: bool,
+    pub encrypted: bool,
+    pub zipped: bool,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// The value of `result`, or `failed` after recording the error
+fn report<T>(result: Result<T>, failed: T) -> T {
+    result.unwrap_or_else(|err| {
+        set_last_error(err.to_string());
+        failed
+    })
+}
+
+fn invalid(what: &str) -> FirmwareError {
+    FirmwareError::InvalidArgument(what.to_string())
+}
+
+/// # Safety
+///
+/// `image` must be NULL or returned by `mlx5fw_open` and not closed
+unsafe fn image_ref<'a>(image: *const Mlx5fwImage) -> Result<&'a Mlx5fwImage> {
+    image.as_ref().ok_or_else(|| invalid("image is NULL"))
+}
+
+fn entry(image: &Mlx5fwImage, index: usize) -> Result<&FirmwareStructure<ItocEntry>> {
+    image
+        .itoc
+        .get(index)
+        .ok_or_else(|| invalid("section index out of range"))
+}
+
+/// Description of the last failure on this thread, NULL if nothing failed yet. Valid until the
+/// next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn mlx5fw_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Open an image file and read its ITOC. Close it with `mlx5fw_close`.
+///
+/// # Safety
+///
+/// `path` must be a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn mlx5fw_open(path: *const c_char) -> *mut Mlx5fwImage {
+    let open = || {
+        if path.is_null() {
+            return Err(invalid("path is NULL"));
+        }
+        let path = CStr::from_ptr(path)
+            .to_str()
+            .map_err(|_| invalid("path is not UTF-8"))?;
+        let firmware = Firmware::read(path)?;
+        let itoc = firmware.itoc()?;
+        Ok(Box::into_raw(Box::new(Mlx5fwImage { firmware, itoc })))
+    };
+    report(open(), ptr::null_mut())
+}
+
+/// # Safety
+///
+/// `image` must be NULL or returned by `mlx5fw_open` and not closed yet
+#[no_mangle]
+pub unsafe extern "C" fn mlx5fw_close(image: *mut Mlx5fwImage) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// Number of ITOC entries
+///
+/// # Safety
+///
+/// `image` must be returned by `mlx5fw_open` and not closed
+#[no_mangle]
+pub unsafe extern "C" fn mlx5fw_itoc_count(image: *const Mlx5fwImage) -> isize {
+    report(image_ref(image).map(|image| image.itoc.len() as isize), -1)
+}
+
+/// Fill `info` with ITOC entry `index`. Returns 0, or -1 on failure.
+///
+/// # Safety
+///
+/// `image` must be returned by `mlx5fw_open` and not closed, `info` must point to writable
+/// memory for one `Mlx5fwSectionInfo`
+#[no_mangle]
+pub unsafe extern "C" fn mlx5fw_section_info(
+    image: *const Mlx5fwImage,
+    index: usize,
+    info: *mut Mlx5fwSectionInfo,
+) -> i32 {
+    let fill = || {
+        let entry = entry(image_ref(image)?, index)?;
+        let info = info.as_mut().ok_or_else(|| invalid("info is NULL"))?;
+        let mut type_name = [0; 32];
+        for (c, byte) in type_name
+            .iter_mut()
+            .zip(entry.entry_type.to_string().bytes().take(31))
+        {
+            *c = byte as c_char;
+        }
+        *info = Mlx5fwSectionInfo {
+            entry_type: entry.to_bytes()?[0],
+            type_name,
+            flash_addr: entry.flash_addr as u32,
+            size: entry.size as u32,
+            load_address: entry.load_address,
+            entry_point: entry.entry_point,
+            cache_line_crc: entry.cache_line_crc,
+            encrypted: entry.encrypted_section,
+            zipped: entry.zipped_image,
+        };
+        Ok(0)
+    };
+    report(fill(), -1)
+}
+
+/// Copy up to `len` bytes of the content of section `index`, as stored on flash, to `buffer`.
+/// Returns the size of the section, which may exceed `len`, or -1 on failure. `buffer` may be
+/// NULL with `len` 0 to query the size.
+///
+/// # Safety
+///
+/// `image` must be returned by `mlx5fw_open` and not closed, `buffer` must point to `len`
+/// writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn mlx5fw_read_section(
+    image: *const Mlx5fwImage,
+    index: usize,
+    buffer: *mut u8,
+    len: usize,
+) -> isize {
+    let read = || {
+        let image = image_ref(image)?;
+        let content = entry(image, index)?.content().read_bytes(&image.firmware);
+        let copied = content.len().min(len);
+        if copied > 0 {
+            if buffer.is_null() {
+                return Err(invalid("buffer is NULL"));
+            }
+            ptr::copy_nonoverlapping(content.as_ptr(), buffer, copied);
+        }
+        Ok(content.len() as isize)
+    };
+    report(read(), -1)
+}
@@ -1,37 +1,505 @@
-use anyhow::{ensure, Result};
 use deku::prelude::*;
+use flate2::read::ZlibDecoder;
+#[cfg(feature = "fs")]
+use memmap2::{MmapMut, MmapOptions};
+use std::collections::HashMap;
+use std::io::Read;
+#[cfg(feature = "fs")]
+use std::io::{IsTerminal, Write};
+#[cfg(feature = "fs")]
 use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::{debug, trace};
 
-use crate::structures::{hwpointers::{Boot2, HwPointers}, itoc::ItocEntry};
+use crate::cacheline::CacheLineGeometry;
+#[cfg(feature = "fs")]
+use crate::changelog::{SectionInfo, SectionStatus};
+use crate::chip::{ChipFamily, Layout};
+use crate::error::{FirmwareError, Result};
+use crate::image::Image;
+#[cfg(feature = "fs")]
+use crate::patchrecord::PatchRecord;
+use crate::rominfo::RomInfo;
+use crate::structures::{
+    bootversion::{BootVersion, ImageFormat},
+    crdump::CrDumpMask,
+    devinfo::{DevInfo, MfgInfo},
+    forbidden::ForbiddenVersions,
+    hashes::HashesTable,
+    hwpointers::{Boot2, HwPointers},
+    imageinfo::ImageInfo,
+    itoc::{CrcMode, ItocEntry, ItocEntryType, TocHeader},
+    nvdata::NvTlv,
+    programmable::ProgrammableHwFw,
+    version::FwVersion,
+    vpd::Vpd,
+};
+use crate::transform::InputTransform;
 
+const IMAGE_MAGIC: [u8; 16] = [
+    0x4d, 0x54, 0x46, 0x57, 0x8c, 0xdf, 0xd0, 0x00, 0xde, 0xad, 0x92, 0x70, 0x41, 0x54, 0xbe, 0xef,
+];
+
+/// Path standing for standard input in `read` and standard output in `write`
+pub const STDIO_PATH: &str = "-";
+
+/// The secondary ITOC follows the primary one a flash sector later, as mstflint expects it
+const SECONDARY_ITOC_DISTANCE: usize = 0x1000;
+
+const IMAGE_BASE_CANDIDATES: [usize; 11] = [
+    0x0, 0x10000, 0x20000, 0x40000, 0x80000, 0x100000, 0x200000, 0x400000, 0x800000, 0x1000000,
+    0x2000000,
+];
+
+/// Append `pattern`, repeated and cut off at `len`, until `data` is `len` bytes long. Every
+/// padding of an image goes through here.
+fn pad(data: &mut Vec<u8>, len: usize, pattern: &[u8]) {
+    let missing = len.saturating_sub(data.len());
+    data.extend(pattern.iter().copied().cycle().take(missing));
+}
+
+/// Backing storage of a `Firmware`. Files are mapped copy-on-write, so large dumps are not read
+/// up front and modifications never reach the file they were read from.
+#[derive(Debug)]
+enum FirmwareData {
+    Owned(Vec<u8>),
+    #[cfg(feature = "fs")]
+    Mapped(MmapMut),
+}
+
+impl std::ops::Deref for FirmwareData {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Owned(data) => data,
+            #[cfg(feature = "fs")]
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+impl std::ops::DerefMut for FirmwareData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::Owned(data) => data,
+            #[cfg(feature = "fs")]
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+impl Clone for FirmwareData {
+    fn clone(&self) -> Self {
+        Self::Owned(self.to_vec())
+    }
+}
+
+impl PartialEq for FirmwareData {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for FirmwareData {}
+
+/// Code of sections by flash address, size and whether they have cache lines, see
+/// `Firmware::decoded_code`. Cleared whenever the image or the cache-line geometry may change.
+/// Clones start empty and every cache compares equal, it is not part of the image.
+#[derive(Debug, Default)]
+struct DecodedCache(Mutex<HashMap<DecodedKey, Arc<[u8]>>>);
+
+/// Flash address, size and `cache_line_crc` of a section
+type DecodedKey = (usize, usize, bool);
+
+impl DecodedCache {
+    fn clear(&mut self) {
+        self.0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl Clone for DecodedCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for DecodedCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for DecodedCache {}
+
+/// A flash dump or image file. Offsets into the firmware, including the ones stored in HW
+/// pointers and ITOC entries, are relative to `image_base`.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Firmware(pub Vec<u8>);
+pub struct Firmware {
+    data: FirmwareData,
+    image_base: usize,
+    /// Overrides the geometry of the chip family's layout
+    cache_line_geometry: Option<CacheLineGeometry>,
+    chip: Option<ChipFamily>,
+    /// Content as read, kept to write a patch record next to modified images
+    original: Option<Vec<u8>>,
+    terminal_output: bool,
+    lint_on_write: bool,
+    /// Write a `PatchRecord` against `original` instead of the image
+    emit_delta: bool,
+    /// Print what `write` would change instead of writing
+    dry_run: bool,
+    /// Transform undone on the data as read, see `set_input_transform`
+    input_transform: Option<InputTransform>,
+    /// Byte written to bytes no longer used and to padding, see `fill`
+    fill_byte: u8,
+    decoded: DecodedCache,
+}
 
 impl std::ops::Deref for Firmware {
-    type Target = Vec<u8>;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.data[self.image_base..]
     }
 }
 
 impl std::ops::DerefMut for Firmware {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        self.decoded.clear();
+        &mut self.data[self.image_base..]
     }
 }
 
 impl Firmware {
     pub fn from_bytes(data: Vec<u8>) -> Self {
-        Self(data)
+        Self::from_data(FirmwareData::Owned(data))
+    }
+
+    /// Dumps without the image magic are checked for it in transformed form and normalized
+    fn from_data(data: FirmwareData) -> Self {
+        let image_base = Self::find_image_base(&data).unwrap_or(0);
+        let mut firmware = Self {
+            data,
+            image_base,
+            cache_line_geometry: None,
+            chip: None,
+            original: None,
+            terminal_output: false,
+            lint_on_write: false,
+            emit_delta: false,
+            dry_run: false,
+            input_transform: None,
+            fill_byte: 0xff,
+            decoded: DecodedCache::default(),
+        };
+        if Self::find_image_base(&firmware.data).is_none() {
+            let transform = Self::detect_input_transform(&firmware.data);
+            firmware.set_input_transform(transform);
+        }
+        firmware
+    }
+
+    pub fn set_image_base(&mut self, image_base: usize) -> Result<()> {
+        if image_base >= self.data.len() {
+            return Err(FirmwareError::TruncatedImage {
+                what: "Image base".to_string(),
+                offset: image_base,
+            });
+        }
+        self.image_base = image_base;
+        self.decoded.clear();
+        Ok(())
+    }
+
+    pub fn find_image_base(data: &[u8]) -> Option<usize> {
+        IMAGE_BASE_CANDIDATES
+            .into_iter()
+            .find(|&base| data.get(base..base + IMAGE_MAGIC.len()) == Some(&IMAGE_MAGIC[..]))
+    }
+
+    /// The transform that turns `data` into an image with the magic at an image base candidate
+    pub fn detect_input_transform(data: &[u8]) -> Option<InputTransform> {
+        InputTransform::ALL.into_iter().find(|transform| {
+            IMAGE_BASE_CANDIDATES.into_iter().any(|base| {
+                data.get(base..base + IMAGE_MAGIC.len())
+                    .is_some_and(|magic| {
+                        let mut magic = magic.to_vec();
+                        transform.apply(&mut magic);
+                        magic == IMAGE_MAGIC
+                    })
+            })
+        })
+    }
+
+    /// Undo `transform` on the data as read, replacing the transform undone so far, and look
+    /// for the image base again. Written images keep the normalized byte order.
+    pub fn set_input_transform(&mut self, transform: Option<InputTransform>) {
+        if transform == self.input_transform {
+            return;
+        }
+        for transform in self.input_transform.into_iter().chain(transform) {
+            transform.apply(&mut self.data);
+        }
+        debug!(?transform, "Transforming input");
+        self.input_transform = transform;
+        self.image_base = Self::find_image_base(&self.data).unwrap_or(0);
+        self.decoded.clear();
+    }
+
+    pub fn input_transform(&self) -> Option<InputTransform> {
+        self.input_transform
+    }
+
+    pub fn image_base(&self) -> usize {
+        self.image_base
+    }
+
+    /// The cache-line geometry of the chip family, unless overridden
+    pub fn cache_line_geometry(&self) -> CacheLineGeometry {
+        self.cache_line_geometry
+            .unwrap_or(self.layout().cache_line_geometry)
+    }
+
+    /// Override the cache-line geometry of the chip family, `None` to use it again
+    pub fn set_cache_line_geometry(&mut self, geometry: Option<CacheLineGeometry>) {
+        self.cache_line_geometry = geometry;
+        self.decoded.clear();
+    }
+
+    pub fn chip(&self) -> Option<ChipFamily> {
+        self.chip
+    }
+
+    /// Select the chip family, which decides the layout used to find the format structures
+    pub fn set_chip(&mut self, chip: Option<ChipFamily>) {
+        self.chip = chip;
+        self.decoded.clear();
+    }
+
+    /// Guess the chip family from IMAGE_INFO, by PCI device id or else by FW branch
+    pub fn detect_chip(&self) -> Option<ChipFamily> {
+        let image_info = self.image_info().ok()?;
+        ChipFamily::from_pci_device_id(image_info.pci_device_id)
+            .or_else(|| ChipFamily::from_fw_major(image_info.fw_version.major))
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.chip.map(|chip| chip.layout()).unwrap_or_default()
+    }
+
+    /// The whole dump, including anything before `image_base`
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.decoded.clear();
+        &mut self.data
+    }
+
+    /// Byte to fill removed sections, gaps and padding with instead of the 0xff of erased flash.
+    /// Erased TOC slots stay 0xff, they terminate the tables.
+    pub fn set_fill_byte(&mut self, fill_byte: u8) {
+        self.fill_byte = fill_byte;
     }
 
+    pub fn fill_byte(&self) -> u8 {
+        self.fill_byte
+    }
+
+    /// Overwrite `size` bytes at `offset` that are no longer used with the fill byte
+    pub fn fill(&mut self, offset: usize, size: usize) -> Result<()> {
+        FirmwareStructure(offset, ()).write_bytes(self, &vec![self.fill_byte; size])
+    }
+
+    /// Allow `write` to standard output when it is a terminal
+    pub fn set_terminal_output(&mut self, terminal_output: bool) {
+        self.terminal_output = terminal_output;
+    }
+
+    /// Refuse to `write` images with `lint` findings
+    pub fn set_lint_on_write(&mut self, lint_on_write: bool) {
+        self.lint_on_write = lint_on_write;
+    }
+
+    /// Write a `PatchRecord` of all changes next to every image written from now on
+    pub fn record_patches(&mut self) {
+        self.original.get_or_insert_with(|| self.data.to_vec());
+    }
+
+    /// Make `write` write a `PatchRecord` of all changes from now on, instead of the image
+    pub fn emit_delta(&mut self) {
+        self.record_patches();
+        self.emit_delta = true;
+    }
+
+    /// Make `write` print the changed byte ranges and sections to standard output from now on,
+    /// after the checks it makes, instead of writing anything
+    pub fn dry_run(&mut self) {
+        self.record_patches();
+        self.dry_run = true;
+    }
+
+    /// Read an image file, or standard input if `path` is `-`. Files are mapped, falling back to
+    /// reading them for pipes and other files that cannot be mapped.
+    #[cfg(feature = "fs")]
     pub fn read(path: impl AsRef<Path>) -> Result<Self> {
-        Ok(Self(std::fs::read(path)?))
+        let path = path.as_ref();
+        if path == Path::new(STDIO_PATH) {
+            let mut data = vec![];
+            std::io::stdin()
+                .read_to_end(&mut data)
+                .map_err(FirmwareError::file(path))?;
+            return Ok(Self::from_bytes(data));
+        }
+        let file = std::fs::File::open(path).map_err(FirmwareError::file(path))?;
+        let is_mappable = file
+            .metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0);
+        if is_mappable {
+            // SAFETY: the mapping is private, changes made to the file by other processes while
+            // it is mapped are the one thing this cannot guard against.
+            if let Ok(map) = unsafe { MmapOptions::new().map_copy(&file) } {
+                return Ok(Self::from_data(FirmwareData::Mapped(map)));
+            }
+        }
+        let data = std::fs::read(path).map_err(FirmwareError::file(path))?;
+        Ok(Self::from_bytes(data))
     }
 
+    /// Write the image to a file, or to standard output if `path` is `-`. No patch record is
+    /// written for standard output.
+    #[cfg(feature = "fs")]
     pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
-        Ok(std::fs::write(path, &self.0)?)
+        let path = path.as_ref();
+        if self.lint_on_write {
+            let findings = crate::lint::lint(self);
+            if !findings.is_empty() {
+                let findings: Vec<String> = findings.iter().map(|f| format!("  {}", f)).collect();
+                return Err(FirmwareError::Lint(findings.join("\n")));
+            }
+        }
+        if let (Some(original), true) = (&self.original, self.dry_run) {
+            tracing::info!("Dry run, not writing {}", path.display());
+            for change in self.describe_changes(original)?.lines() {
+                tracing::info!("{}", change);
+            }
+            return Ok(());
+        }
+        let stdio = path == Path::new(STDIO_PATH);
+        // Diffed before anything is written, so a resized image fails without output
+        let record = match &self.original {
+            Some(original) if self.emit_delta || !stdio => {
+                Some(PatchRecord::diff(original, &self.data)?)
+            }
+            _ => None,
+        };
+        let delta = match (&record, self.emit_delta) {
+            (Some(record), true) => Some(record.to_json()?),
+            _ => None,
+        };
+        if stdio {
+            let mut stdout = std::io::stdout().lock();
+            if stdout.is_terminal() && !self.terminal_output && delta.is_none() {
+                return Err(FirmwareError::TerminalOutput);
+            }
+            stdout
+                .write_all(delta.as_deref().unwrap_or(&self.data))
+                .and_then(|_| stdout.flush())
+                .map_err(FirmwareError::file(path))?;
+            return Ok(());
+        }
+        if let Some(delta) = delta {
+            debug!(path = %path.display(), size = delta.len(), "Writing delta");
+            return std::fs::write(path, delta).map_err(FirmwareError::file(path));
+        }
+        debug!(path = %path.display(), size = self.data.len(), "Writing image");
+        // Copy out first, `path` may be the file this firmware is mapped from
+        let data = self.data.to_vec();
+        std::fs::write(path, data).map_err(FirmwareError::file(path))?;
+        if let Some(record) = record {
+            record.write(&PatchRecord::path_for(path))?;
+        }
+        Ok(())
+    }
+
+    /// Changed byte ranges and sections since `original`, one per line
+    #[cfg(feature = "fs")]
+    fn describe_changes(&self, original: &[u8]) -> Result<String> {
+        let mut changes = String::new();
+        if original.len() == self.data.len() {
+            for range in PatchRecord::diff(original, &self.data)?.ranges {
+                changes += &format!(
+                    "  {:#010x} +{:#x} bytes\n",
+                    range.offset,
+                    range.original.len() / 2
+                );
+            }
+        } else {
+            changes += &format!(
+                "  size {:#x} -> {:#x} bytes\n",
+                original.len(),
+                self.data.len()
+            );
+        }
+        let mut old = Firmware::from_bytes(original.to_vec());
+        old.set_image_base(self.image_base)?;
+        old.set_chip(self.chip);
+        old.set_cache_line_geometry(self.cache_line_geometry);
+        // Sections are only listed if both ITOCs can still be read
+        let Ok(changelog) = crate::changelog::changelog(&old, self) else {
+            return Ok(changes);
+        };
+        let crc = |firmware: &Firmware, index: Option<usize>| {
+            let entry = firmware.itoc().ok()?.into_iter().nth(index?)?;
+            entry.stored_section_crc(firmware)
+        };
+        let show_crc = |crc: Option<u16>| {
+            crc.map_or_else(|| "none".to_string(), |crc| format!("{:#06x}", crc))
+        };
+        let location = |info: &Option<SectionInfo>| {
+            info.as_ref().map_or_else(
+                || "-".to_string(),
+                |info| format!("{:#010x}+{:#x}", info.flash_addr, info.size),
+            )
+        };
+        for change in changelog.sections {
+            let (old_location, new_location) = (location(&change.old), location(&change.new));
+            // Sections only moved within the ITOC are covered by the byte ranges
+            if change.status == SectionStatus::Unchanged && old_location == new_location {
+                continue;
+            }
+            let (old_index, new_index) = (
+                change.old.as_ref().map(|info| info.index),
+                change.new.as_ref().map(|info| info.index),
+            );
+            changes += &format!(
+                "  {} #{} {}: {} -> {}, CRC {} -> {}\n",
+                change.section_type,
+                change.occurrence,
+                format!("{:?}", change.status).to_lowercase(),
+                old_location,
+                new_location,
+                show_crc(crc(&old, old_index)),
+                show_crc(crc(self, new_index))
+            );
+        }
+        Ok(changes)
+    }
+
+    /// Borrow `size` bytes at `offset`, failing instead of panicking if they are out of bounds
+    pub fn bytes(&self, offset: usize, size: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(size)
+            .and_then(|end| self.get(offset..end))
+            .ok_or_else(|| FirmwareError::TruncatedImage {
+                what: format!("{:#x} byte range", size),
+                offset,
+            })
     }
 
     pub fn slice(&self, offset: usize, size: usize) -> FirmwareStructure<&[u8]> {
@@ -43,26 +511,432 @@ impl Firmware {
     }
 
     pub fn hwpointers(&self) -> Result<FirmwareStructure<HwPointers>> {
-        Ok(FirmwareStructure::read(self, 0x18)?)
+        FirmwareStructure::read(self, self.layout().hwpointers_offset)
     }
 
     pub fn boot2(&self) -> Result<FirmwareStructure<Boot2>> {
         let hwpointers = self.hwpointers()?;
-        Ok(FirmwareStructure::read(self ,hwpointers.boot2.ptr)?)
+        FirmwareStructure::read(self, hwpointers.boot2.ptr)
+    }
+
+    pub fn boot_version(&self) -> Result<FirmwareStructure<BootVersion>> {
+        FirmwareStructure::read(self, BootVersion::OFFSET)
+    }
+
+    /// The format the boot version gives, FS4 if it has none
+    pub fn image_format(&self) -> ImageFormat {
+        self.boot_version()
+            .ok()
+            .and_then(|boot_version| boot_version.image_format())
+            .unwrap_or(ImageFormat::Fs4)
+    }
+
+    /// FS5 images are only detected. Their boot component header is not parsed yet, so boot2
+    /// and what depends on it are unavailable for them.
+    pub fn is_fs5(&self) -> bool {
+        self.image_format() == ImageFormat::Fs5
     }
 
     pub fn itoc(&self) -> Result<Vec<FirmwareStructure<ItocEntry>>> {
-        let hwpointers = self.hwpointers()?;
-        let mut itoc = vec![];
+        self.itoc_entries().lenient().collect()
+    }
+
+    /// Offset of the ITOC header, as the HW pointers give it
+    pub fn itoc_offset(&self) -> Result<usize> {
+        Ok(self.hwpointers()?.toc.ptr)
+    }
+
+    /// The ITOC entries, failing on the first entry with a bad `itoc_entry_crc` or a section
+    /// outside the image. Use `TocEntries::lenient` to read entries regardless of their CRC.
+    pub fn itoc_entries(&self) -> TocEntries<'_> {
+        TocEntries::new(self, "ITOC", self.itoc_offset())
+    }
+
+    pub fn itoc_header(&self) -> Result<FirmwareStructure<TocHeader>> {
+        FirmwareStructure::read(self, self.itoc_offset()?)
+    }
+
+    /// Offset of the copy of the ITOC kept one sector after the primary for failsafe updates,
+    /// if a valid ITOC header is found there
+    pub fn secondary_itoc_offset(&self) -> Option<usize> {
+        let offset = self.itoc_offset().ok()? + SECONDARY_ITOC_DISTANCE;
+        FirmwareStructure::<TocHeader>::read(self, offset)
+            .ok()
+            .filter(|header| header.is_valid(b"ITOC"))
+            .map(|_| offset)
+    }
+
+    /// The entries of the secondary ITOC, checked like `itoc_entries`
+    pub fn secondary_itoc_entries(&self) -> TocEntries<'_> {
+        let toc_offset = self
+            .secondary_itoc_offset()
+            .ok_or(FirmwareError::MissingSection {
+                section: "Secondary ITOC".to_string(),
+                toc: "ITOC",
+            });
+        TocEntries::new(self, "secondary ITOC", toc_offset)
+    }
+
+    /// Rewrite the ITOC with `entries`, in the given order. The table may grow into the erased
+    /// slots following it, slots no longer used are erased.
+    pub fn write_itoc(&mut self, entries: &[ItocEntry]) -> Result<()> {
+        let first = self.itoc_offset()? + 0x20;
+        let used = self.itoc()?.len();
+        let capacity = used
+            + (first + used * 0x20..)
+                .step_by(0x20)
+                .take_while(|&offset| {
+                    self.get(offset..offset + 0x20)
+                        .is_some_and(|slot| slot == [0xffu8; 0x20])
+                })
+                .count();
+        if entries.len() >= capacity {
+            return Err(FirmwareError::SizeMismatch {
+                what: "ITOC".to_string(),
+                expected: capacity.saturating_sub(1) * 0x20,
+                actual: entries.len() * 0x20,
+            });
+        }
+
+        for (i, entry) in entries.iter().enumerate() {
+            let mut entry = FirmwareStructure(first + i * 0x20, entry.clone());
+            entry.update()?;
+            entry.write(self)?;
+        }
+        let erased = first + entries.len() * 0x20..first + used.max(entries.len()) * 0x20 + 0x20;
+        FirmwareStructure(erased.start, ()).write_bytes(self, &vec![0xff; erased.len()])?;
+        Ok(())
+    }
+
+    pub fn itoc_entry(&self, entry_type: ItocEntryType) -> Result<FirmwareStructure<ItocEntry>> {
+        self.itoc()?
+            .into_iter()
+            .find(|entry| entry.entry_type == entry_type)
+            .ok_or_else(|| FirmwareError::MissingSection {
+                section: entry_type.to_string(),
+                toc: "ITOC",
+            })
+    }
+
+    pub fn sections_of_type(
+        &self,
+        entry_type: ItocEntryType,
+    ) -> Result<Vec<FirmwareStructure<ItocEntry>>> {
+        Ok(self
+            .itoc()?
+            .into_iter()
+            .filter(|entry| entry.entry_type == entry_type)
+            .collect())
+    }
+
+    pub fn code_sections(&self) -> Result<Vec<FirmwareStructure<ItocEntry>>> {
+        Ok(self
+            .itoc()?
+            .into_iter()
+            .filter(|entry| entry.entry_type.is_code())
+            .collect())
+    }
+
+    /// Content of a code section with the cache-line padding and CRCs stripped
+    pub fn section_code(&self, entry: &ItocEntry) -> Vec<u8> {
+        self.decoded_code(entry).to_vec()
+    }
+
+    /// Like `section_code`, but decoded once and shared by later calls for the same section
+    /// until the image changes
+    pub fn decoded_code(&self, entry: &ItocEntry) -> Arc<[u8]> {
+        let key = (entry.flash_addr, entry.size, entry.cache_line_crc);
+        let mut decoded = self
+            .decoded
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        decoded
+            .entry(key)
+            .or_insert_with(|| {
+                self.code_from_content(entry, entry.content().read_bytes(self))
+                    .into()
+            })
+            .clone()
+    }
+
+    /// Strip the cache-line framing off section `content` if the section has one
+    pub fn code_from_content(&self, entry: &ItocEntry, content: &[u8]) -> Vec<u8> {
+        if !entry.cache_line_crc {
+            return content.to_vec();
+        }
+        self.cache_line_geometry().strip(content)
+    }
+
+    pub fn read_section<'a, T: DekuContainerRead<'a>>(
+        &'a self,
+        entry: &ItocEntry,
+    ) -> Result<FirmwareStructure<T>> {
+        FirmwareStructure::read(self, entry.flash_addr)
+    }
+
+    /// Offset the DTOC header is expected at, a fixed distance from the end of the image. No HW
+    /// pointer refers to it. See `has_dtoc` for whether there is one.
+    pub fn dtoc_offset(&self) -> Option<usize> {
+        self.len().checked_sub(self.layout().dtoc_size)
+    }
+
+    pub fn has_dtoc(&self) -> bool {
+        self.dtoc_offset()
+            .is_some_and(|offset| self[offset..].starts_with(b"DTOC"))
+    }
+
+    /// Start of the device data at the end of the image: the DTOC and the sections it lists
+    pub fn device_data_offset(&self) -> Option<usize> {
+        let dtoc_offset = self.dtoc_offset().filter(|_| self.has_dtoc())?;
+        let dtoc = self.dtoc().ok()?;
+        Some(
+            dtoc.iter()
+                .map(|entry| entry.flash_addr)
+                .filter(|&flash_addr| flash_addr < dtoc_offset)
+                .fold(dtoc_offset, usize::min),
+        )
+    }
+
+    /// Truncate or pad the image with the fill byte to `size` bytes, moving the device data to the new
+    /// end of the image and relocating its DTOC entries. Anything else past `size` is dropped.
+    pub fn resize(&mut self, size: usize) -> Result<()> {
+        let old_start = self.device_data_offset().unwrap_or(self.len());
+        let dtoc = if self.has_dtoc() {
+            self.dtoc()?
+        } else {
+            vec![]
+        };
+        let device_data = self[old_start..].to_vec();
+        let new_start =
+            size.checked_sub(device_data.len())
+                .ok_or_else(|| FirmwareError::SizeMismatch {
+                    what: "Resized image".to_string(),
+                    expected: device_data.len(),
+                    actual: size,
+                })?;
+
+        debug!(
+            size = self.len(),
+            new_size = size,
+            device_data = old_start,
+            new_device_data = new_start,
+            "Resizing image"
+        );
+        let mut data = self.data.to_vec();
+        data.truncate(self.image_base + old_start.min(new_start));
+        pad(&mut data, self.image_base + new_start, &[self.fill_byte]);
+        data.extend_from_slice(&device_data);
+        self.data = FirmwareData::Owned(data);
+        self.decoded.clear();
+
+        let relocate = |offset: usize| offset - old_start + new_start;
+        for mut entry in dtoc {
+            entry.0 = relocate(entry.0);
+            if entry.flash_addr >= old_start && entry.flash_addr < old_start + device_data.len() {
+                entry.flash_addr = relocate(entry.flash_addr);
+                entry.update()?;
+            }
+            entry.write(self)?;
+        }
+        Ok(())
+    }
+
+    /// Drop everything past `size`, including the device data. Use `resize` to keep it.
+    pub fn truncate(&mut self, size: usize) {
+        if size >= self.len() {
+            return;
+        }
+        debug!(size = self.len(), new_size = size, "Truncating image");
+        let mut data = self.data.to_vec();
+        data.truncate(self.image_base + size);
+        self.data = FirmwareData::Owned(data);
+        self.decoded.clear();
+    }
+
+    /// Append `pattern`, repeated and cut off at `size`, until the image is `size` bytes long.
+    /// Unlike `resize`, nothing is moved.
+    pub fn pad_to(&mut self, size: usize, pattern: &[u8]) {
+        if size <= self.len() {
+            return;
+        }
+        let mut data = self.data.to_vec();
+        pad(&mut data, self.image_base + size, pattern);
+        self.data = FirmwareData::Owned(data);
+        self.decoded.clear();
+    }
+
+    pub fn dtoc(&self) -> Result<Vec<FirmwareStructure<ItocEntry>>> {
+        self.dtoc_entries().lenient().collect()
+    }
+
+    /// The DTOC entries, checked like `itoc_entries`
+    pub fn dtoc_entries(&self) -> TocEntries<'_> {
+        let toc_offset = match self.has_dtoc() {
+            true => Ok(self.len() - self.layout().dtoc_size),
+            false => Err(FirmwareError::MissingDtoc),
+        };
+        TocEntries::new(self, "DTOC", toc_offset)
+    }
+
+    pub fn parse(&self) -> Image<'_> {
+        Image::parse(self)
+    }
+
+    /// Fail if a section at `offset..offset + size` would lie outside the image or overwrite
+    /// another structure: the HW pointers, boot2, a TOC or another section
+    pub fn check_section_range(&self, section: &str, offset: usize, size: usize) -> Result<()> {
+        let overlap = |other: String| FirmwareError::SectionOverlap {
+            section: section.to_string(),
+            offset,
+            end: offset + size,
+            other,
+        };
+        if offset + size > self.len() {
+            return Err(overlap(format!(
+                "the end of the image at {:#x}",
+                self.len()
+            )));
+        }
+        match self.parse().overlap(offset, size) {
+            Some(region) => Err(overlap(format!(
+                "{} at {:#x}..{:#x}",
+                region.name,
+                region.offset,
+                region.offset + region.size
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn dtoc_entry(&self, entry_type: ItocEntryType) -> Result<FirmwareStructure<ItocEntry>> {
+        self.dtoc()?
+            .into_iter()
+            .find(|entry| entry.entry_type == entry_type)
+            .ok_or_else(|| FirmwareError::MissingSection {
+                section: entry_type.to_string(),
+                toc: "DTOC",
+            })
+    }
+
+    pub fn vpd(&self) -> Result<FirmwareStructure<Vpd>> {
+        let entry = self.dtoc_entry(ItocEntryType::VpdR0)?;
+        let vpd = Vpd::parse(entry.content().read_bytes(self))?;
+        Ok(FirmwareStructure(entry.flash_addr, vpd))
+    }
+
+    /// Replace the VPD_R0 content, the rest of the section is erased
+    pub fn write_vpd(&mut self, vpd: &Vpd) -> Result<()> {
+        let mut entry = self.dtoc_entry(ItocEntryType::VpdR0)?;
+        let mut content = vpd.to_bytes();
+        if content.len() > entry.payload().1 {
+            return Err(FirmwareError::SizeMismatch {
+                what: "VPD".to_string(),
+                expected: entry.payload().1,
+                actual: content.len(),
+            });
+        }
+        content.resize(entry.payload().1, 0xff);
+        FirmwareStructure(entry.flash_addr, ()).write_bytes(self, &content)?;
+        entry.update_section_crc(self)
+    }
+
+    pub fn dev_info(&self) -> Result<FirmwareStructure<DevInfo>> {
+        let entry = self.dtoc_entry(ItocEntryType::DevInfo)?;
+        self.read_section(&entry)
+    }
+
+    pub fn image_info(&self) -> Result<FirmwareStructure<ImageInfo>> {
+        let entry = self.itoc_entry(ItocEntryType::ImageInfo)?;
+        self.read_section(&entry)
+    }
 
-        for offset in (hwpointers.toc.ptr + 0x20..).step_by(0x20) {
-            if self[offset..offset + 0x20] == [0xffu8; 0x20] {
+    pub fn version(&self) -> Result<FwVersion> {
+        Ok(self.image_info()?.fw_version)
+    }
+
+    pub fn nv_tlvs(&self, entry: &ItocEntry) -> Result<Vec<FirmwareStructure<NvTlv>>> {
+        let content = entry.content().read_bytes(self);
+        let mut tlvs = vec![];
+
+        let mut offset = 0;
+        while offset + NvTlv::HEADER_SIZE <= content.len() {
+            if content[offset..offset + 4] == [0xffu8; 4] {
                 break;
             }
-            itoc.push(FirmwareStructure::read(self, offset)?);
+            let truncated = || FirmwareError::TruncatedSection {
+                what: "TLV".to_string(),
+                offset: entry.flash_addr + offset,
+                section: entry.entry_type.to_string(),
+            };
+            let tlv: FirmwareStructure<NvTlv> =
+                FirmwareStructure(entry.flash_addr + offset, &content[offset..])
+                    .decode()
+                    .map_err(|err| match err {
+                        FirmwareError::Deku(DekuError::Incomplete(_)) => truncated(),
+                        err => err,
+                    })?;
+            if offset + tlv.total_size() > content.len() {
+                return Err(truncated());
+            }
+            offset += tlv.total_size();
+            tlvs.push(tlv);
         }
 
-        Ok(itoc)
+        Ok(tlvs)
+    }
+
+    /// The raw contents of the DTOC's FW_NV_LOG section. Its record layout is unknown, a
+    /// decoder is still to be written once it is known.
+    pub fn nv_log(&self) -> Result<FirmwareStructure<&[u8]>> {
+        let entry = self.dtoc_entry(ItocEntryType::FwNvLog)?;
+        Ok(FirmwareStructure(
+            entry.flash_addr,
+            entry.content().read_bytes(self),
+        ))
+    }
+
+    pub fn forbidden_versions(&self) -> Result<FirmwareStructure<ForbiddenVersions>> {
+        let entry = self.itoc_entry(ItocEntryType::ForbiddenVersions)?;
+        self.read_section(&entry)
+    }
+
+    /// Versions of the expansion ROMs in ROM_CODE
+    pub fn rom_info(&self) -> Result<Vec<RomInfo>> {
+        let entry = self.itoc_entry(ItocEntryType::RomCode)?;
+        Ok(RomInfo::parse(entry.content().read_bytes(self)))
+    }
+
+    pub fn dbg_fw_ini(&self) -> Result<String> {
+        let entry = self.itoc_entry(ItocEntryType::DbgFwIni)?;
+        let mut ini = String::new();
+        ZlibDecoder::new(entry.content().read_bytes(self))
+            .read_to_string(&mut ini)
+            .map_err(|source| FirmwareError::Decompress {
+                section: entry.entry_type.to_string(),
+                source,
+            })?;
+        Ok(ini)
+    }
+
+    pub fn programmable_hw_fw(&self) -> Result<FirmwareStructure<ProgrammableHwFw>> {
+        let entry = self.itoc_entry(ItocEntryType::ProgrammableHwFw)?;
+        self.read_section(&entry)
+    }
+
+    pub fn hashes_table(&self) -> Result<FirmwareStructure<HashesTable>> {
+        let entry = self.itoc_entry(ItocEntryType::HashesTable)?;
+        self.read_section(&entry)
+    }
+
+    pub fn crdump_mask(&self) -> Result<FirmwareStructure<CrDumpMask>> {
+        let entry = self.itoc_entry(ItocEntryType::CrDumpMaskData)?;
+        self.read_section(&entry)
+    }
+
+    pub fn mfg_info(&self) -> Result<FirmwareStructure<MfgInfo>> {
+        let entry = self.dtoc_entry(ItocEntryType::MfgInfo)?;
+        self.read_section(&entry)
     }
 }
 
@@ -85,18 +959,166 @@ impl<T> std::ops::DerefMut for FirmwareStructure<T> {
 
 impl<T> FirmwareStructure<T> {
     pub fn write_bytes(&self, firmware: &mut Firmware, value: &[u8]) -> Result<()> {
-        ensure!(
-            self.0 + value.len() < firmware.len(),
-            "Firmware structure out of bounds"
+        let Some(end) = self
+            .0
+            .checked_add(value.len())
+            .filter(|&end| end <= firmware.len())
+        else {
+            return Err(FirmwareError::TruncatedImage {
+                what: "Firmware structure".to_string(),
+                offset: self.0,
+            });
+        };
+        debug!(offset = %format_args!("{:#x}", self.0), size = value.len(), "Writing bytes");
+        trace!(
+            offset = %format_args!("{:#x}", self.0),
+            original = hex::encode(&firmware[self.0..end]),
+            new = hex::encode(value)
         );
-        firmware[self.0..self.0 + value.len()].copy_from_slice(value);
+        firmware[self.0..end].copy_from_slice(value);
         Ok(())
     }
 }
 
+/// Iterator over the entries of an ITOC or DTOC, up to the first erased slot. Iteration ends
+/// after the first error.
+#[derive(Debug)]
+pub struct TocEntries<'a> {
+    firmware: &'a Firmware,
+    /// "ITOC" or "DTOC", for errors
+    toc: &'static str,
+    /// Offset of the next entry, or why the table could not be located
+    next: Option<Result<usize>>,
+    index: usize,
+    check_crc: bool,
+    check_bounds: bool,
+}
+
+impl<'a> TocEntries<'a> {
+    fn new(firmware: &'a Firmware, toc: &'static str, toc_offset: Result<usize>) -> Self {
+        Self {
+            firmware,
+            toc,
+            next: Some(toc_offset.map(|offset| offset + 0x20)),
+            index: 0,
+            check_crc: true,
+            check_bounds: true,
+        }
+    }
+
+    /// Sections must lie within the image, so their content can be sliced without checks
+    fn check_bounds(&self, entry: &FirmwareStructure<ItocEntry>) -> Result<()> {
+        if entry.end().is_some_and(|end| end <= self.firmware.len()) {
+            return Ok(());
+        }
+        Err(FirmwareError::SectionOutOfBounds {
+            toc: self.toc.to_string(),
+            index: self.index,
+            entry_type: entry.entry_type.to_string(),
+            offset: entry.0,
+            flash_addr: entry.flash_addr,
+            size: entry.size,
+            image_size: self.firmware.len(),
+        })
+    }
+
+    /// Yield entries with a bad `itoc_entry_crc` instead of failing on them
+    pub fn lenient(mut self) -> Self {
+        self.check_crc = false;
+        self
+    }
+
+    /// Yield entries of sections reaching past the end of the image instead of failing on
+    /// them. Their content must not be sliced without checks.
+    pub fn truncated(mut self) -> Self {
+        self.check_bounds = false;
+        self
+    }
+}
+
+impl Iterator for TocEntries<'_> {
+    type Item = Result<FirmwareStructure<ItocEntry>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = match self.next.take()? {
+            Ok(offset) => offset,
+            Err(err) => return Some(Err(err)),
+        };
+        let Some(slot) = self.firmware.get(offset..offset + 0x20) else {
+            return Some(Err(FirmwareError::TruncatedImage {
+                what: "TOC".to_string(),
+                offset,
+            }));
+        };
+        if slot == [0xffu8; 0x20] {
+            return None;
+        }
+        let entry = FirmwareStructure::<ItocEntry>::read(self.firmware, offset).and_then(|entry| {
+            if self.check_crc {
+                entry.check_itoc_entry_crc()?;
+            }
+            if self.check_bounds {
+                self.check_bounds(&entry)?;
+            }
+            Ok(entry)
+        });
+        if entry.is_ok() {
+            self.next = Some(Ok(offset + 0x20));
+            self.index += 1;
+        }
+        Some(entry)
+    }
+}
+
+impl FirmwareStructure<ItocEntry> {
+    /// Recompute the section CRC and store it where the entry's CRC mode says, then write the
+    /// entry with its updated CRCs
+    pub fn update_section_crc(&mut self, firmware: &mut Firmware) -> Result<()> {
+        self.store_section_crc(firmware)?;
+        self.update()?;
+        self.write(firmware)
+    }
+
+    /// Recompute the section CRC and store it where the entry's CRC mode says, without writing
+    /// the entry. In-section CRCs are written to the image, `section_crc` is only set on `self`.
+    pub fn store_section_crc(&mut self, firmware: &mut Firmware) -> Result<()> {
+        let crc = self.calc_section_crc(firmware);
+        match self.crc_mode() {
+            CrcMode::InItocEntry => self.section_crc = crc,
+            CrcMode::None => {}
+            CrcMode::InSection => {
+                let payload = self.payload();
+                let tail = FirmwareStructure(payload.0 + payload.1, ());
+                tail.write_bytes(firmware, &(crc as u32).to_be_bytes())?;
+            }
+            CrcMode::Unknown(mode) => {
+                return Err(FirmwareError::UnknownCrcMode {
+                    section: format!("{} at {:#x}", self.entry_type, self.flash_addr),
+                    mode,
+                })
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FirmwareStructure<ImageInfo> {
+    /// Write IMAGE_INFO and update its section CRC. IMAGE_INFO carries no CRC of its own, so
+    /// `update` leaves it unchanged and the CRC goes to its ITOC entry.
+    pub fn write_section(&self, firmware: &mut Firmware) -> Result<()> {
+        self.write(firmware)?;
+        firmware
+            .itoc_entry(ItocEntryType::ImageInfo)?
+            .update_section_crc(firmware)
+    }
+}
+
 impl FirmwareStructure<usize> {
+    /// The bytes within the image, cut off at its end. Verification reports structures reaching
+    /// past the end.
     pub fn read_bytes<'a>(&self, firmware: &'a Firmware) -> &'a [u8] {
-        &firmware.0[self.0..][..self.1]
+        let rest = firmware.get(self.0..).unwrap_or_default();
+        &rest[..self.1.min(rest.len())]
     }
 }
 
@@ -109,7 +1131,17 @@ impl<'a> FirmwareStructure<&'a [u8]> {
 
 impl<'a, T: DekuContainerRead<'a>> FirmwareStructure<T> {
     pub fn read(firmware: &'a Firmware, offset: usize) -> Result<Self> {
-        let inner = T::from_bytes((&firmware[offset..], 0))?.1;
+        let bytes = firmware
+            .get(offset..)
+            .ok_or_else(|| FirmwareError::TruncatedImage {
+                what: std::any::type_name::<T>()
+                    .rsplit("::")
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                offset,
+            })?;
+        let inner = T::from_bytes((bytes, 0))?.1;
         Ok(Self(offset, inner))
     }
 }
@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use crate::error::Result;
+use crate::firmware::{Firmware, FirmwareStructure};
+use crate::image::Image;
+use crate::structures::itoc::ItocEntry;
+
+/// Attribute list with an optional label, red if not `ok`
+fn attributes(label: Option<&str>, ok: bool) -> String {
+    let mut attributes = vec![];
+    if let Some(label) = label {
+        attributes.push(format!("label=\"{}\"", label.replace('"', "\\\"")));
+    }
+    if !ok {
+        attributes.push("color=red, fontcolor=red".to_string());
+    }
+    match attributes.is_empty() {
+        true => String::new(),
+        false => format!(" [{}]", attributes.join(", ")),
+    }
+}
+
+fn crc_status(stored: Option<u16>, calculated: u16) -> (&'static str, bool) {
+    match stored {
+        None => ("no CRC", true),
+        Some(stored) if stored == calculated => ("CRC ok", true),
+        Some(_) => ("CRC BAD", false),
+    }
+}
+
+fn node(dot: &mut String, id: &str, lines: &[String], ok: bool) -> Result<()> {
+    let label = lines.join("\\n");
+    writeln!(dot, "  {}{};", id, attributes(Some(&label), ok))?;
+    Ok(())
+}
+
+fn toc(
+    dot: &mut String,
+    firmware: &Firmware,
+    name: &str,
+    offset: usize,
+    toc: &[FirmwareStructure<ItocEntry>],
+) -> Result<()> {
+    let id = name.to_lowercase();
+    node(
+        dot,
+        &id,
+        &[
+            name.to_string(),
+            format!("{:#x}", offset),
+            format!("{} entries", toc.len()),
+        ],
+        true,
+    )?;
+    for (i, entry) in toc.iter().enumerate() {
+        let (crc, crc_ok) = crc_status(
+            entry.stored_section_crc(firmware),
+            entry.calc_section_crc(firmware),
+        );
+        let section = format!("{}_{}", id, i);
+        node(
+            dot,
+            &section,
+            &[
+                format!("{}: {}", i, entry.entry_type),
+                format!("{:#x}, {:#x} bytes", entry.flash_addr, entry.size),
+                crc.to_string(),
+            ],
+            crc_ok,
+        )?;
+        let ok = entry.itoc_entry_crc_valid();
+        let label = (!ok).then_some("entry CRC BAD");
+        writeln!(dot, "  {} -> {}{};", id, section, attributes(label, ok))?;
+    }
+    Ok(())
+}
+
+/// Graphviz DOT of the image layout: HW pointers to boot2 and the ITOC, the TOCs to their
+/// sections. Structures with bad CRCs are red.
+pub fn dot(firmware: &Firmware) -> Result<String> {
+    let image = Image::parse(firmware);
+    let mut dot = String::new();
+    writeln!(dot, "digraph image {{")?;
+    writeln!(dot, "  rankdir=LR;")?;
+    writeln!(dot, "  node [shape=box, fontname=monospace];")?;
+    node(
+        &mut dot,
+        "image",
+        &["image".to_string(), format!("{:#x} bytes", firmware.len())],
+        image.errors.is_empty(),
+    )?;
+
+    if let Some(hwpointers) = &image.hwpointers {
+        node(
+            &mut dot,
+            "hw_pointers",
+            &["HW pointers".to_string(), format!("{:#x}", hwpointers.0)],
+            true,
+        )?;
+        writeln!(dot, "  image -> hw_pointers;")?;
+        for (name, pointer) in hwpointers.pointers() {
+            if pointer.is_erased() {
+                continue;
+            }
+            let target = match name {
+                "boot2" if image.boot2.is_some() => "boot2".to_string(),
+                "toc" => "itoc".to_string(),
+                _ => {
+                    let id = format!("pointer_{}", name);
+                    node(&mut dot, &id, &[format!("{:#x}", pointer.ptr)], true)?;
+                    id
+                }
+            };
+            let ok = pointer.crc == pointer.calc_crc();
+            writeln!(
+                dot,
+                "  hw_pointers -> {}{};",
+                target,
+                attributes(Some(name), ok)
+            )?;
+        }
+    }
+
+    if let Some(boot2) = &image.boot2 {
+        let (crc, crc_ok) = crc_status(Some(boot2.crc), boot2.calc_crc()?);
+        node(
+            &mut dot,
+            "boot2",
+            &[
+                "boot2".to_string(),
+                format!("{:#x}, {:#x} bytes", boot2.0, boot2.total_size()),
+                crc.to_string(),
+            ],
+            crc_ok,
+        )?;
+    }
+    if let Some(hwpointers) = &image.hwpointers {
+        toc(&mut dot, firmware, "ITOC", hwpointers.toc.ptr, &image.itoc)?;
+    }
+    if let (Some(dtoc), Some(offset)) = (&image.dtoc, firmware.dtoc_offset()) {
+        toc(&mut dot, firmware, "DTOC", offset, dtoc)?;
+        writeln!(dot, "  image -> dtoc;")?;
+    }
+
+    for error in &image.errors {
+        writeln!(
+            dot,
+            "  // parse error at {:#x}: {}",
+            error.offset,
+            error.message.replace('\n', " ")
+        )?;
+    }
+    writeln!(dot, "}}")?;
+    Ok(dot)
+}
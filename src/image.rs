@@ -0,0 +1,258 @@
+use crate::cacheline::CacheLineGeometry;
+use crate::error::Result;
+use crate::firmware::{Firmware, FirmwareStructure, TocEntries};
+use crate::structures::{
+    hwpointers::{Boot2, HwPointers},
+    itoc::{ItocEntry, TocHeader},
+};
+use crate::verify::{self, Finding, Profile};
+
+/// A part of the image that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#010x}: {}", self.offset, self.message)
+    }
+}
+
+/// A byte range of the image occupied by a known structure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub offset: usize,
+    pub size: usize,
+    pub name: String,
+    /// For section contents, the entry describing the section
+    pub entry: Option<ItocEntry>,
+}
+
+impl Region {
+    fn new(offset: usize, size: usize, name: impl Into<String>) -> Self {
+        Self {
+            offset,
+            size,
+            name: name.into(),
+            entry: None,
+        }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        (self.offset..self.offset + self.size).contains(&offset)
+    }
+
+    /// Address a flash offset within a code section is loaded to, `None` for offsets outside
+    /// code sections and for cache-line trailers
+    pub fn load_address(&self, offset: usize, geometry: CacheLineGeometry) -> Option<u64> {
+        let entry = self
+            .entry
+            .as_ref()
+            .filter(|entry| entry.entry_type.is_code())?;
+        let within = offset.checked_sub(self.offset)?;
+        let code_offset = if entry.cache_line_crc {
+            let (line, byte) = (within / geometry.stride(), within % geometry.stride());
+            if byte >= geometry.data_size {
+                return None;
+            }
+            line * geometry.data_size + byte
+        } else {
+            within
+        };
+        Some(entry.load_address as u64 + code_offset as u64)
+    }
+
+    /// Flash offset the code loaded to `address` is read from, the inverse of `load_address`.
+    /// `None` if the section is not code or does not cover `address`.
+    pub fn flash_offset(&self, address: u64, geometry: CacheLineGeometry) -> Option<usize> {
+        let entry = self
+            .entry
+            .as_ref()
+            .filter(|entry| entry.entry_type.is_code())?;
+        let code_offset = usize::try_from(address.checked_sub(entry.load_address as u64)?).ok()?;
+        let within = if entry.cache_line_crc {
+            let (line, byte) = (
+                code_offset / geometry.data_size,
+                code_offset % geometry.data_size,
+            );
+            line.checked_mul(geometry.stride())?.checked_add(byte)?
+        } else {
+            code_offset
+        };
+        (within < self.size).then(|| self.offset + within)
+    }
+}
+
+/// The structures of a firmware image as found, without checking any CRCs. Whatever could not
+/// be parsed is left out and recorded in `errors`, so a damaged image still yields everything
+/// that is readable.
+#[derive(Debug, Clone)]
+pub struct Image<'a> {
+    pub firmware: &'a Firmware,
+    pub hwpointers: Option<FirmwareStructure<HwPointers>>,
+    pub boot2: Option<FirmwareStructure<Boot2>>,
+    pub itoc_header: Option<FirmwareStructure<TocHeader>>,
+    pub itoc: Vec<FirmwareStructure<ItocEntry>>,
+    /// `None` for images without a secondary ITOC
+    pub secondary_itoc: Option<Vec<FirmwareStructure<ItocEntry>>>,
+    /// `None` for images without device data
+    pub dtoc: Option<Vec<FirmwareStructure<ItocEntry>>>,
+    pub errors: Vec<ParseError>,
+}
+
+impl<'a> Image<'a> {
+    pub fn parse(firmware: &'a Firmware) -> Self {
+        let mut image = Self {
+            firmware,
+            hwpointers: None,
+            boot2: None,
+            itoc_header: None,
+            itoc: vec![],
+            secondary_itoc: None,
+            dtoc: None,
+            errors: vec![],
+        };
+
+        image.hwpointers = image.record(
+            firmware.layout().hwpointers_offset,
+            "HW pointers",
+            firmware.hwpointers(),
+        );
+        if let Some(hwpointers) = &image.hwpointers {
+            let (boot2_offset, toc_offset) = (hwpointers.boot2.ptr, hwpointers.toc.ptr);
+            if firmware.is_fs5() {
+                image.errors.push(ParseError {
+                    offset: boot2_offset,
+                    message: "FS5 boot components are not supported".to_string(),
+                });
+            } else {
+                image.boot2 = image.record(boot2_offset, "Boot2", firmware.boot2());
+            }
+            image.itoc_header = image.record(toc_offset, "ITOC header", firmware.itoc_header());
+            image.itoc = image.record_toc(toc_offset, "ITOC", firmware.itoc_entries());
+            if let Some(offset) = firmware.secondary_itoc_offset() {
+                image.secondary_itoc = Some(image.record_toc(
+                    offset,
+                    "secondary ITOC",
+                    firmware.secondary_itoc_entries(),
+                ));
+            }
+        }
+        if let Some(dtoc_offset) = firmware.dtoc_offset().filter(|_| firmware.has_dtoc()) {
+            image.dtoc = Some(image.record_toc(dtoc_offset, "DTOC", firmware.dtoc_entries()));
+        }
+
+        image
+    }
+
+    fn record<T>(&mut self, offset: usize, what: &str, result: Result<T>) -> Option<T> {
+        result
+            .map_err(|err| {
+                self.errors.push(ParseError {
+                    offset,
+                    message: format!("Could not parse {}: {}", what, err),
+                })
+            })
+            .ok()
+    }
+
+    /// The entries before the first one that fails to parse, recording the failure
+    fn record_toc(
+        &mut self,
+        offset: usize,
+        what: &str,
+        entries: TocEntries,
+    ) -> Vec<FirmwareStructure<ItocEntry>> {
+        entries
+            .lenient()
+            .map_while(|entry| self.record(offset, what, entry))
+            .collect()
+    }
+
+    /// ITOC and DTOC entries with their content, skipping entries pointing outside the image
+    pub fn sections(&self) -> impl Iterator<Item = (&ItocEntry, &'a [u8])> + '_ {
+        let firmware = self.firmware;
+        self.itoc
+            .iter()
+            .chain(self.dtoc.iter().flatten())
+            .filter_map(move |entry| {
+                let content = firmware.get(entry.flash_addr..)?.get(..entry.size)?;
+                Some((&entry.1, content))
+            })
+    }
+
+    /// Every known structure of the image, ordered by offset. Regions nest, e.g. a single HW
+    /// pointer lies within the HW pointer table.
+    pub fn regions(&self) -> Vec<Region> {
+        let mut regions = vec![Region::new(0, 0x10, "image magic")];
+
+        if let Some(hwpointers) = &self.hwpointers {
+            regions.push(Region::new(hwpointers.0, HwPointers::SIZE, "HW pointers"));
+            for (i, (name, _)) in hwpointers.pointers().into_iter().enumerate() {
+                regions.push(Region::new(
+                    hwpointers.0 + i * 8,
+                    8,
+                    format!("HW pointer {}", name),
+                ));
+            }
+            if let Some(boot2) = &self.boot2 {
+                regions.push(Region::new(boot2.0, boot2.total_size(), "boot2"));
+            }
+            Self::toc_regions(&mut regions, "ITOC", hwpointers.toc.ptr, &self.itoc);
+        }
+        if let (Some(dtoc), Some(dtoc_offset)) = (&self.dtoc, self.firmware.dtoc_offset()) {
+            Self::toc_regions(&mut regions, "DTOC", dtoc_offset, dtoc);
+        }
+
+        regions.sort_by_key(|region| (region.offset, std::cmp::Reverse(region.size)));
+        regions
+    }
+
+    fn toc_regions(
+        regions: &mut Vec<Region>,
+        name: &str,
+        offset: usize,
+        toc: &[FirmwareStructure<ItocEntry>],
+    ) {
+        regions.push(Region::new(offset, 0x20, format!("{} header", name)));
+        for (i, entry) in toc.iter().enumerate() {
+            regions.push(Region::new(
+                entry.0,
+                0x20,
+                format!("{} entry {} ({})", name, i, entry.entry_type),
+            ));
+            regions.push(Region {
+                entry: Some(entry.1.clone()),
+                ..Region::new(
+                    entry.flash_addr,
+                    entry.size,
+                    format!("{} section {} ({})", name, i, entry.entry_type),
+                )
+            });
+        }
+        regions.push(Region::new(
+            offset + 0x20 * (toc.len() + 1),
+            0x20,
+            format!("{} end marker", name),
+        ));
+    }
+
+    /// The first region `offset..offset + size` overlaps, not counting sections with exactly that
+    /// range: the section itself and sections sharing its content
+    pub fn overlap(&self, offset: usize, size: usize) -> Option<Region> {
+        self.regions().into_iter().find(|region| {
+            let same_section =
+                region.entry.is_some() && (region.offset, region.size) == (offset, size);
+            !same_section
+                && region.size > 0
+                && region.offset < offset + size
+                && offset < region.offset + region.size
+        })
+    }
+
+    pub fn validate(&self, profile: Profile) -> Vec<Finding> {
+        verify::validate(self, profile)
+    }
+}
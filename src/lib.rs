@@ -0,0 +1,34 @@
+pub mod buildinfo;
+pub mod cacheline;
+pub mod changelog;
+pub mod chip;
+pub mod cipher;
+#[cfg(feature = "fs")]
+pub mod corpus;
+pub mod coverage;
+pub mod crc;
+pub mod devdata;
+pub mod error;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod firmware;
+pub mod graph;
+pub mod image;
+pub mod lint;
+pub mod memimage;
+pub mod patchrecord;
+pub mod phy;
+pub mod progress;
+pub mod provenance;
+pub mod reconstruct;
+pub mod rominfo;
+#[cfg(feature = "fs")]
+pub mod scan;
+pub mod security;
+pub mod structures;
+#[cfg(feature = "synthetic")]
+pub mod synthetic;
+pub mod tag;
+pub mod transform;
+pub mod verify;
@@ -0,0 +1,169 @@
+//! Show-stoppers that would make an image unbootable or unreadable by other tools, checked
+//! before images are written
+
+use crate::error::FirmwareError;
+use crate::firmware::{Firmware, FirmwareStructure, TocEntries};
+use crate::structures::itoc::{CrcMode, ItocEntry};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#010x}: {}", self.offset, self.message)
+    }
+}
+
+struct Linter<'a> {
+    firmware: &'a Firmware,
+    findings: Vec<LintFinding>,
+}
+
+impl Linter<'_> {
+    fn finding(&mut self, offset: usize, message: String) {
+        self.findings.push(LintFinding { offset, message });
+    }
+
+    /// The entries up to the terminating erased slot, reporting a table without one
+    fn toc(&mut self, name: &str, entries: TocEntries) -> Vec<FirmwareStructure<ItocEntry>> {
+        let mut toc = vec![];
+        for entry in entries.lenient() {
+            match entry {
+                Ok(entry) => toc.push(entry),
+                Err(err @ FirmwareError::SectionOutOfBounds { offset, .. }) => {
+                    self.finding(offset, format!("Truncated section: {}", err))
+                }
+                Err(ref err @ FirmwareError::TruncatedImage { offset, ref what })
+                    if what == "TOC" =>
+                {
+                    self.finding(
+                        offset,
+                        format!("{} is not terminated by an erased entry: {}", name, err),
+                    )
+                }
+                Err(err) => {
+                    let offset = toc.last().map_or(0, |entry| entry.0 + 0x20);
+                    self.finding(offset, format!("Could not read {}: {}", name, err))
+                }
+            }
+        }
+        toc
+    }
+
+    fn entry_point(&mut self, itoc: &[FirmwareStructure<ItocEntry>]) {
+        let geometry = self.firmware.cache_line_geometry();
+        let code: Vec<_> = itoc
+            .iter()
+            .filter(|entry| entry.entry_type.is_code())
+            .collect();
+        for entry in &code {
+            if matches!(entry.entry_point, 0 | 0xffffffff) {
+                continue;
+            }
+            let entry_point = entry.entry_point as u64;
+            let inside = code.iter().any(|section| {
+                let code_size = match section.cache_line_crc {
+                    true => geometry.code_size(section.size),
+                    false => section.size,
+                };
+                let start = section.load_address as u64;
+                (start..start + code_size as u64).contains(&entry_point)
+            });
+            if !inside {
+                self.finding(
+                    entry.0,
+                    format!(
+                        "{} entry point {:#010x} lies outside all code sections",
+                        entry.entry_type, entry.entry_point
+                    ),
+                );
+            }
+        }
+    }
+
+    fn crc_mode(&mut self, entry: &FirmwareStructure<ItocEntry>) {
+        let message = match entry.crc_mode() {
+            CrcMode::Unknown(mode) => format!("unknown CRC mode {}", mode),
+            CrcMode::InSection if entry.size < 4 => {
+                "CRC kept in the section, but the section has no room for it".to_string()
+            }
+            CrcMode::None | CrcMode::InSection if entry.section_crc != 0 => format!(
+                "section_crc {:#06x} set, but the CRC mode does not use it",
+                entry.section_crc
+            ),
+            _ => return,
+        };
+        self.finding(entry.0, format!("{}: {}", entry.entry_type, message));
+    }
+
+    fn truncated(&mut self, entry: &FirmwareStructure<ItocEntry>) {
+        let stride = self.firmware.cache_line_geometry().stride();
+        if entry.cache_line_crc && !entry.size.is_multiple_of(stride) {
+            self.finding(
+                entry.0,
+                format!(
+                    "{} ends in a truncated cache line, {:#x} bytes are not a multiple of {:#x}",
+                    entry.entry_type, entry.size, stride
+                ),
+            );
+        }
+    }
+
+    fn alignment(&mut self, entry: &FirmwareStructure<ItocEntry>) {
+        let geometry = self.firmware.cache_line_geometry();
+        for misalignment in entry.misalignments(geometry) {
+            self.finding(entry.0, format!("{}: {}", entry.entry_type, misalignment));
+        }
+    }
+
+    /// Sections sharing some but not all of their bytes
+    fn overlaps(&mut self, entries: &[FirmwareStructure<ItocEntry>]) {
+        for (i, entry) in entries.iter().enumerate() {
+            let range = entry.flash_addr..entry.flash_addr + entry.size;
+            for other in &entries[i + 1..] {
+                let other_range = other.flash_addr..other.flash_addr + other.size;
+                if range != other_range
+                    && range.start < other_range.end
+                    && other_range.start < range.end
+                {
+                    self.finding(
+                        entry.0,
+                        format!(
+                            "{} at {:#x}..{:#x} overlaps {} at {:#x}..{:#x}",
+                            entry.entry_type,
+                            range.start,
+                            range.end,
+                            other.entry_type,
+                            other_range.start,
+                            other_range.end
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Check for an entry point outside the code, section CRCs inconsistent with their CRC mode,
+/// TOCs without terminator, truncated, misaligned and partially overlapping sections
+pub fn lint(firmware: &Firmware) -> Vec<LintFinding> {
+    let mut linter = Linter {
+        firmware,
+        findings: vec![],
+    };
+    let mut entries = linter.toc("ITOC", firmware.itoc_entries());
+    linter.entry_point(&entries);
+    if firmware.has_dtoc() {
+        entries.extend(linter.toc("DTOC", firmware.dtoc_entries()));
+    }
+    for entry in &entries {
+        linter.crc_mode(entry);
+        linter.truncated(entry);
+        linter.alignment(entry);
+    }
+    linter.overlaps(&entries);
+    linter.findings
+}
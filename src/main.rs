@@ -1,152 +1,2992 @@
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use clap::{Parser, Subcommand};
 use deku::prelude::*;
-use std::path::PathBuf;
+use flate2::{write::ZlibEncoder, Compression};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
 
-pub mod crc;
-pub mod firmware;
-pub mod structures;
+#[cfg(feature = "conformance")]
+mod conformance;
+mod manifest;
+mod outdir;
+mod plan;
+mod progressbar;
+mod table;
+#[cfg(feature = "tui")]
+mod tui;
 
-use firmware::Firmware;
+use indicatif::HumanBytes;
+use manifest::{CodeInfo, Manifest, ManifestSection, MANIFEST_FILE};
+use outdir::{create_output_dir, sanitize_file_name};
+use plan::Plan;
+use progressbar::ByteProgress;
+use table::{Align, Cell, ColorChoice, Style, Table};
 
-fn show_sections(firmware: Firmware) -> Result<()> {
-    for (i, itoc_entry) in firmware.itoc()?.iter().enumerate() {
+use mlx5fw::cacheline::CacheLineGeometry;
+use mlx5fw::chip::ChipFamily;
+use mlx5fw::cipher::{AesCtrCipher, SectionCipher};
+use mlx5fw::corpus::{CorpusIndex, CorpusQuery};
+use mlx5fw::export::{CodeLayout, ExportFormat};
+use mlx5fw::firmware::{Firmware, FirmwareStructure, STDIO_PATH};
+use mlx5fw::memimage::MemImage;
+use mlx5fw::patchrecord::PatchRecord;
+use mlx5fw::progress::Progress;
+use mlx5fw::reconstruct::Presence;
+use mlx5fw::structures::{
+    bootversion::ImageFormat,
+    devinfo::Uid,
+    hashes::HashesTable,
+    hwpointers::Boot2,
+    imageinfo::ImageInfo,
+    itoc::{CrcMode, ItocEntry, ItocEntryType},
+    programmable::ProgrammableHwFw,
+    version::FwVersion,
+};
+use mlx5fw::transform::InputTransform;
+use mlx5fw::verify::{self, Profile, Severity};
+use mlx5fw::{
+    buildinfo, changelog, coverage, devdata, graph, lint, phy, provenance, reconstruct, scan,
+    security, tag,
+};
+
+fn show_sections(firmware: Firmware, verbose: bool, color: bool) -> Result<()> {
+    if let Ok(image_info) = firmware.image_info() {
+        let mut security = image_info.security_attributes();
+        let signed = firmware.itoc()?.iter().any(|entry| {
+            matches!(
+                entry.entry_type,
+                ItocEntryType::ImageSignature256 | ItocEntryType::ImageSignature512
+            )
+        });
+        if signed {
+            security.insert(0, "signed");
+        }
         println!(
-            "{:2} {:#010x}/{:#010x} {:#010x} {:#010x}: {} {} {}",
-            i,
-            itoc_entry.flash_addr,
-            itoc_entry.size,
-            itoc_entry.load_address,
-            itoc_entry.entry_point,
-            itoc_entry.encrypted_section,
-            itoc_entry.cache_line_crc,
+            "FW {} ({}), PSID {}, security: {}",
+            image_info.fw_version,
+            image_info.release_date(),
+            image_info.psid(),
+            if security.is_empty() {
+                "none".to_string()
+            } else {
+                security.join(", ")
+            }
+        );
+    }
+    for rom in firmware.rom_info().unwrap_or_default() {
+        println!("ROM {}", rom);
+    }
+    let mut tocs = vec![format!("ITOC at {:#010x}", firmware.itoc_offset()?)];
+    if let Some(offset) = firmware.secondary_itoc_offset() {
+        tocs.push(format!("secondary ITOC at {:#010x}", offset));
+    }
+    match firmware.dtoc_offset().filter(|_| firmware.has_dtoc()) {
+        Some(offset) => tocs.push(format!("DTOC at {:#010x}", offset)),
+        None => tocs.push("no DTOC".to_string()),
+    }
+    println!("{}", tocs.join(", "));
+    let mut table = Table::new(
+        &[
+            ("#", Align::Right),
+            ("TYPE", Align::Left),
+            ("OFFSET", Align::Left),
+            ("SIZE", Align::Right),
+            ("LOAD ADDR", Align::Left),
+            ("ENTRY", Align::Left),
+            ("FLAGS", Align::Left),
+            ("CRC", Align::Left),
+        ],
+        color,
+    );
+    let itoc = firmware.itoc()?;
+    for (i, itoc_entry) in itoc.iter().enumerate() {
+        let flags: Vec<&str> = [
+            (itoc_entry.encrypted_section, "encrypted"),
+            (itoc_entry.cache_line_crc, "cache-line-crc"),
+            (itoc_entry.zipped_image, "zipped"),
+        ]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect();
+        let content_crc = [
+            itoc_entry.section_crc_valid(&firmware),
+            itoc_entry.cache_line_crcs_valid(&firmware),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a && b);
+        let crc = match (itoc_entry.itoc_entry_crc_valid(), content_crc) {
+            (false, _) => Cell::styled("BAD entry", Style::Bad),
+            (true, Some(false)) => Cell::styled("BAD", Style::Bad),
+            (true, Some(true)) => Cell::styled("ok", Style::Good),
+            (true, None) => Cell::styled("-", Style::Dim),
+        };
+        table.row(vec![
+            i.to_string().into(),
+            itoc_entry.entry_type.to_string().into(),
+            format!("{:#010x}", itoc_entry.flash_addr).into(),
+            HumanBytes(itoc_entry.size as u64).to_string().into(),
+            format!("{:#010x}", itoc_entry.load_address).into(),
+            format!("{:#010x}", itoc_entry.entry_point).into(),
+            flags.join(",").into(),
+            crc,
+        ]);
+    }
+    let lines = table.lines();
+    let (header, rows) = lines.split_first().unwrap();
+    println!("{}", header);
+    for (row, itoc_entry) in rows.iter().zip(&itoc) {
+        println!("{}", row);
+        if verbose {
+            show_entry_fields(&firmware, itoc_entry);
+        }
+    }
+    Ok(())
+}
+
+fn show_entry_fields(firmware: &Firmware, entry: &ItocEntry) {
+    let valid = |valid: Option<bool>| match valid {
+        Some(true) => " (valid)",
+        Some(false) => " (BAD)",
+        None => "",
+    };
+    for (name, value) in entry.fields() {
+        let status = match name {
+            "section_crc" if entry.crc_mode() == CrcMode::InItocEntry => {
+                valid(entry.section_crc_valid(firmware))
+            }
+            "itoc_entry_crc" => valid(Some(entry.itoc_entry_crc_valid())),
+            _ => "",
+        };
+        println!("      {:<18} {}{}", name, value, status);
+    }
+    if entry.crc_mode() == CrcMode::InSection {
+        println!(
+            "      {:<18} {}",
+            "in-section crc",
+            match entry.stored_section_crc(firmware) {
+                Some(crc) => format!("{:#06x}{}", crc, valid(entry.section_crc_valid(firmware))),
+                None => "missing".to_string(),
+            }
+        );
+    }
+    if let Some(ok) = entry.cache_line_crcs_valid(firmware) {
+        println!(
+            "      {:<18} {}",
+            "cache line crcs",
+            if ok { "valid" } else { "BAD" }
+        );
+    }
+}
+
+/// Section content as stored, decrypted if the section is encrypted. `None` for encrypted
+/// sections without a cipher.
+fn read_plain_section(
+    firmware: &Firmware,
+    entry: &ItocEntry,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<Option<Vec<u8>>> {
+    let content = entry.content().read_bytes(firmware);
+    match (entry.encrypted_section, cipher) {
+        (false, _) => Ok(Some(content.to_vec())),
+        (true, Some(cipher)) => Ok(Some(cipher.decrypt(entry, content)?)),
+        (true, None) => Ok(None),
+    }
+}
+
+/// Code of a code section, decrypted if needed. `None` for encrypted sections without a key.
+fn read_plain_code(
+    firmware: &Firmware,
+    entry: &ItocEntry,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<Option<Arc<[u8]>>> {
+    if !entry.encrypted_section {
+        return Ok(Some(firmware.decoded_code(entry)));
+    }
+    Ok(read_plain_section(firmware, entry, cipher)?
+        .map(|content| firmware.code_from_content(entry, &content).into()))
+}
+
+/// Write plain section content, encrypting it if the section is encrypted, and update the
+/// section and ITOC entry CRCs
+fn write_plain_section(
+    firmware: &mut Firmware,
+    mut entry: FirmwareStructure<ItocEntry>,
+    content: Vec<u8>,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    write_plain_content(firmware, &entry, content, cipher)?;
+    Ok(entry.update_section_crc(firmware)?)
+}
+
+/// Write plain section content, encrypting it if the section is encrypted, leaving all CRCs
+fn write_plain_content(
+    firmware: &mut Firmware,
+    entry: &FirmwareStructure<ItocEntry>,
+    content: Vec<u8>,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    ensure!(
+        content.len() <= entry.payload().1,
+        "New content for {} is too big",
+        entry.entry_type
+    );
+    firmware.check_section_range(&entry.entry_type.to_string(), entry.flash_addr, entry.size)?;
+    let content = match (entry.encrypted_section, cipher) {
+        (false, _) => content,
+        (true, Some(cipher)) => cipher.encrypt(entry, &content)?,
+        (true, None) => bail!(
+            "{} section is encrypted, a key file is needed to replace it",
+            entry.entry_type
+        ),
+    };
+
+    let section = firmware.slice_ptr(entry.flash_addr, entry.size);
+    section.write_bytes(firmware, &content)?;
+    Ok(())
+}
+
+/// Expand the `--name-template` placeholders for one section into a portable file name
+fn section_file_name(
+    template: &str,
+    index: usize,
+    occurrence: usize,
+    itoc_entry: &ItocEntry,
+) -> Result<String> {
+    let name = template
+        .replace("{index}", &index.to_string())
+        .replace("{occurrence}", &occurrence.to_string())
+        .replace("{type}", &itoc_entry.entry_type.to_string())
+        .replace("{flash_addr}", &format!("{:08x}", itoc_entry.flash_addr))
+        .replace("{load_addr}", &format!("{:08x}", itoc_entry.load_address));
+    ensure!(
+        !name.contains(['{', '}']),
+        "Unknown placeholder in name template {:?}",
+        template
+    );
+    ensure!(
+        !name.is_empty() && !name.contains(['/', '\\']),
+        "Name template {:?} must expand to a plain file name",
+        template
+    );
+    Ok(sanitize_file_name(&name))
+}
+
+fn dump_sections(
+    firmware: Firmware,
+    args: CliDumpSections,
+    cipher: Option<&dyn SectionCipher>,
+    force: bool,
+) -> Result<()> {
+    let swap32 = args.swap32;
+    let out_dir = create_output_dir(&args.dir, force)?;
+    let mut occurrences = HashMap::new();
+    let mut names = HashSet::from([out_dir.join(MANIFEST_FILE)]);
+    let mut manifest = Manifest {
+        swap32,
+        sections: vec![],
+    };
+    let itoc = firmware.itoc()?;
+    let progress = ByteProgress::new("Dumping", itoc.iter().map(|entry| entry.size).sum());
+    for (index, itoc_entry) in itoc.into_iter().enumerate() {
+        progress.advance(itoc_entry.size);
+        let content = match read_plain_section(&firmware, &itoc_entry, cipher)? {
+            Some(content) => content,
+            None => {
+                progress.suspend(|| {
+                    warn!(
+                        "{} section at {:#010x} is encrypted, dumped as stored",
+                        itoc_entry.entry_type, itoc_entry.flash_addr
+                    )
+                });
+                itoc_entry.content().read_bytes(&firmware).to_vec()
+            }
+        };
+        // In-section CRCs are dumped without their CRC dword, replace-section puts it back
+        let crc_dword = match itoc_entry.crc_mode() {
+            CrcMode::InSection => content
+                .get(itoc_entry.payload().1..)
+                .and_then(|tail| tail.try_into().ok())
+                .map(u32::from_be_bytes),
+            _ => None,
+        };
+        let content = match itoc_entry.crc_mode() {
+            CrcMode::InSection => content[..itoc_entry.payload().1].to_vec(),
+            _ => content,
+        };
+        let occurrence = occurrences
+            .entry(itoc_entry.entry_type.to_string())
+            .and_modify(|occurrence| *occurrence += 1)
+            .or_insert(0);
+        let name = section_file_name(&args.name_template, index, *occurrence, &itoc_entry)?;
+        let dir = match args.by_type {
+            true => out_dir.join(itoc_entry.entry_type.to_string()),
+            false => out_dir.clone(),
+        };
+        ensure!(
+            names.insert(dir.join(&name)),
+            "Name template {:?} gives several sections the name {}",
+            args.name_template,
+            name
+        );
+        std::fs::create_dir_all(&dir)?;
+        // The PLD header layout is not verified, so the section is always dumped whole too
+        if itoc_entry.entry_type == ItocEntryType::ProgrammableHwFw {
+            match ProgrammableHwFw::from_bytes((&content, 0)) {
+                Ok((_, programmable)) => {
+                    for (i, image) in programmable.images.iter().enumerate() {
+                        let Some(data) = image.data(&content) else {
+                            progress.suspend(|| {
+                                warn!("PLD image {} lies outside the section, not dumped", i)
+                            });
+                            continue;
+                        };
+                        std::fs::write(
+                            dir.join(format!("{}.pld{}_{:08x}", name, i, image.pld_id)),
+                            swap_words(data.to_vec(), swap32),
+                        )?;
+                    }
+                }
+                Err(err) => progress.suspend(|| {
+                    warn!(
+                        "Could not split {} into PLD images, dumped raw: {}",
+                        itoc_entry.entry_type, err
+                    )
+                }),
+            }
+        }
+        std::fs::write(dir.join(&name), swap_words(content, swap32))?;
+        manifest.sections.push(ManifestSection::new(
+            &itoc_entry,
+            match args.by_type {
+                true => format!("{}/{}", itoc_entry.entry_type, name),
+                false => name,
+            },
+            itoc_entry.encrypted_section && cipher.is_some(),
+            crc_dword,
+        ));
+    }
+    manifest.write(&out_dir.join(MANIFEST_FILE))
+}
+
+fn dump_code(
+    firmware: Firmware,
+    dir: &Path,
+    swap32: bool,
+    cipher: Option<&dyn SectionCipher>,
+    force: bool,
+) -> Result<()> {
+    let dir = create_output_dir(dir, force)?;
+    for itoc_entry in firmware.code_sections()? {
+        let Some(code) = read_plain_code(&firmware, &itoc_entry, cipher)? else {
+            warn!(
+                "skipping encrypted {} section at {:#010x}",
+                itoc_entry.entry_type, itoc_entry.flash_addr
+            );
+            continue;
+        };
+        let section_path = dir.join(itoc_entry.code_file_name());
+        std::fs::write(section_path, swap_words(code.to_vec(), swap32))?;
+        CodeInfo::new(&firmware, &itoc_entry, code.len(), swap32).write(&dir)?;
+    }
+    Ok(())
+}
+
+fn split_phy(
+    firmware: Firmware,
+    dir: &Path,
+    min_gap: usize,
+    cipher: Option<&dyn SectionCipher>,
+    force: bool,
+) -> Result<()> {
+    let sections: Vec<_> = firmware
+        .itoc()?
+        .into_iter()
+        .filter(|entry| phy::is_phy_section(&entry.entry_type))
+        .collect();
+    ensure!(!sections.is_empty(), "No PHY microcode sections found");
+    let dir = create_output_dir(dir, force)?;
+    for itoc_entry in sections {
+        let Some(content) = read_plain_section(&firmware, &itoc_entry, cipher)? else {
+            warn!(
+                "skipping encrypted {} section at {:#010x}",
+                itoc_entry.entry_type, itoc_entry.flash_addr
+            );
+            continue;
+        };
+        let blobs = phy::split_blobs(&content, min_gap);
+        println!(
+            "{} at {:#010x}: {} piece(s)",
             itoc_entry.entry_type,
+            itoc_entry.flash_addr,
+            blobs.len()
+        );
+        for (i, blob) in blobs.iter().enumerate() {
+            let name = format!(
+                "{:08x}_{}_{}.bin",
+                itoc_entry.flash_addr, itoc_entry.entry_type, i
+            );
+            println!(
+                "  {:2} {:#010x}/{:#010x} {}",
+                i,
+                itoc_entry.flash_addr + blob.offset,
+                blob.size,
+                name
+            );
+            std::fs::write(
+                dir.join(name),
+                &content[blob.offset..blob.offset + blob.size],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn show_strings(
+    firmware: Firmware,
+    min_len: usize,
+    all: bool,
+    json: bool,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    let mut sections = vec![];
+    for itoc_entry in firmware.code_sections()? {
+        let Some(code) = read_plain_code(&firmware, &itoc_entry, cipher)? else {
+            warn!(
+                "skipping encrypted {} section at {:#010x}",
+                itoc_entry.entry_type, itoc_entry.flash_addr
+            );
+            continue;
+        };
+        let strings: Vec<_> = buildinfo::strings(&code, min_len)
+            .into_iter()
+            .filter(|string| all || string.kind != buildinfo::StringKind::Other)
+            .collect();
+        sections.push((itoc_entry, strings));
+    }
+
+    if json {
+        let sections: Vec<_> = sections
+            .iter()
+            .map(|(entry, strings)| {
+                serde_json::json!({
+                    "section": entry.entry_type.to_string(),
+                    "load_address": entry.load_address,
+                    "strings": strings,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&sections)?);
+        return Ok(());
+    }
+    for (entry, strings) in sections {
+        println!("{} ({} strings)", entry.entry_type, strings.len());
+        for string in strings {
+            println!(
+                "  {:#010x} {:<8} {}",
+                entry.load_address as usize + string.offset,
+                string.kind,
+                string.text
+            );
+        }
+    }
+    Ok(())
+}
+
+fn export_code_layout(firmware: Firmware, format: ExportFormat, output: PathBuf) -> Result<()> {
+    let layout = CodeLayout::from_firmware(&firmware)?;
+    std::fs::write(output, layout.export(format)?)?;
+    Ok(())
+}
+
+fn export_linker_script(firmware: Firmware, symbols: bool, output: PathBuf) -> Result<()> {
+    let layout = CodeLayout::from_firmware(&firmware)?;
+    let script = match symbols {
+        true => layout.symbols()?,
+        false => layout.linker_script()?,
+    };
+    std::fs::write(output, script)?;
+    Ok(())
+}
+
+fn fix_hwpointers(mut firmware: Firmware, output: PathBuf) -> Result<()> {
+    fix_hwpointer_crcs(&mut firmware)?;
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn fix_hwpointer_crcs(firmware: &mut Firmware) -> Result<()> {
+    let mut hwpointers = firmware.hwpointers()?;
+    let stored = hwpointers.1.clone();
+    hwpointers.update()?;
+    if hwpointers.1 == stored {
+        return Ok(());
+    }
+    for ((name, old), (_, new)) in stored.pointers().into_iter().zip(hwpointers.pointers()) {
+        if old.crc != new.crc {
+            info!(
+                "{}: {:#06x} -> {:#06x} (pointer {:#010x})",
+                name, old.crc, new.crc, new.ptr
+            );
+        }
+    }
+    Ok(hwpointers.write(firmware)?)
+}
+
+fn fix_crc(mut firmware: Firmware, args: CliFixCrc) -> Result<()> {
+    fix_crcs(&mut firmware, args.section)?;
+    firmware.write(args.output)?;
+    Ok(())
+}
+
+/// Recompute the CRCs of one section, or of all sections, the ITOC header, boot2 and HW pointers
+fn fix_crcs(firmware: &mut Firmware, section: Option<usize>) -> Result<()> {
+    let itoc = firmware.itoc()?;
+    let selected = match section {
+        Some(index) => {
+            ensure!(index < itoc.len(), "Section index out of range");
+            vec![itoc[index].clone()]
+        }
+        None => itoc,
+    };
+
+    for mut itoc_entry in selected {
+        let name = format!(
+            "{} at {:#010x}",
+            itoc_entry.entry_type, itoc_entry.flash_addr
+        );
+        if itoc_entry.cache_line_crc {
+            let content = firmware
+                .cache_line_geometry()
+                .encode(&firmware.section_code(&itoc_entry))?;
+            let section = itoc_entry.content();
+            if content != section.read_bytes(firmware) {
+                info!("{}: cache-line CRCs", name);
+                section.write_bytes(firmware, &content)?;
+            }
+        }
+        let section_crc = itoc_entry.calc_section_crc(firmware);
+        if let Some(stored) = itoc_entry.stored_section_crc(firmware) {
+            if stored != section_crc {
+                info!(
+                    "{}: section CRC {:#06x} -> {:#06x}",
+                    name, stored, section_crc
+                );
+            }
+        }
+        let itoc_entry_crc = itoc_entry.itoc_entry_crc;
+        itoc_entry.update_section_crc(firmware)?;
+        if itoc_entry.itoc_entry_crc != itoc_entry_crc {
+            info!(
+                "{}: ITOC entry CRC {:#06x} -> {:#06x}",
+                name, itoc_entry_crc, itoc_entry.itoc_entry_crc
+            );
+        }
+    }
+
+    if section.is_none() {
+        let mut header = firmware.itoc_header()?;
+        let stored = header.crc;
+        header.update()?;
+        if header.crc != stored {
+            info!("ITOC header: {:#06x} -> {:#06x}", stored, header.crc);
+            header.write(firmware)?;
+        }
+        if let Ok(mut boot2) = firmware.boot2() {
+            let stored = boot2.crc;
+            boot2.update()?;
+            if boot2.crc != stored {
+                info!("boot2: {:#06x} -> {:#06x}", stored, boot2.crc);
+                boot2.write(firmware)?;
+            }
+        }
+        fix_hwpointer_crcs(firmware)?;
+    }
+    Ok(())
+}
+
+fn show_crdump_mask(firmware: Firmware) -> Result<()> {
+    let crdump_mask = firmware.crdump_mask()?;
+    for range in &crdump_mask.ranges {
+        println!(
+            "{:#010x}-{:#010x} ({:#x} bytes)",
+            range.address,
+            range.end(),
+            range.size
+        );
+    }
+    Ok(())
+}
+
+fn dump_mem(firmware: Firmware, output: PathBuf) -> Result<()> {
+    let mem_image = MemImage::from_firmware(&firmware)?;
+    for (lower, upper) in mem_image.overlaps() {
+        warn!(
+            "{} at {:#010x} overlaps {} at {:#010x}",
+            upper.section_type, upper.load_address, lower.section_type, lower.load_address
+        );
+    }
+    for segment in &mem_image.segments {
+        println!(
+            "{:#010x}-{:#010x} at {:#x}: {}",
+            segment.load_address,
+            (segment.load_address as u64 + segment.size as u64).saturating_sub(1),
+            segment.file_offset,
+            segment.section_type
+        );
+    }
+    Ok(mem_image.write(&output)?)
+}
+
+fn whatis(firmware: Firmware, offset: usize) -> Result<()> {
+    ensure!(
+        offset < firmware.len(),
+        "Offset {:#x} lies outside the image ({:#x} bytes)",
+        offset,
+        firmware.len()
+    );
+    let image = firmware.parse();
+    let regions: Vec<_> = image
+        .regions()
+        .into_iter()
+        .filter(|region| region.contains(offset))
+        .collect();
+    if regions.is_empty() {
+        let all = image.regions();
+        let start = all
+            .iter()
+            .map(|region| region.offset + region.size)
+            .filter(|&end| end <= offset)
+            .max()
+            .unwrap_or(0);
+        let end = all
+            .iter()
+            .map(|region| region.offset)
+            .filter(|&start| start > offset)
+            .min()
+            .unwrap_or(firmware.len());
+        println!(
+            "{:#010x}: gap {:#010x}-{:#010x}, not part of any known structure{}",
+            offset,
+            start,
+            end - 1,
+            if firmware[offset] == 0xff {
+                " (erased)"
+            } else {
+                ""
+            }
+        );
+    }
+    for region in regions {
+        print!(
+            "{:#010x}-{:#010x} {} +{:#x}",
+            region.offset,
+            region.offset + region.size - 1,
+            region.name,
+            offset - region.offset
+        );
+        if let Some(load_address) = region.load_address(offset, firmware.cache_line_geometry()) {
+            print!(", loaded at {:#010x}", load_address);
+        } else if region
+            .entry
+            .as_ref()
+            .is_some_and(|entry| entry.cache_line_crc)
+        {
+            print!(", cache-line trailer");
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn whatis_va(firmware: Firmware, address: u64) -> Result<()> {
+    let geometry = firmware.cache_line_geometry();
+    let image = firmware.parse();
+    let mut found = false;
+    for region in image.regions() {
+        if let Some(offset) = region.flash_offset(address, geometry) {
+            let entry = region.entry.as_ref().unwrap();
+            println!(
+                "{:#010x}: {} +{:#x}, flash offset {:#010x}",
+                address,
+                region.name,
+                address - entry.load_address as u64,
+                offset
+            );
+            found = true;
+        }
+    }
+    if !found {
+        println!("{:#010x}: not loaded from any code section", address);
+    }
+    Ok(())
+}
+
+/// Byte-swap every complete 32-bit word if `swap`. A trailing partial word is kept as is.
+fn swap_words(mut data: Vec<u8>, swap: bool) -> Vec<u8> {
+    if swap {
+        data.chunks_exact_mut(4).for_each(|word| word.reverse());
+    }
+    data
+}
+
+/// One hexdump line of up to 16 bytes, with an ASCII column
+fn hexdump_line(offset: usize, line: &[u8]) -> String {
+    let hex: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let ascii: String = line
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{:#010x}: {:47} |{:16}|", offset, hex.join(" "), ascii)
+}
+
+fn inspect(firmware: Firmware, offset: usize, len: usize) -> Result<()> {
+    ensure!(
+        offset < firmware.len(),
+        "Offset {:#x} lies outside the image ({:#x} bytes)",
+        offset,
+        firmware.len()
+    );
+    let end = offset.saturating_add(len).min(firmware.len());
+    let regions = firmware.parse().regions();
+
+    for region in regions
+        .iter()
+        .filter(|region| region.offset < offset && region.contains(offset))
+    {
+        println!("in {} (+{:#x})", region.name, offset - region.offset);
+    }
+    for line_start in (offset..end).step_by(16) {
+        let line = &firmware[line_start..end.min(line_start + 16)];
+        let starting: Vec<&str> = regions
+            .iter()
+            .filter(|region| (line_start..line_start + line.len()).contains(&region.offset))
+            .map(|region| region.name.as_str())
+            .collect();
+        print!("{}", hexdump_line(line_start, line));
+        if !starting.is_empty() {
+            print!(" < {}", starting.join(", "));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn warn_itoc_entry_crc(itoc_entry: &ItocEntry) {
+    if let Err(err) = itoc_entry.check_itoc_entry_crc() {
+        warn!(
+            "{} at {:#010x} before the update",
+            err, itoc_entry.flash_addr
+        );
+    }
+}
+
+fn pack_code(
+    mut firmware: Firmware,
+    args: CliPackCode,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    let mut packed = 0;
+    for itoc_entry in firmware.code_sections()? {
+        let code_path = args.dir.join(itoc_entry.code_file_name());
+        if !code_path.exists() {
+            continue;
+        }
+        warn_itoc_entry_crc(&itoc_entry);
+        let code = std::fs::read(&code_path)
+            .with_context(|| format!("Could not read {}", code_path.display()))?;
+        let code = swap_words(code, args.swap32);
+        let content = if itoc_entry.cache_line_crc {
+            firmware.cache_line_geometry().encode(&code)?
+        } else {
+            code
+        };
+        write_plain_section(&mut firmware, itoc_entry, content, cipher)
+            .with_context(|| format!("Could not pack {}", code_path.display()))?;
+
+        info!("Packed {}", code_path.display());
+        packed += 1;
+    }
+    ensure!(packed > 0, "No code files found in {}", args.dir.display());
+
+    firmware.write(args.output)?;
+
+    Ok(())
+}
+
+fn show_code_map(firmware: Firmware) -> Result<()> {
+    let geometry = firmware.cache_line_geometry();
+    let mut sections: Vec<_> = firmware
+        .code_sections()?
+        .into_iter()
+        .map(|entry| {
+            let code_size = if entry.cache_line_crc {
+                geometry.code_size(entry.size)
+            } else {
+                entry.size
+            };
+            let start = entry.load_address as u64;
+            (start, start + code_size as u64, entry)
+        })
+        .collect();
+    sections.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let mut covered_end = None;
+    for (i, (start, end, entry)) in sections.iter().enumerate() {
+        if let Some(covered_end) = covered_end {
+            if *start > covered_end {
+                println!(
+                    "{:#010x}-{:#010x} gap ({:#x} bytes)",
+                    covered_end,
+                    start - 1,
+                    start - covered_end
+                );
+            }
+        }
+        covered_end = Some(covered_end.map_or(*end, |covered_end: u64| covered_end.max(*end)));
+
+        let overlaps: Vec<String> = sections
+            .iter()
+            .enumerate()
+            .filter(|(j, (other_start, other_end, _))| {
+                *j != i && start < other_end && other_start < end
+            })
+            .map(|(_, (_, _, other))| other.entry_type.to_string())
+            .collect();
+
+        print!(
+            "{:#010x}-{:#010x} entry {:#010x}: {}",
+            start,
+            end.saturating_sub(1),
+            entry.entry_point,
+            entry.entry_type
+        );
+        if !(start..end).contains(&&(entry.entry_point as u64)) {
+            print!(" [entry point outside section]");
+        }
+        if !overlaps.is_empty() {
+            print!(" [overlaps {}]", overlaps.join(", "));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Read a file, or standard input if `path` is `-`
+fn read_input(path: &Path) -> std::io::Result<Vec<u8>> {
+    if path != Path::new(STDIO_PATH) {
+        return std::fs::read(path);
+    }
+    let mut data = vec![];
+    std::io::stdin().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn replace_section(
+    mut firmware: Firmware,
+    args: CliReplaceSection,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    let (replacements, output) = args.replacements()?;
+    let itoc = firmware.itoc()?;
+    for (i, &(index, _)) in replacements.iter().enumerate() {
+        ensure!(index < itoc.len(), "Section index {} out of range", index);
+        ensure!(
+            replacements[..i].iter().all(|&(other, _)| other != index),
+            "Section {} is replaced twice",
+            index
+        );
+    }
+    ensure!(
+        replacements
+            .iter()
+            .filter(|(_, content)| content == Path::new(STDIO_PATH))
+            .count()
+            <= 1,
+        "Only one section content can be read from standard input"
+    );
+
+    // Every replacement lands in memory, the output is written once at the end
+    for (index, content) in replacements {
+        let itoc_entry = itoc[index].clone();
+        warn_itoc_entry_crc(&itoc_entry);
+
+        let section = read_input(&content)
+            .with_context(|| format!("Could not read new content for section {}", index))?;
+        let section = swap_words(section, args.swap32);
+        let section_content = if itoc_entry.cache_line_crc && !args.no_fix_cache_line_crc {
+            firmware.cache_line_geometry().encode(&section)?
+        } else {
+            section
+        };
+
+        if args.no_update_itoc {
+            // Only an in-section CRC lies outside the ITOC entry
+            let mut itoc_entry = itoc_entry;
+            write_plain_content(&mut firmware, &itoc_entry, section_content, cipher)?;
+            itoc_entry.store_section_crc(&mut firmware)?;
+        } else {
+            write_plain_section(&mut firmware, itoc_entry, section_content, cipher)?;
+        }
+    }
+    if args.sort_by_addr {
+        let itoc = firmware.itoc()?;
+        rewrite_itoc(&mut firmware, itoc, true)?;
+    }
+
+    firmware.write(output)?;
+
+    Ok(())
+}
+
+/// Write the ITOC entries and section files of a dump-sections manifest as they were dumped,
+/// without recomputing any CRC
+fn pack_sections(
+    mut firmware: Firmware,
+    path: &Path,
+    output: PathBuf,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    let manifest = Manifest::read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut entries = manifest
+        .sections
+        .iter()
+        .map(ManifestSection::itoc_entry)
+        .collect::<Result<Vec<_>>>()?;
+    let mut aligned = vec![];
+    for entry in &mut entries {
+        let (flash_addr, size) = (entry.flash_addr, entry.size);
+        let realigned = entry.align();
+        aligned.push(realigned);
+        if realigned {
+            warn!(
+                "{} at {:#x}+{:#x} is not dword aligned, packed at {:#x}+{:#x}",
+                entry.entry_type, flash_addr, size, entry.flash_addr, entry.size
+            );
+            ensure!(
+                entry.size <= ItocEntry::MAX_SIZE,
+                "{} size {:#x} does not fit its field, at most {:#x}",
+                entry.entry_type,
+                entry.size,
+                ItocEntry::MAX_SIZE
+            );
+        }
+    }
+    // Checked once the new ITOC is in place, against the aligned ranges of all entries
+    firmware.write_itoc(&entries)?;
+    for entry in &entries {
+        firmware.check_section_range(
+            &entry.entry_type.to_string(),
+            entry.flash_addr,
+            entry.size,
+        )?;
+    }
+
+    let first = firmware.itoc_offset()? + 0x20;
+    for (i, ((section, entry), aligned)) in manifest
+        .sections
+        .iter()
+        .zip(entries)
+        .zip(aligned)
+        .enumerate()
+    {
+        let file = dir.join(&section.file);
+        let content = std::fs::read(&file)
+            .with_context(|| format!("Could not read section file {}", file.display()))?;
+        let mut content = swap_words(content, manifest.swap32);
+        // Aligned sections get a new CRC at their new end instead
+        if let Some(crc_dword) = section.crc_dword.filter(|_| !aligned) {
+            content.extend_from_slice(&crc_dword.to_be_bytes());
+        }
+        if section.decrypted {
+            let cipher = cipher.with_context(|| {
+                format!(
+                    "{} was dumped decrypted, a key file is needed",
+                    section.file
+                )
+            })?;
+            content = cipher.encrypt(&entry, &content)?;
+        }
+        ensure!(
+            content.len() <= entry.size,
+            "{} does not fit its {:#x} byte section",
+            section.file,
+            entry.size
+        );
+        entry.content().write_bytes(&mut firmware, &content)?;
+        let mut entry = FirmwareStructure(first + i * 0x20, entry);
+        if aligned {
+            // The dumped CRCs do not cover the aligned range
+            entry.update_section_crc(&mut firmware)?;
+        } else {
+            // Keep the dumped ITOC entry CRC even if it is wrong
+            entry.write(&mut firmware)?;
+        }
+    }
+    info!(
+        "Restored {} sections from {}",
+        manifest.sections.len(),
+        path.display()
+    );
+    firmware.write(output)?;
+    Ok(())
+}
+
+/// Write back the ITOC in on-flash order, or sorted by flash address
+fn rewrite_itoc(
+    firmware: &mut Firmware,
+    itoc: Vec<FirmwareStructure<ItocEntry>>,
+    sort_by_addr: bool,
+) -> Result<()> {
+    let mut entries: Vec<ItocEntry> = itoc.into_iter().map(|entry| entry.1).collect();
+    if sort_by_addr {
+        entries.sort_by_key(|entry| entry.flash_addr);
+    }
+    Ok(firmware.write_itoc(&entries)?)
+}
+
+fn remove_section(mut firmware: Firmware, args: CliRemoveSection) -> Result<()> {
+    let mut itoc = firmware.itoc()?;
+    ensure!(
+        args.section_index < itoc.len(),
+        "Section index out of range"
+    );
+
+    let itoc_entry = itoc.remove(args.section_index);
+    let shared = itoc.iter().any(|entry| {
+        entry.flash_addr < itoc_entry.flash_addr + itoc_entry.size
+            && itoc_entry.flash_addr < entry.flash_addr + entry.size
+    });
+    if !shared {
+        firmware.fill(itoc_entry.flash_addr, itoc_entry.size)?;
+    }
+    rewrite_itoc(&mut firmware, itoc, args.sort_by_addr)?;
+
+    firmware.write(args.output)?;
+    info!(
+        "Removed {} at {:#010x}",
+        itoc_entry.entry_type, itoc_entry.flash_addr
+    );
+    Ok(())
+}
+
+fn trim(mut firmware: Firmware, args: CliTrim) -> Result<()> {
+    let device_data = firmware.device_data_offset();
+    let end = firmware
+        .parse()
+        .regions()
+        .iter()
+        .filter(|region| device_data.is_none_or(|offset| region.offset < offset))
+        .map(|region| region.offset + region.size)
+        .max()
+        .unwrap_or(0);
+    let size = match device_data {
+        Some(offset) if !args.strip_device_data => {
+            end.next_multiple_of(firmware.layout().sector_size) + firmware.len() - offset
+        }
+        _ => end,
+    };
+    ensure!(
+        size <= firmware.len(),
+        "Image content overlaps the device data"
+    );
+
+    let old_size = firmware.len();
+    match args.strip_device_data {
+        true => firmware.truncate(size),
+        false => firmware.resize(size)?,
+    }
+    firmware.write(args.output)?;
+    info!("Trimmed image from {:#x} to {:#x} bytes", old_size, size);
+    Ok(())
+}
+
+fn expand(mut firmware: Firmware, size: usize, output: PathBuf) -> Result<()> {
+    ensure!(
+        size >= firmware.len(),
+        "Image ({:#x} bytes) is larger than {:#x} bytes",
+        firmware.len(),
+        size
+    );
+    let old_size = firmware.len();
+    firmware.resize(size)?;
+    firmware.write(output)?;
+    info!("Expanded image from {:#x} to {:#x} bytes", old_size, size);
+    Ok(())
+}
+
+/// Decode an ITOC or DTOC section with the parser for its type. Types without one are
+/// hexdumped, annotated like `inspect`.
+fn show_section_info(firmware: Firmware, index: usize, dtoc: bool) -> Result<()> {
+    let (toc, entries) = match dtoc {
+        true => ("DTOC", firmware.dtoc()?),
+        false => ("ITOC", firmware.itoc()?),
+    };
+    let Some(entry) = entries.get(index) else {
+        bail!(
+            "{} has no section {}, it lists {}",
+            toc,
+            index,
+            entries.len()
+        );
+    };
+    println!(
+        "{} section {}: {} at {:#010x}, {:#x} bytes",
+        toc, index, entry.entry_type, entry.flash_addr, entry.size
+    );
+    match entry.entry_type {
+        ItocEntryType::ImageInfo => {
+            let image_info: FirmwareStructure<ImageInfo> = firmware.read_section(entry)?;
+            show_image_info(&image_info);
+        }
+        ItocEntryType::MfgInfo | ItocEntryType::DevInfo => show_dev_info(firmware)?,
+        ItocEntryType::VpdR0 => show_vpd(firmware)?,
+        ItocEntryType::FwNvLog => show_nv_log(firmware)?,
+        ItocEntryType::ForbiddenVersions => show_forbidden(firmware)?,
+        ItocEntryType::CrDumpMaskData => show_crdump_mask(firmware)?,
+        ItocEntryType::DbgFwIni => print!("{}", firmware.dbg_fw_ini()?),
+        ItocEntryType::HashesTable => {
+            let table: FirmwareStructure<HashesTable> = firmware.read_section(entry)?;
+            println!("Version:   {}", table.version);
+            println!("Hash size: {} bytes", table.hash_size);
+            for table_entry in &table.entries {
+                println!(
+                    "  {} digest at +{:#x}",
+                    table_entry.section_type, table_entry.hash_offset
+                );
+            }
+        }
+        ItocEntryType::RomCode => {
+            for rom in firmware.rom_info()? {
+                println!("{}", rom);
+            }
+        }
+        ItocEntryType::PublicKeys2048 | ItocEntryType::PublicKeys4096 => {
+            for key in security::public_keys(&firmware, entry) {
+                println!(
+                    "{} RSA-{} e={} sha256:{}",
+                    key.uuid, key.bits, key.exponent, key.fingerprint
+                );
+            }
+        }
+        ItocEntryType::ImageSignature256 | ItocEntryType::ImageSignature512 => {
+            let content = entry.content().read_bytes(&firmware);
+            println!(
+                "Key UUID: {}",
+                hex::encode(content.get(..16).unwrap_or_default())
+            );
+        }
+        ref entry_type if entry_type.is_nv_data() => show_nv_tlvs(&firmware, entry)?,
+        _ => {
+            let (offset, size) = (entry.flash_addr, entry.size);
+            inspect(firmware, offset, size)?;
+        }
+    }
+    Ok(())
+}
+
+fn show_image_info(image_info: &ImageInfo) {
+    let security = image_info.security_attributes();
+    println!("FW version:   {}", image_info.fw_version);
+    println!("MIC version:  {}", image_info.mic_version);
+    println!(
+        "Format:       {}.{}",
+        image_info.major_version, image_info.minor_version
+    );
+    println!(
+        "Build date:   {} {:02x}:{:02x}:{:02x}",
+        image_info.release_date(),
+        image_info.build_hour,
+        image_info.build_minutes,
+        image_info.build_seconds
+    );
+    println!("PSID:         {}", image_info.psid());
+    println!(
+        "PCI IDs:      {:04x}:{:04x}",
+        image_info.pci_vendor_id, image_info.pci_device_id
+    );
+    println!(
+        "Security:     {}",
+        if security.is_empty() {
+            "none".to_string()
+        } else {
+            security.join(", ")
+        }
+    );
+    println!("VSD vendor:   {:#06x}", image_info.vsd_vendor_id);
+    println!("VSD:          {}", image_info.vsd());
+}
+
+fn show_dev_info(firmware: Firmware) -> Result<()> {
+    let mfg_info = firmware.mfg_info()?;
+    let dev_info = firmware.dev_info()?;
+    let show_uid = |name: &str, uid: &Uid| {
+        println!(
+            "{:14} {:#018x} (count {}, step {})",
+            name,
+            uid.uid,
+            uid.count(),
+            uid.step
+        );
+    };
+
+    match firmware.chip() {
+        Some(chip) => println!("Chip:          {}", chip),
+        None => println!("Chip:          unknown"),
+    }
+    println!("PSID:          {}", mfg_info.psid());
+    show_uid("MFG GUIDs:", &mfg_info.uids.guids);
+    show_uid("MFG MACs:", &mfg_info.uids.macs);
+    show_uid("GUIDs:", &dev_info.uids.guids);
+    show_uid("MACs:", &dev_info.uids.macs);
+    println!("VSD vendor id: {:#06x}", dev_info.vsd_vendor_id);
+    println!("VSD:           {}", dev_info.vsd());
+    Ok(())
+}
+
+/// `mstflint query full` output, for scripts that parse it
+fn query(firmware: Firmware) -> Result<()> {
+    let field = |name: &str, value: &str| println!("{:<23}{}", format!("{}:", name), value);
+    let or_na = |value: String| {
+        if value.is_empty() {
+            "N/A".to_string()
+        } else {
+            value
+        }
+    };
+
+    field("Image type", &firmware.image_format().to_string());
+    if let Some(boot_version) = firmware
+        .boot_version()
+        .ok()
+        .filter(|boot_version| boot_version.image_format().is_some())
+    {
+        field(
+            "Boot Version",
+            &format!(
+                "{}.{} (format version {})",
+                boot_version.major_version,
+                boot_version.minor_version,
+                boot_version.image_format_version
+            ),
+        );
+    }
+    let image_info = firmware.image_info()?;
+    field("FW Version", &image_info.fw_version.to_string());
+    field(
+        "FW Release Date",
+        &format!(
+            "{:x}.{:x}.{:x}",
+            image_info.build_day, image_info.build_month, image_info.build_year
+        ),
+    );
+    let mic = image_info.mic_version;
+    field(
+        "MIC Version",
+        &format!("{}.{}.{}", mic.major, mic.minor, mic.subminor),
+    );
+    if image_info.pci_device_id != 0 {
+        field("PCI Device ID", &image_info.pci_device_id.to_string());
+    }
+    let roms = firmware.rom_info().unwrap_or_default();
+    for (i, rom) in roms.iter().enumerate() {
+        match i {
+            0 => field("Rom Info", &rom.to_string()),
+            _ => println!("{:23}{}", "", rom),
+        }
+    }
+
+    let dev_info = firmware.dev_info().ok();
+    let mfg_info = firmware.mfg_info().ok();
+    field("Description", "UID                GuidsNumber");
+    let uid = |name: &str, uid: Option<&Uid>, digits: usize, spacing: &str| match uid {
+        Some(uid) => field(
+            name,
+            &format!("{:0digits$x}{}{}", uid.uid, spacing, uid.count()),
+        ),
+        None => field(name, "N/A"),
+    };
+    let guids = dev_info.as_ref().map(|dev_info| &dev_info.uids.guids);
+    let macs = dev_info.as_ref().map(|dev_info| &dev_info.uids.macs);
+    let orig_guids = mfg_info.as_ref().map(|mfg_info| &mfg_info.uids.guids);
+    let orig_macs = mfg_info.as_ref().map(|mfg_info| &mfg_info.uids.macs);
+    uid("Base GUID", guids, 16, "        ");
+    if orig_guids.is_some_and(|orig| guids.is_some_and(|guids| guids.uid != orig.uid)) {
+        uid("Orig Base GUID", orig_guids, 16, "        ");
+    }
+    uid("Base MAC", macs, 12, "            ");
+    if orig_macs.is_some_and(|orig| macs.is_some_and(|macs| macs.uid != orig.uid)) {
+        uid("Orig Base MAC", orig_macs, 12, "            ");
+    }
+
+    field("Image VSD", &or_na(image_info.vsd()));
+    field(
+        "Device VSD",
+        &or_na(dev_info.map(|dev_info| dev_info.vsd()).unwrap_or_default()),
+    );
+    field("PSID", &image_info.psid());
+    if let Some(orig_psid) = mfg_info
+        .map(|mfg_info| mfg_info.psid())
+        .filter(|psid| *psid != image_info.psid())
+    {
+        field("Orig PSID", &orig_psid);
+    }
+
+    let signed = firmware.itoc()?.iter().any(|entry| {
+        matches!(
+            entry.entry_type,
+            ItocEntryType::ImageSignature256 | ItocEntryType::ImageSignature512
+        )
+    });
+    let security: Vec<&str> = [
+        (image_info.secure_boot, "secure-fw"),
+        (signed, "signed-fw"),
+        (image_info.debug_fw, "debug"),
+        (image_info.cs_tokens_supported, "cs-token"),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, name)| name)
+    .collect();
+    field("Security Attributes", &or_na(security.join(", ")));
+    Ok(())
+}
+
+fn update_dtoc_section_crc(firmware: &mut Firmware, entry_type: ItocEntryType) -> Result<()> {
+    Ok(firmware
+        .dtoc_entry(entry_type)?
+        .update_section_crc(firmware)?)
+}
+
+fn set_uids(mut firmware: Firmware, args: CliSetUids, macs: bool) -> Result<()> {
+    let mut dev_info = firmware.dev_info()?;
+    let uid = if macs {
+        &mut dev_info.uids.macs
+    } else {
+        &mut dev_info.uids.guids
+    };
+    uid.uid = args.uid;
+    dev_info.update()?;
+    dev_info.write(&mut firmware)?;
+
+    update_dtoc_section_crc(&mut firmware, ItocEntryType::DevInfo)?;
+
+    firmware.write(args.output)?;
+
+    Ok(())
+}
+
+fn show_vpd(firmware: Firmware) -> Result<()> {
+    let vpd = firmware.vpd()?;
+    let content = firmware.dtoc_entry(ItocEntryType::VpdR0)?;
+    println!("Identifier: {}", vpd.identifier);
+    for (area, keywords) in [("R", &vpd.read_only), ("W", &vpd.read_write)] {
+        for keyword in keywords {
+            match keyword.text() {
+                Some(text) => println!("{} {}: {}", area, keyword.keyword, text),
+                None => println!(
+                    "{} {}: {}",
+                    area,
+                    keyword.keyword,
+                    hex::encode(&keyword.data)
+                ),
+            }
+        }
+    }
+    let checksum = if vpd.checksum_valid(content.content().read_bytes(&firmware)) {
+        "ok"
+    } else {
+        "BAD"
+    };
+    println!("Checksum: {}", checksum);
+    Ok(())
+}
+
+fn set_vpd(mut firmware: Firmware, keyword: String, value: String, output: PathBuf) -> Result<()> {
+    let mut vpd = firmware.vpd()?;
+    vpd.set(&keyword, value.into_bytes())?;
+    firmware.write_vpd(&vpd)?;
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn run_lint(firmware: Firmware) -> Result<()> {
+    let findings = lint::lint(&firmware);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    ensure!(
+        findings.is_empty(),
+        "Lint found {} problem(s)",
+        findings.len()
+    );
+    Ok(())
+}
+
+fn show_coverage(firmware: Firmware, unprotected: bool, json: bool) -> Result<()> {
+    let mut report = coverage::report(&firmware)?;
+    if unprotected {
+        report.ranges.retain(|range| range.protections.is_empty());
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if report.signature_range_unknown {
+        warn!("Image is signed, but the HW pointers do not define the signed range");
+    }
+    for range in &report.ranges {
+        let protections: Vec<String> = range.protections.iter().map(|p| p.to_string()).collect();
+        println!(
+            "{:#010x}-{:#010x} {:>#10x} bytes  {}{}",
+            range.offset,
+            range.end - 1,
+            range.size(),
+            match protections.is_empty() {
+                true => "unprotected".to_string(),
+                false => protections.join(", "),
+            },
+            if range.erased { " (erased)" } else { "" }
+        );
+    }
+    if unprotected {
+        return Ok(());
+    }
+    println!();
+    for protection in [
+        Some(coverage::Protection::Signature),
+        Some(coverage::Protection::HashesTable),
+        Some(coverage::Protection::SectionCrc),
+        Some(coverage::Protection::HeaderCrc),
+        None,
+    ] {
+        let name = protection.map_or("unprotected".to_string(), |p| p.to_string());
+        println!(
+            "{:<14}{:#x} bytes",
+            format!("{}:", name),
+            report.bytes(protection)
+        );
+    }
+    Ok(())
+}
+
+fn show_security(firmware: Firmware, json: bool) -> Result<()> {
+    let report = security::report(&firmware)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    let flag = |flag: Option<bool>| match flag {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "unknown",
+    };
+    println!("Secure boot:   {}", flag(report.secure_boot));
+    println!("Debug FW:      {}", flag(report.debug_fw));
+    println!("Debug tokens:  {}", flag(report.debug_tokens));
+    match report.encrypted_sections.as_slice() {
+        [] => println!("Encrypted:     none"),
+        sections => println!("Encrypted:     {}", sections.join(", ")),
+    }
+    if report.signatures.is_empty() {
+        println!("Signatures:    none");
+    }
+    for signature in &report.signatures {
+        println!(
+            "Signature:     {} key {} ({}{})",
+            signature.section_type,
+            signature.uuid,
+            if signature.crc_ok {
+                "CRC ok"
+            } else {
+                "CRC BAD"
+            },
+            if signature.key_present {
+                ", key present"
+            } else {
+                ", key missing"
+            }
+        );
+    }
+    println!("Public keys:   {}", report.public_keys.len());
+    for key in &report.public_keys {
+        println!(
+            "  {} RSA-{} e={} sha256:{}",
+            key.uuid, key.bits, key.exponent, key.fingerprint
+        );
+    }
+    println!(
+        "Hashes table:  {}",
+        if report.hashes_table { "yes" } else { "no" }
+    );
+    Ok(())
+}
+
+fn verify_firmware(
+    firmware: Firmware,
+    profile: Profile,
+    expected_version: Option<FwVersion>,
+) -> Result<()> {
+    let progress = ByteProgress::new("Verifying", 0);
+    let findings = verify::verify_with_progress(&firmware, profile, expected_version, &progress)?;
+    drop(progress);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    ensure!(
+        !findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Fatal),
+        "Verification failed"
+    );
+    println!("OK");
+    Ok(())
+}
+
+fn run_scan(dir: PathBuf, profile: Profile, json: bool) -> Result<()> {
+    let entries = scan::scan(&dir, profile)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for entry in &entries {
+            let result = match &entry.error {
+                Some(err) => format!("ERROR {}", err),
+                None if entry.failures > 0 => format!("FAIL ({} fatal)", entry.failures),
+                None => "OK".to_string(),
+            };
+            println!(
+                "{:40} {:14} {:16} {:10} {}{}",
+                entry.path.display(),
+                entry.chip.as_deref().unwrap_or("unknown"),
+                entry.psid.as_deref().unwrap_or("-"),
+                entry.fw_version.as_deref().unwrap_or("-"),
+                result,
+                match entry.warnings {
+                    0 => String::new(),
+                    n => format!(", {} warnings", n),
+                }
+            );
+        }
+    }
+    let failed = entries.iter().filter(|entry| !entry.passed()).count();
+    ensure!(
+        failed == 0,
+        "{} of {} images failed verification",
+        failed,
+        entries.len()
+    );
+    Ok(())
+}
+
+fn show_changelog(firmware: Firmware, other_path: PathBuf) -> Result<()> {
+    let other = Firmware::read(other_path).context("Could not open other firmware")?;
+    let changelog = changelog::changelog(&firmware, &other)?;
+    println!("{}", serde_json::to_string_pretty(&changelog)?);
+    Ok(())
+}
+
+fn check_downgrade(firmware: Firmware, target_path: PathBuf, json: bool) -> Result<()> {
+    let target = Firmware::read(target_path).context("Could not open target image")?;
+    let check = security::check_downgrade(&firmware, &target)?;
+    if json {
+        let report = serde_json::json!({ "check": check, "accepted": check.accepted() });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        let security_version = |version: Option<u32>| {
+            version.map_or_else(|| "none".to_string(), |version| version.to_string())
+        };
+        println!(
+            "FW version:       {} -> {}{}",
+            check.running_version,
+            check.target_version,
+            if check.downgrade { " (downgrade)" } else { "" }
+        );
+        println!(
+            "Security version: {} -> {}",
+            security_version(check.running_security_version),
+            security_version(check.target_security_version)
+        );
+        if check.forbidden {
+            println!("{} is a forbidden version", check.target_version);
+        }
+        if check.security_version_rollback() {
+            println!("The security version would roll back");
+        }
+    }
+    ensure!(check.accepted(), "The device would refuse the target image");
+    if !json {
+        println!("OK");
+    }
+    Ok(())
+}
+
+fn compare_release(firmware: Firmware, release_path: PathBuf, json: bool) -> Result<()> {
+    let release = Firmware::read(release_path).context("Could not open release image")?;
+    let comparison = provenance::compare_release(&firmware, &release)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+    } else {
+        println!(
+            "PSID:       {} (release {})",
+            comparison.psid.as_deref().unwrap_or("-"),
+            comparison.release_psid.as_deref().unwrap_or("-")
+        );
+        println!(
+            "FW version: {} (release {})",
+            comparison.fw_version.as_deref().unwrap_or("-"),
+            comparison.release_fw_version.as_deref().unwrap_or("-")
+        );
+        if let Some(boot2) = comparison.boot2 {
+            println!("boot2:      {:?}", boot2);
+        }
+        for change in &comparison.sections {
+            let flash_addr = change
+                .new
+                .as_ref()
+                .or(change.old.as_ref())
+                .unwrap()
+                .flash_addr;
+            println!(
+                "{:?} {} #{} at {:#010x}",
+                change.status, change.section_type, change.occurrence, flash_addr
+            );
+        }
+    }
+    ensure!(comparison.psid_matches(), "PSID differs from the release");
+    ensure!(comparison.pristine(), "Image differs from the release");
+    println!("Pristine");
+    Ok(())
+}
+
+fn show_nv(firmware: Firmware) -> Result<()> {
+    for dtoc_entry in firmware.dtoc()? {
+        if !dtoc_entry.entry_type.is_nv_data() {
+            continue;
+        }
+        println!(
+            "{} @ {:#010x}",
+            dtoc_entry.entry_type, dtoc_entry.flash_addr
+        );
+        show_nv_tlvs(&firmware, &dtoc_entry)?;
+    }
+    Ok(())
+}
+
+fn show_nv_tlvs(firmware: &Firmware, dtoc_entry: &ItocEntry) -> Result<()> {
+    for tlv in firmware.nv_tlvs(dtoc_entry)? {
+        println!(
+            "  {:#010x}: {} (version {}, priority {})",
+            tlv.0, *tlv, tlv.version, tlv.priority
         );
+        match tlv.known_type() {
+            Some(tlv_type) => {
+                for field in tlv_type.fields {
+                    match field.decode(&tlv) {
+                        Some(value) => println!("      {:20} = {:#x}", field.name, value),
+                        None => println!("      {:20} = <truncated>", field.name),
+                    }
+                }
+            }
+            None => {
+                for (i, chunk) in tlv.data.chunks(16).enumerate() {
+                    let hex: Vec<String> =
+                        chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    println!("      {:04x}: {}", i * 16, hex.join(" "));
+                }
+            }
+        }
     }
     Ok(())
 }
 
-fn dump_sections(firmware: Firmware, dir: &PathBuf) -> Result<()> {
-    std::fs::create_dir(dir).context("Failed to create output directory")?;
-    for itoc_entry in firmware.itoc()? {
-        let content = firmware.slice(itoc_entry.flash_addr, itoc_entry.size);
-        std::fs::write(
-            dir.join(format!(
-                "{:08x}_{}",
-                itoc_entry.flash_addr, itoc_entry.entry_type
-            )),
-            content.1,
-        )?;
+fn show_nv_log(firmware: Firmware) -> Result<()> {
+    let nv_log = firmware.nv_log()?;
+    println!("FW_NV_LOG record layout unknown, raw contents:");
+    for (i, line) in nv_log.chunks(16).enumerate() {
+        println!("{}", hexdump_line(nv_log.0 + i * 16, line));
     }
     Ok(())
 }
 
-fn dump_code(firmware: Firmware, dir: &PathBuf) -> Result<()> {
-    std::fs::create_dir(dir).context("Failed to create output directory")?;
-    for itoc_entry in firmware.itoc()? {
-        if itoc_entry.entry_type.is_code() {
-            let content = &firmware[itoc_entry.flash_addr..][..itoc_entry.size];
-            let section_path = dir.join(format!(
-                "{:08x}_{}",
-                itoc_entry.load_address, itoc_entry.entry_type
-            ));
-            if itoc_entry.cache_line_crc {
-                let mut code = vec![];
-                for chunk in content.chunks(0x44) {
-                    if chunk.len() == 0x44 {
-                        code.extend_from_slice(&chunk[..0x40]);
-                    }
-                }
-                std::fs::write(section_path, code)?;
-            } else {
-                std::fs::write(section_path, content)?;
+fn show_forbidden(firmware: Firmware) -> Result<()> {
+    let forbidden = firmware.forbidden_versions()?;
+    for version in &forbidden.versions {
+        println!("{}", version);
+    }
+    Ok(())
+}
+
+/// The current time as an ISO 8601 UTC timestamp, e.g. 2024-05-01T12:00:00Z
+fn utc_now() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // Civil date from days since 1970-01-01, counted in 400 year eras starting in March
+    let days = days + 719468;
+    let (era, day_of_era) = (days / 146097, days % 146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+fn tag(mut firmware: Firmware, note: String, output: PathBuf) -> Result<()> {
+    let tag = tag::Tag::new(&firmware, utc_now(), note);
+    let entry = tag::write(&mut firmware, &tag)?;
+    info!("Tag written to {:#010x}", entry.flash_addr);
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn show_tag(firmware: Firmware, json: bool) -> Result<()> {
+    let tag = tag::read(&firmware)?.context("Image has no tag")?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tag)?);
+        return Ok(());
+    }
+    println!("Note:            {}", tag.note);
+    println!("Date:            {}", tag.date);
+    println!("Tool version:    {}", tag.tool_version);
+    println!("Original image:  sha256:{}", tag.original_sha256);
+    Ok(())
+}
+
+fn patch(mut firmware: Firmware, args: CliPatch) -> Result<()> {
+    if args.strip_forbidden {
+        strip_forbidden(&mut firmware)?;
+    }
+
+    firmware.write(args.output)?;
+
+    Ok(())
+}
+
+fn hexpatch(mut firmware: Firmware, args: CliHexPatch) -> Result<()> {
+    ensure!(
+        args.offsets.len() == args.bytes.len(),
+        "Every --at needs a --bytes, got {} and {}",
+        args.offsets.len(),
+        args.bytes.len()
+    );
+    let mut patches = vec![];
+    for (offset, bytes) in args.offsets.into_iter().zip(args.bytes) {
+        let Some(end) = offset.checked_add(bytes.len()) else {
+            bail!(
+                "Patch of {:#x} bytes at {:#x} overflows",
+                bytes.len(),
+                offset
+            );
+        };
+        patches.push((offset..end, bytes));
+    }
+    for (range, bytes) in &patches {
+        FirmwareStructure(range.start, ()).write_bytes(&mut firmware, bytes)?;
+    }
+
+    let itoc = firmware.itoc()?;
+    let dtoc = firmware.dtoc().unwrap_or_default();
+    let overlaps = |range: &std::ops::Range<usize>, entry: &ItocEntry| -> Result<bool> {
+        let Some(end) = entry.flash_addr.checked_add(entry.size) else {
+            bail!(
+                "{} at {:#x}+{:#x} overflows",
+                entry.entry_type,
+                entry.flash_addr,
+                entry.size
+            );
+        };
+        Ok(range.start < end && entry.flash_addr < range.end)
+    };
+    let touches = |entry: &ItocEntry| -> Result<bool> {
+        for (range, _) in &patches {
+            if overlaps(range, entry)? {
+                return Ok(true);
             }
         }
+        Ok(false)
+    };
+    for (range, _) in &patches {
+        let mut touched = false;
+        for entry in itoc.iter().chain(&dtoc) {
+            touched |= overlaps(range, entry)?;
+        }
+        if !touched {
+            warn!(
+                "Patch at {:#010x} touches no section, its CRCs are not updated",
+                range.start
+            );
+        }
+    }
+    for (i, entry) in itoc.iter().enumerate() {
+        if touches(entry)? {
+            fix_crcs(&mut firmware, Some(i))?;
+        }
+    }
+    for mut entry in dtoc {
+        if !touches(&entry)? {
+            continue;
+        }
+        info!(
+            "{} at {:#010x}: section CRC",
+            entry.entry_type, entry.flash_addr
+        );
+        entry.update_section_crc(&mut firmware)?;
     }
+
+    firmware.write(args.output)?;
     Ok(())
 }
 
-fn replace_section(mut firmware: Firmware, args: CliReplaceSection) -> Result<()> {
-    let itoc = firmware.itoc()?;
+fn strip_forbidden(firmware: &mut Firmware) -> Result<()> {
+    let mut itoc_entry = firmware.itoc_entry(ItocEntryType::ForbiddenVersions)?;
+    let section = itoc_entry.content();
+    section.write_bytes(firmware, &vec![0x00; itoc_entry.size])?;
+
+    let mut forbidden = firmware.forbidden_versions()?;
+    forbidden.num_versions = 0;
+    forbidden.versions.clear();
+    forbidden.write(firmware)?;
+
+    Ok(itoc_entry.update_section_crc(firmware)?)
+}
+
+fn backup_dev_data(firmware: Firmware, output: PathBuf) -> Result<()> {
+    let backup = devdata::backup(&firmware)?;
+    std::fs::write(output, serde_json::to_string_pretty(&backup)?)
+        .context("Could not write backup")?;
+    Ok(())
+}
+
+fn sanitize(mut firmware: Firmware, output: PathBuf) -> Result<()> {
+    let sanitized = devdata::sanitize(&mut firmware)?;
+    ensure!(!sanitized.is_empty(), "No device data to sanitize");
+    for entry_type in sanitized {
+        info!("Sanitized {}", entry_type);
+    }
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn strip_security(mut firmware: Firmware, output: PathBuf) -> Result<()> {
+    let stripped = security::strip(&mut firmware)?;
     ensure!(
-        args.section_index < itoc.len(),
-        "Section index out of range"
+        !stripped.is_empty(),
+        "No signatures, public keys or secure boot flags to strip"
     );
+    for entry_type in &stripped.sections {
+        info!("Removed {}", entry_type);
+    }
+    for flag in &stripped.flags {
+        info!("Cleared IMAGE_INFO {}", flag);
+    }
+    for entry_type in &stripped.stale_digests {
+        warn!("The HASHES_TABLE digest of {} is now stale", entry_type);
+    }
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn restore_dev_data(
+    mut firmware: Firmware,
+    backup: PathBuf,
+    output: PathBuf,
+    force: bool,
+) -> Result<()> {
+    let backup = std::fs::read_to_string(backup).context("Could not read backup")?;
+    devdata::restore(&mut firmware, &serde_json::from_str(&backup)?, force)?;
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn dump_dbg_ini(firmware: Firmware, output: PathBuf) -> Result<()> {
+    std::fs::write(output, firmware.dbg_fw_ini()?).context("Could not write DBG_FW_INI")?;
+    Ok(())
+}
 
-    let mut itoc_entry = itoc[args.section_index].clone();
+fn replace_dbg_ini(mut firmware: Firmware, ini: PathBuf, output: PathBuf) -> Result<()> {
+    let ini = std::fs::read(ini).context("Could not read new DBG_FW_INI")?;
+    let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+    encoder.write_all(&ini)?;
+    let mut compressed = encoder.finish()?;
+    compressed.resize(compressed.len().next_multiple_of(4), 0x00);
 
-    let section_content = if itoc_entry.cache_line_crc && !args.no_fix_cache_line_crc {
-        let section =
-            std::fs::read(args.section_content).context("Could not read new section content")?;
-        let mut content = vec![];
-        for cache_line in section.chunks(0x40) {
-            let mut cache_line = cache_line.to_vec();
-            cache_line.extend_from_slice(&[0x00, 0x00]);
-            cache_line.extend_from_slice(&crc::calc_hwcrc(0x0000, &cache_line).to_le_bytes());
-            content.extend_from_slice(&cache_line);
-        }
-        content
-    } else {
-        std::fs::read(args.section_content).context("Could not read new section content")?
-    };
+    let mut itoc_entry = firmware.itoc_entry(ItocEntryType::DbgFwIni)?;
+    ensure!(
+        compressed.len() <= itoc_entry.size,
+        "Compressed DBG_FW_INI is too big"
+    );
+
+    firmware.fill(itoc_entry.flash_addr, itoc_entry.size)?;
+    itoc_entry.size = compressed.len();
+    itoc_entry
+        .content()
+        .write_bytes(&mut firmware, &compressed)?;
+    itoc_entry.update_section_crc(&mut firmware)?;
+
+    firmware.write(output)?;
 
+    Ok(())
+}
+
+fn replace_boot2(mut firmware: Firmware, boot2_path: PathBuf, output: PathBuf) -> Result<()> {
+    ensure!(
+        !firmware.is_fs5(),
+        "FS5 images have boot components instead of boot2, which are not parsed yet"
+    );
+    let data = std::fs::read(boot2_path).context("Could not read new boot2")?;
+    let (_, boot2) = Boot2::from_bytes((&data, 0))?;
     ensure!(
-        section_content.len() <= itoc_entry.size,
-        "New Section content is too big"
+        boot2.total_size() == data.len(),
+        "boot2 header says {:#x} bytes, file has {:#x}",
+        boot2.total_size(),
+        data.len()
     );
 
-    let section = firmware.slice_ptr(itoc_entry.flash_addr, itoc_entry.size);
-    section.write_bytes(&mut firmware, &section_content)?;
+    let old = firmware.boot2()?;
+    let limit = firmware
+        .parse()
+        .regions()
+        .iter()
+        .filter(|region| region.offset > old.0)
+        .map(|region| region.offset)
+        .min()
+        .unwrap_or(firmware.len());
+    ensure!(
+        old.0 + boot2.total_size() <= limit,
+        "New boot2 ({:#x} bytes) does not fit before {:#x}",
+        boot2.total_size(),
+        limit
+    );
 
-    itoc_entry.section_crc = crc::calc_crc16(0x0000, section.read_bytes(&firmware));
-    itoc_entry.section_crc = crc::calc_crc16(itoc_entry.section_crc, &[0x00, 0x00]);
-    itoc_entry.update()?;
+    firmware.fill(old.0, old.total_size())?;
+    let mut boot2 = FirmwareStructure(old.0, boot2);
+    boot2.update()?;
+    boot2.write(&mut firmware)?;
+    info!("boot2 CRC: {:#06x}", boot2.crc);
 
-    itoc_entry.write(&mut firmware)?;
+    firmware.write(output)?;
+    Ok(())
+}
 
-    firmware.write(args.output)?;
+fn dump_boot2(firmware: Firmware, info: bool, output: Option<PathBuf>) -> Result<()> {
+    ensure!(
+        !firmware.is_fs5(),
+        "FS5 images have boot components instead of boot2, which are not parsed yet"
+    );
+    let boot2 = firmware.boot2()?;
+    if info {
+        let crc = boot2.calc_crc()?;
+        println!("Offset:  {:#010x}", boot2.0);
+        println!("Header:  {:#010x}", boot2.header);
+        println!(
+            "Size:    {:#x} dwords ({:#x} bytes total)",
+            boot2.size,
+            boot2.total_size()
+        );
+        println!("dword0:  {:#010x}", boot2.dword0);
+        println!(
+            "CRC:     {:#06x} at {:#010x} ({})",
+            boot2.crc,
+            boot2.0 + boot2.crc_offset(),
+            if boot2.crc == crc {
+                "ok".to_string()
+            } else {
+                format!("calculated {:#06x}", crc)
+            }
+        );
+    }
+    if let Some(output) = output {
+        let content = firmware.slice(boot2.0, boot2.total_size());
+        std::fs::write(output, content.1).context("Could not write boot2")?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "conformance")]
+fn run_conformance(expected: Option<PathBuf>, bless: bool) -> Result<()> {
+    let expected = expected
+        .unwrap_or_else(|| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/conformance")));
+    let deviations = conformance::run(&std::env::current_exe()?, &expected, bless)?;
+    for deviation in &deviations {
+        println!("DEVIATION {}", deviation);
+    }
+    ensure!(deviations.is_empty(), "{} deviations", deviations.len());
+    println!("OK");
+    Ok(())
+}
+
+fn run_corpus(command: CliCorpusCommand) -> Result<()> {
+    match command {
+        CliCorpusCommand::Index { dir } => {
+            let index = CorpusIndex::build(&dir)?;
+            for entry in &index.entries {
+                match &entry.error {
+                    Some(error) => println!("{}: {}", entry.path.display(), error),
+                    None => println!(
+                        "{}: {} {}",
+                        entry.path.display(),
+                        entry.psid.as_deref().unwrap_or("-"),
+                        entry.fw_version.as_deref().unwrap_or("-")
+                    ),
+                }
+            }
+            Ok(index.write(&dir)?)
+        }
+        CliCorpusCommand::Find {
+            dir,
+            fw_version,
+            psid,
+            sha256,
+        } => {
+            let index = CorpusIndex::read(&dir).context("Run corpus index first")?;
+            let query = CorpusQuery {
+                fw_version,
+                psid,
+                sha256,
+            };
+            for entry in index.find(&query) {
+                println!("{}", dir.join(&entry.path).display());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn revert(mut firmware: Firmware, record: PathBuf, output: PathBuf) -> Result<()> {
+    let record = PatchRecord::read(&record)?;
+    record.revert(&mut firmware)?;
+    firmware.write(output)?;
+    info!("Reverted {} ranges", record.ranges.len());
+    Ok(())
+}
+
+fn show_fingerprint(
+    firmware: Firmware,
+    db: Option<PathBuf>,
+    record: bool,
+    note: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let fingerprint = provenance::fingerprint(&firmware)?;
+    let mut known = match &db {
+        Some(db) => provenance::KnownImages::read(db)?,
+        None => Default::default(),
+    };
+    let found = known.find(&fingerprint).cloned();
+
+    if json {
+        let report = serde_json::json!({ "fingerprint": fingerprint, "known": found });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", fingerprint.sha256);
+        let na = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+        match (&db, &found) {
+            (_, Some(image)) => println!(
+                "Matches {} {} for PSID {}",
+                image.note.as_deref().unwrap_or("known image"),
+                na(&image.fingerprint.fw_version),
+                na(&image.fingerprint.psid)
+            ),
+            (Some(_), None) => println!("Not in the database"),
+            (None, None) => {}
+        }
+    }
+
+    if let (true, Some(db)) = (record, &db) {
+        known.record(fingerprint, note);
+        known.write(db)?;
+        info!("Recorded in {}", db.display());
+    }
+    Ok(())
+}
+
+fn reconstruct(
+    mut firmware: Firmware,
+    size: Option<usize>,
+    marker: Vec<u8>,
+    json: bool,
+    output: PathBuf,
+) -> Result<()> {
+    let report = reconstruct::reconstruct(&mut firmware, size, &marker)?;
+    // Missing sections are the point of the output, lint would refuse every incomplete dump
+    firmware.set_lint_on_write(false);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for section in &report.sections {
+            let crc = match section.crc_ok {
+                Some(true) => ", CRC ok",
+                Some(false) => ", CRC mismatch",
+                None => "",
+            };
+            println!(
+                "{:>3} {:<24} {:#010x} {:#10x} {} ({:#x} bytes present{})",
+                section.index,
+                section.entry_type,
+                section.flash_addr,
+                section.size,
+                section.presence,
+                section.present,
+                crc
+            );
+        }
+        if let Some(err) = &report.itoc_error {
+            println!("ITOC incomplete: {}", err);
+        }
+        println!(
+            "{} complete, {} truncated, {} missing sections",
+            report.count(Presence::Complete),
+            report.count(Presence::Truncated),
+            report.count(Presence::Missing)
+        );
+        if !report.has_dtoc {
+            println!("No DTOC at the end of the dump, device data is missing");
+        }
+    }
+    firmware.write(output)?;
+    if report.missing() > 0 {
+        warn!(
+            "Marked {:#x}..{:#x} as missing",
+            report.dump_size, report.image_size
+        );
+    }
+    Ok(())
+}
 
+fn apply_delta(mut firmware: Firmware, delta: PathBuf, output: PathBuf) -> Result<()> {
+    let delta = PatchRecord::read(&delta)?;
+    delta.apply(&mut firmware)?;
+    firmware.write(output)?;
+    info!("Applied {} ranges", delta.ranges.len());
     Ok(())
 }
 
+fn apply_plan(
+    mut firmware: Firmware,
+    plan_path: PathBuf,
+    output: PathBuf,
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    let plan = Plan::read(&plan_path)?;
+    let dir = plan_path.parent().unwrap_or(Path::new("."));
+    plan.apply(&mut firmware, dir, cipher)?;
+    firmware.write(output)?;
+    Ok(())
+}
+
+fn show_hashes(firmware: Firmware, sha512: bool) -> Result<()> {
+    let mut regions = vec![];
+    for (i, itoc_entry) in firmware.itoc()?.iter().enumerate() {
+        regions.push((
+            format!("section {} {}", i, itoc_entry.entry_type),
+            itoc_entry.content().read_bytes(&firmware),
+        ));
+    }
+    for (name, range) in firmware.hwpointers()?.signed_ranges() {
+        ensure!(range.end <= firmware.len(), "{} region out of bounds", name);
+        regions.push((
+            format!("{} region {:#010x}-{:#010x}", name, range.start, range.end),
+            &firmware[range],
+        ));
+    }
+
+    let progress = ByteProgress::new("Hashing", regions.iter().map(|(_, data)| data.len()).sum());
+    let mut lines = vec![];
+    for (name, data) in regions {
+        lines.push(format!(
+            "sha256 {} {}",
+            hex::encode(Sha256::digest(data)),
+            name
+        ));
+        if sha512 {
+            lines.push(format!(
+                "sha512 {} {}",
+                hex::encode(Sha512::digest(data)),
+                name
+            ));
+        }
+        progress.advance(data.len());
+    }
+    drop(progress);
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn parse_number(value: &str) -> Result<usize> {
+    match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .context("Invalid number")
+}
+
+fn parse_cache_line_size(value: &str) -> Result<usize> {
+    let size = parse_number(value)?;
+    ensure!(size > 0, "A cache line holds at least one code byte");
+    Ok(size)
+}
+
+fn parse_byte(value: &str) -> Result<u8> {
+    u8::try_from(parse_number(value)?).context("Not a byte value")
+}
+
+/// A number with an optional K, M or G suffix, e.g. `32M`
+fn parse_size(value: &str) -> Result<usize> {
+    let (number, shift) = match value.to_ascii_uppercase().chars().last() {
+        Some('K') => (&value[..value.len() - 1], 10),
+        Some('M') => (&value[..value.len() - 1], 20),
+        Some('G') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    parse_number(number)?
+        .checked_mul(1 << shift)
+        .context("Size too large")
+}
+
+fn parse_section_file(value: &str) -> Result<(usize, PathBuf)> {
+    let (index, path) = value.split_once('=').context("Expected N=PATH")?;
+    Ok((parse_number(index)?, PathBuf::from(path)))
+}
+
+fn parse_hex_bytes(value: &str) -> Result<Vec<u8>> {
+    let value = value.trim_start_matches("0x").replace([' ', ':'], "");
+    let bytes = hex::decode(value).context("Invalid hex bytes")?;
+    ensure!(!bytes.is_empty(), "No bytes given");
+    Ok(bytes)
+}
+
+fn parse_uid(value: &str) -> Result<u64> {
+    let value = value.trim_start_matches("0x").replace([':', '-'], "");
+    u64::from_str_radix(&value, 16).context("Invalid GUID/MAC")
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliPatch {
+    /// Empty the FORBIDDEN_VERSIONS list
+    #[arg(long, default_value_t = false)]
+    strip_forbidden: bool,
+
+    output: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliHexPatch {
+    /// Offset of a patch, paired with the --bytes at the same position
+    #[arg(long = "at", value_parser = parse_number, required = true)]
+    offsets: Vec<usize>,
+    /// Hex bytes to write, e.g. DEADBEEF
+    #[arg(long = "bytes", value_parser = parse_hex_bytes, required = true)]
+    bytes: Vec<Vec<u8>>,
+    output: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliSetUids {
+    #[arg(value_parser = parse_uid)]
+    uid: u64,
+    output: PathBuf,
+}
+
 #[derive(Debug, Clone, Parser)]
 struct CliReplaceSection {
+    /// Leave the ITOC entry as it is, only a CRC kept in the section is updated
     #[arg(long, default_value_t = false)]
     no_update_itoc: bool,
     #[arg(long, default_value_t = false)]
     no_fix_cache_line_crc: bool,
+    /// Rewrite the ITOC with entries sorted by flash address
+    #[arg(long, default_value_t = false)]
+    sort_by_addr: bool,
+    /// The content has byte-swapped 32-bit words, as written with --swap32
+    #[arg(long, default_value_t = false)]
+    swap32: bool,
+    /// Replace the section with ITOC index N by the content of PATH, e.g. `--section 2=cfg.bin`.
+    /// Repeat to replace several sections in one write, with only OUTPUT given then.
+    #[arg(long = "section", value_name = "N=PATH", value_parser = parse_section_file)]
+    sections: Vec<(usize, PathBuf)>,
+    /// SECTION_INDEX SECTION_CONTENT OUTPUT, or only OUTPUT with --section. Content may be read
+    /// from standard input with `-`.
+    #[arg(value_name = "ARGS", num_args = 1..=3, required = true)]
+    args: Vec<PathBuf>,
+}
+
+impl CliReplaceSection {
+    /// The section index and content file of each replacement, and the output
+    fn replacements(&self) -> Result<(Vec<(usize, PathBuf)>, PathBuf)> {
+        match (self.sections.is_empty(), self.args.as_slice()) {
+            (true, [index, content, output]) => {
+                let index = index.to_str().context("Invalid section index")?;
+                Ok((
+                    vec![(parse_number(index)?, content.clone())],
+                    output.clone(),
+                ))
+            }
+            (false, [output]) => Ok((self.sections.clone(), output.clone())),
+            (true, _) => bail!("Expected SECTION_INDEX SECTION_CONTENT OUTPUT"),
+            (false, _) => bail!("Only OUTPUT is expected with --section"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliFixCrc {
+    /// Only fix the section with this ITOC index and its ITOC entry
+    #[arg(long, conflicts_with = "all")]
+    section: Option<usize>,
+    /// Fix every section, the ITOC header, boot2 and the HW pointers (the default)
+    #[arg(long, default_value_t = false)]
+    all: bool,
+
+    output: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliRemoveSection {
+    /// Rewrite the ITOC with entries sorted by flash address instead of keeping their order
+    #[arg(long, default_value_t = false)]
+    sort_by_addr: bool,
 
     section_index: usize,
-    section_content: PathBuf,
     output: PathBuf,
 }
 
+#[derive(Debug, Clone, Parser)]
+struct CliPackCode {
+    /// The code files have byte-swapped 32-bit words, as written with --swap32
+    #[arg(long, default_value_t = false)]
+    swap32: bool,
+    /// Directory of plain code files as written by dump-code
+    dir: PathBuf,
+    output: PathBuf,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum CliCorpusCommand {
+    /// Record PSID, FW version and SHA-256 of every file below a directory
+    #[command(name = "index")]
+    Index { dir: PathBuf },
+    /// List indexed files matching all given criteria
+    #[command(name = "find")]
+    Find {
+        dir: PathBuf,
+        #[arg(long = "version")]
+        fw_version: Option<FwVersion>,
+        #[arg(long)]
+        psid: Option<String>,
+        #[arg(long)]
+        sha256: Option<String>,
+    },
+}
+
 #[derive(Debug, Clone, Subcommand)]
 enum CliCommand {
     #[command(name = "show-sections")]
-    ShowSections,
+    ShowSections {
+        /// Show every ITOC entry field and whether each CRC validates
+        #[arg(long, default_value_t = false)]
+        verbose: bool,
+    },
     #[command(name = "dump-sections")]
-    DumpSections { dir: PathBuf },
+    DumpSections(CliDumpSections),
+    /// Write the code of each code section without cache-line CRCs, named by load address. Each
+    /// file gets a .json next to it with the entry point, flash address and CRC status.
     #[command(name = "dump-code")]
-    DumpCode { dir: PathBuf },
+    DumpCode {
+        /// Byte-swap every 32-bit word, making the big-endian words little-endian
+        #[arg(long, default_value_t = false)]
+        swap32: bool,
+        dir: PathBuf,
+    },
+    #[command(name = "pack-code")]
+    PackCode(CliPackCode),
+    /// Split the PHY microcode sections at runs of padding. This is a heuristic: the section
+    /// header is undocumented, so the pieces need not match the per-core images.
+    #[command(name = "split-phy", alias = "dumpphy")]
+    SplitPhy {
+        /// Shortest run of 0x00 or 0xff bytes separating two pieces
+        #[arg(long, value_parser = parse_number, default_value_t = phy::DEFAULT_MIN_GAP)]
+        min_gap: usize,
+        dir: PathBuf,
+    },
+    /// List version strings, build dates and paths embedded in the code sections
+    #[command(name = "strings")]
+    Strings {
+        /// Shortest run of printable characters reported
+        #[arg(long, default_value_t = 6)]
+        min_len: usize,
+        /// Also list strings that look like neither
+        #[arg(long, default_value_t = false)]
+        all: bool,
+        /// Print the strings as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Restore the ITOC and every section of a dump-sections directory, reproducing the dumped
+    /// image
+    #[command(name = "pack-sections")]
+    PackSections { manifest: PathBuf, output: PathBuf },
+    /// Print what `mstflint query full` prints for an image, in the same format
+    #[command(name = "query")]
+    Query,
+    /// Print the image layout as a Graphviz DOT graph
+    #[command(name = "graph")]
+    Graph,
+    #[command(name = "show-code-map")]
+    ShowCodeMap,
+    /// Which structure or gap contains a flash offset
+    #[command(name = "whatis")]
+    Whatis {
+        #[arg(value_parser = parse_number)]
+        address: usize,
+        /// Treat the address as an iRISC virtual address and look up the code section it is
+        /// loaded from
+        #[arg(long, default_value_t = false)]
+        va: bool,
+    },
+    #[command(name = "export-ghidra")]
+    ExportGhidra {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        output: PathBuf,
+    },
+    /// GNU ld script placing replacement code at the load address of each code section
+    #[command(name = "export-ld")]
+    ExportLd {
+        /// Only write the start, end and entry point symbols of each section
+        #[arg(long, default_value_t = false)]
+        symbols: bool,
+        output: PathBuf,
+    },
+    #[command(name = "fix-hwpointers")]
+    FixHwpointers { output: PathBuf },
+    /// Recompute CRCs over the current image contents, e.g. after editing it by hand
+    #[command(name = "fix-crc")]
+    FixCrc(CliFixCrc),
+    #[command(name = "show-crdump-mask")]
+    ShowCrdumpMask,
+    #[command(name = "inspect")]
+    Inspect {
+        #[arg(value_parser = parse_number)]
+        offset: usize,
+        #[arg(value_parser = parse_number, default_value = "0x100")]
+        len: usize,
+    },
+    /// Decode a section with the parser for its type, or hexdump it if there is none
+    #[command(name = "info")]
+    Info {
+        /// Index of the section in the ITOC, as listed by show-sections
+        #[arg(long)]
+        section: usize,
+        /// Index into the DTOC instead
+        #[arg(long, default_value_t = false)]
+        dtoc: bool,
+    },
+    #[command(name = "dump-mem")]
+    DumpMem { output: PathBuf },
     #[command(name = "replace-section")]
     ReplaceSection(CliReplaceSection),
+    /// Drop an ITOC entry and erase its content unless another entry uses it
+    #[command(name = "remove-section")]
+    RemoveSection(CliRemoveSection),
+    /// Drop the erase fill after the last section, keeping the device data at the end
+    #[command(name = "trim")]
+    Trim(CliTrim),
+    /// Pad the image with the fill byte to the flash size, moving the device data to the end of
+    /// flash
+    #[command(name = "expand")]
+    Expand {
+        /// Flash size, e.g. 32M
+        #[arg(long, value_parser = parse_size)]
+        size: usize,
+        output: PathBuf,
+    },
+    #[command(name = "show-dev-info")]
+    ShowDevInfo,
+    #[command(name = "set-guids")]
+    SetGuids(CliSetUids),
+    #[command(name = "set-macs")]
+    SetMacs(CliSetUids),
+    #[command(name = "show-vpd")]
+    ShowVpd,
+    /// Set a VPD keyword, e.g. SN for the serial number, and recompute the checksum
+    #[command(name = "set-vpd")]
+    SetVpd {
+        keyword: String,
+        value: String,
+        output: PathBuf,
+    },
+    /// Summarize secure boot, debug, encryption, signature and key attributes
+    #[command(name = "security")]
+    Security {
+        /// Print the report as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Check for show-stoppers, as done before writing any image
+    #[command(name = "lint")]
+    Lint,
+    /// Show which byte ranges are protected by the signature, the hashes table and CRCs
+    #[command(name = "coverage")]
+    Coverage {
+        /// Only list ranges without any protection
+        #[arg(long, default_value_t = false)]
+        unprotected: bool,
+        /// Print the report as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    #[command(name = "verify")]
+    Verify {
+        #[arg(long, value_enum, default_value_t = Profile::Strict)]
+        profile: Profile,
+        /// Fail unless IMAGE_INFO is of this release, e.g. 22.41.1000. Sections whose version
+        /// suggests another release are warned about, to catch images assembled from several.
+        #[arg(long)]
+        expected_version: Option<FwVersion>,
+    },
+    #[command(name = "changelog")]
+    Changelog { other: PathBuf },
+    /// Check whether the image is an unmodified vendor release, ignoring device data
+    #[command(name = "compare-release")]
+    CompareRelease {
+        release: PathBuf,
+        /// Print the comparison as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Check whether a device running the image would accept the target image, going by the
+    /// image's FORBIDDEN_VERSIONS and the security version counters
+    #[command(name = "check-downgrade")]
+    CheckDowngrade {
+        target: PathBuf,
+        /// Print the check as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    #[command(name = "show-nv")]
+    ShowNv,
+    /// Hexdump the firmware event log kept in the DTOC. Its record layout is unknown, so
+    /// timestamps and event IDs are not decoded yet.
+    #[command(name = "show-nv-log")]
+    ShowNvLog,
+    #[command(name = "show-forbidden")]
+    ShowForbidden,
+    /// Embed a section with a note, the date, the mlx5fw version and the hash of the image, so
+    /// the patched image can be identified later. An existing tag is replaced.
+    #[command(name = "tag")]
+    Tag {
+        #[arg(long)]
+        note: String,
+        output: PathBuf,
+    },
+    /// Print the tag written by `tag`
+    #[command(name = "show-tag", alias = "showtag")]
+    ShowTag {
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    #[command(name = "patch")]
+    Patch(CliPatch),
+    /// Write raw bytes, then fix the CRCs of every section they touch
+    #[command(name = "hexpatch")]
+    HexPatch(CliHexPatch),
+    /// Save the device data sections of an image file. Reading them from a device is not
+    /// supported yet.
+    #[command(name = "backup-dev-data", alias = "backup-devdata")]
+    BackupDevData { output: PathBuf },
+    /// Write a device data backup into an image file, refusing a backup of another PSID unless
+    /// --force is given. Writing to a device is not supported yet.
+    #[command(name = "restore-dev-data", alias = "restore-devdata")]
+    RestoreDevData { backup: PathBuf, output: PathBuf },
+    /// Replace GUIDs, MACs, serial number and VSD with placeholders, for sharing dumps
+    #[command(name = "sanitize")]
+    Sanitize { output: PathBuf },
+    /// Remove the signature and public key sections and clear the secure boot flags, for boards
+    /// with secure boot fused off
+    #[command(name = "strip-security")]
+    StripSecurity { output: PathBuf },
+    #[command(name = "dump-dbg-ini")]
+    DumpDbgIni { output: PathBuf },
+    #[command(name = "replace-dbg-ini")]
+    ReplaceDbgIni { ini: PathBuf, output: PathBuf },
+    /// Replace boot2 with a file as written by dump-boot2, recomputing its CRC
+    #[command(name = "replace-boot2")]
+    ReplaceBoot2 { boot2: PathBuf, output: PathBuf },
+    #[command(name = "dump-boot2")]
+    DumpBoot2 {
+        #[arg(long, default_value_t = false)]
+        info: bool,
+        #[arg(required_unless_present = "info")]
+        output: Option<PathBuf>,
+    },
+    #[cfg(feature = "conformance")]
+    #[command(name = "conformance")]
+    Conformance {
+        /// Directory with the expected outputs
+        #[arg(long)]
+        expected: Option<PathBuf>,
+        /// Rewrite the expected outputs instead of comparing against them
+        #[arg(long, default_value_t = false)]
+        bless: bool,
+    },
+    #[command(name = "corpus", subcommand)]
+    Corpus(CliCorpusCommand),
+    /// Show image info and verify every image below a directory, recursively
+    #[command(name = "scan")]
+    Scan {
+        dir: PathBuf,
+        #[arg(long, value_enum, default_value_t = Profile::Strict)]
+        profile: Profile,
+        /// Print the summary as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Digest of boot2 and all sections except device data, optionally looked up in and
+    /// recorded to a database of known images
+    #[command(name = "fingerprint")]
+    Fingerprint {
+        /// JSON database of known images
+        #[arg(long)]
+        db: Option<PathBuf>,
+        /// Add the image to the database
+        #[arg(long, default_value_t = false, requires = "db")]
+        record: bool,
+        /// Note recorded with the image, e.g. "vendor release"
+        #[arg(long, requires = "record")]
+        note: Option<String>,
+        /// Print the fingerprint and match as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Report which sections an incomplete dump, cut off at its end, contains and write it
+    /// padded with a marker to its full size
+    #[command(name = "reconstruct")]
+    Reconstruct {
+        /// Size of the reconstructed image, e.g. 32M. Defaults to the end of the last section.
+        #[arg(long, value_parser = parse_size)]
+        size: Option<usize>,
+        /// Hex bytes filling the missing ranges, repeated. The default keeps a cut off TOC
+        /// terminated.
+        #[arg(long, value_parser = parse_hex_bytes, default_value = "ff")]
+        marker: std::vec::Vec<u8>,
+        /// Print the report as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        output: PathBuf,
+    },
+    /// Undo the changes of a patch record written with --patch-record
+    #[command(name = "revert")]
+    Revert { record: PathBuf, output: PathBuf },
+    /// Apply a delta written with --emit-delta to the image it was made from
+    #[command(name = "apply-delta")]
+    ApplyDelta { delta: PathBuf, output: PathBuf },
+    /// Apply the operations of a YAML patch plan and write one output image
+    #[command(name = "apply")]
+    Apply { plan: PathBuf, output: PathBuf },
+    /// Verify the HASHES_TABLE digests. Modifications do not update them yet.
+    #[command(name = "hashes")]
+    Hashes {
+        /// Also print SHA-512 digests
+        #[arg(long, default_value_t = false)]
+        sha512: bool,
+    },
+    /// Browse sections, hexdumps, decoded structures and CRC status interactively
+    #[cfg(feature = "tui")]
+    #[command(name = "tui")]
+    Tui,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliDumpSections {
+    /// Byte-swap every 32-bit word, making the big-endian words little-endian
+    #[arg(long, default_value_t = false)]
+    swap32: bool,
+    /// File name of each section. Placeholders: {index} (ITOC index), {occurrence} (index among
+    /// the sections of the same type), {type}, {flash_addr} and {load_addr}.
+    #[arg(long, default_value = "{flash_addr}_{type}")]
+    name_template: String,
+    /// Write all sections to the output directory (default)
+    #[arg(long, default_value_t = false, conflicts_with = "by_type")]
+    flat: bool,
+    /// Write each section to a subdirectory named after its type
+    #[arg(long, default_value_t = false)]
+    by_type: bool,
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CliTrim {
+    /// Also drop the DTOC and the device data sections it lists
+    #[arg(long, default_value_t = false)]
+    strip_device_data: bool,
+    output: PathBuf,
 }
 
 #[derive(Debug, Clone, Parser)]
 struct CliArgs {
-    firmware_path: PathBuf,
+    /// Image or flash dump, `-` for standard input. Output paths accept `-` for standard output.
+    firmware_path: Option<PathBuf>,
+    #[arg(long, value_parser = parse_number)]
+    image_base: Option<usize>,
+    /// Byte to fill removed sections, gaps and padding with. The default is the erase state, which
+    /// programmers can skip when writing.
+    #[arg(long, value_parser = parse_byte, default_value = "0xff")]
+    fill: u8,
+    /// Undo the byte order a programmer stored the dump in. Detected from the image magic if not
+    /// given. Written images are normalized.
+    #[arg(long, value_enum)]
+    input_transform: Option<InputTransform>,
+    /// Number of code bytes per cache line in code sections, instead of the chip family's
+    #[arg(long, value_parser = parse_cache_line_size)]
+    cache_line_size: Option<usize>,
+    /// Chip family, detected from IMAGE_INFO if not given
+    #[arg(long, value_enum)]
+    device: Option<ChipFamily>,
+    /// AES-CTR key and IV for encrypted sections, as JSON {"key": "<hex>", "iv": "<hex>"}
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+    /// Write `<output>.patch.json` with the original bytes of every change, for `revert`
+    #[arg(long, default_value_t = false)]
+    patch_record: bool,
+    /// Write output images as a delta against the input, a patch record for `apply-delta`
+    #[arg(long, default_value_t = false)]
+    emit_delta: bool,
+    /// Make all checks but only log the byte ranges and sections that would change, with
+    /// their old and new CRCs, instead of writing output images
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+    /// Write images even if lint finds show-stoppers in them, let dump commands write into an
+    /// existing directory and restore device data of another PSID
+    #[arg(long, default_value_t = false)]
+    force: bool,
+    /// Allow writing a binary image to standard output (`-`) when it is a terminal
+    #[arg(long, default_value_t = false)]
+    force_tty: bool,
+    /// Color tables on standard output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Log more, -v logs every byte range written to the image, -vv also the bytes
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Log as JSON lines
+    #[arg(long, default_value_t = false)]
+    log_json: bool,
+    /// Number of threads for checking sections and images, all cores if not given
+    #[arg(long)]
+    jobs: Option<usize>,
     #[command(subcommand)]
     command: CliCommand,
 }
 
+/// Log to stderr, keeping stdout for command output and images
+fn init_logging(verbose: u8, json: bool) {
+    let level = match verbose {
+        0 => tracing::Level::INFO,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_target(false);
+    if json {
+        subscriber.json().init();
+    } else {
+        subscriber.without_time().init();
+    }
+}
+
 fn main() -> Result<()> {
     let args = CliArgs::parse();
-    let firmware = Firmware::read(args.firmware_path).context("Could not open firmware")?;
+    init_logging(args.verbose, args.log_json);
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+    #[cfg(feature = "conformance")]
+    if let CliCommand::Conformance { expected, bless } = args.command {
+        return run_conformance(expected, bless);
+    }
+    if let CliCommand::Corpus(command) = args.command {
+        return run_corpus(command);
+    }
+    if let CliCommand::Scan { dir, profile, json } = args.command {
+        return run_scan(dir, profile, json);
+    }
+    let firmware_path = args.firmware_path.context("No firmware given")?;
+    if let CliCommand::ReplaceSection(replace) = &args.command {
+        ensure!(
+            firmware_path != Path::new(STDIO_PATH)
+                || replace.replacements().is_ok_and(|(replacements, _)| {
+                    replacements
+                        .iter()
+                        .all(|(_, content)| content != Path::new(STDIO_PATH))
+                }),
+            "The firmware and the section content cannot both be read from standard input"
+        );
+    }
+    let mut firmware = Firmware::read(firmware_path).context("Could not open firmware")?;
+    if args.input_transform.is_some() {
+        firmware.set_input_transform(args.input_transform);
+    }
+    if let Some(transform) = firmware.input_transform() {
+        info!("Input is {}, normalized before parsing", transform);
+    }
+    if let Some(image_base) = args.image_base {
+        firmware.set_image_base(image_base)?;
+    }
+    firmware.set_cache_line_geometry(args.cache_line_size.map(CacheLineGeometry::new));
+    if args.patch_record {
+        firmware.record_patches();
+    }
+    if args.emit_delta {
+        firmware.emit_delta();
+    }
+    if args.dry_run {
+        firmware.dry_run();
+    }
+    firmware.set_fill_byte(args.fill);
+    firmware.set_terminal_output(args.force_tty);
+    firmware.set_lint_on_write(!args.force);
+    if matches!(firmware.image_format(), ImageFormat::Fs3 | ImageFormat::Fs5) {
+        warn!(
+            "The boot version says this is an {} image, which is not supported",
+            firmware.image_format()
+        );
+    }
+    let chip = args.device.or_else(|| firmware.detect_chip());
+    firmware.set_chip(chip);
+    let cipher = args
+        .key_file
+        .map(|key_file| AesCtrCipher::from_key_file(&key_file))
+        .transpose()?;
+    let cipher = cipher.as_ref().map(|cipher| cipher as &dyn SectionCipher);
+    let color = args.color.enabled();
     match args.command {
-        CliCommand::ShowSections => show_sections(firmware),
-        CliCommand::DumpSections { dir } => dump_sections(firmware, &dir),
-        CliCommand::DumpCode { dir } => dump_code(firmware, &dir),
-        CliCommand::ReplaceSection(args) => replace_section(firmware, args),
+        CliCommand::ShowSections { verbose } => show_sections(firmware, verbose, color),
+        CliCommand::DumpSections(dump_args) => {
+            dump_sections(firmware, dump_args, cipher, args.force)
+        }
+        CliCommand::DumpCode { swap32, dir } => {
+            dump_code(firmware, &dir, swap32, cipher, args.force)
+        }
+        CliCommand::PackCode(args) => pack_code(firmware, args, cipher),
+        CliCommand::SplitPhy { min_gap, dir } => {
+            split_phy(firmware, &dir, min_gap, cipher, args.force)
+        }
+        CliCommand::Strings { min_len, all, json } => {
+            show_strings(firmware, min_len, all, json, cipher)
+        }
+        CliCommand::PackSections { manifest, output } => {
+            pack_sections(firmware, &manifest, output, cipher)
+        }
+        CliCommand::Query => query(firmware),
+        CliCommand::Graph => {
+            print!("{}", graph::dot(&firmware)?);
+            Ok(())
+        }
+        CliCommand::ShowCodeMap => show_code_map(firmware),
+        CliCommand::Whatis { address, va: false } => whatis(firmware, address),
+        CliCommand::Whatis { address, va: true } => whatis_va(firmware, address as u64),
+        CliCommand::ExportGhidra { format, output } => export_code_layout(firmware, format, output),
+        CliCommand::ExportLd { symbols, output } => export_linker_script(firmware, symbols, output),
+        CliCommand::FixHwpointers { output } => fix_hwpointers(firmware, output),
+        CliCommand::FixCrc(args) => fix_crc(firmware, args),
+        CliCommand::ShowCrdumpMask => show_crdump_mask(firmware),
+        CliCommand::Inspect { offset, len } => inspect(firmware, offset, len),
+        CliCommand::Info { section, dtoc } => show_section_info(firmware, section, dtoc),
+        CliCommand::DumpMem { output } => dump_mem(firmware, output),
+        CliCommand::ReplaceSection(args) => replace_section(firmware, args, cipher),
+        CliCommand::RemoveSection(args) => remove_section(firmware, args),
+        CliCommand::Trim(args) => trim(firmware, args),
+        CliCommand::Expand { size, output } => expand(firmware, size, output),
+        CliCommand::ShowDevInfo => show_dev_info(firmware),
+        CliCommand::SetGuids(args) => set_uids(firmware, args, false),
+        CliCommand::SetMacs(args) => set_uids(firmware, args, true),
+        CliCommand::ShowVpd => show_vpd(firmware),
+        CliCommand::SetVpd {
+            keyword,
+            value,
+            output,
+        } => set_vpd(firmware, keyword, value, output),
+        CliCommand::Security { json } => show_security(firmware, json),
+        CliCommand::Lint => run_lint(firmware),
+        CliCommand::Coverage { unprotected, json } => show_coverage(firmware, unprotected, json),
+        CliCommand::Verify {
+            profile,
+            expected_version,
+        } => verify_firmware(firmware, profile, expected_version),
+        CliCommand::Changelog { other } => show_changelog(firmware, other),
+        CliCommand::CompareRelease { release, json } => compare_release(firmware, release, json),
+        CliCommand::CheckDowngrade { target, json } => check_downgrade(firmware, target, json),
+        CliCommand::ShowNv => show_nv(firmware),
+        CliCommand::ShowNvLog => show_nv_log(firmware),
+        CliCommand::ShowForbidden => show_forbidden(firmware),
+        CliCommand::Tag { note, output } => tag(firmware, note, output),
+        CliCommand::ShowTag { json } => show_tag(firmware, json),
+        CliCommand::Patch(args) => patch(firmware, args),
+        CliCommand::HexPatch(args) => hexpatch(firmware, args),
+        CliCommand::BackupDevData { output } => backup_dev_data(firmware, output),
+        CliCommand::RestoreDevData { backup, output } => {
+            restore_dev_data(firmware, backup, output, args.force)
+        }
+        CliCommand::Sanitize { output } => sanitize(firmware, output),
+        CliCommand::StripSecurity { output } => strip_security(firmware, output),
+        CliCommand::DumpDbgIni { output } => dump_dbg_ini(firmware, output),
+        CliCommand::ReplaceDbgIni { ini, output } => replace_dbg_ini(firmware, ini, output),
+        CliCommand::ReplaceBoot2 { boot2, output } => replace_boot2(firmware, boot2, output),
+        CliCommand::DumpBoot2 { info, output } => dump_boot2(firmware, info, output),
+        #[cfg(feature = "conformance")]
+        CliCommand::Conformance { .. } => unreachable!(),
+        CliCommand::Corpus(_) => unreachable!(),
+        CliCommand::Scan { .. } => unreachable!(),
+        CliCommand::Hashes { sha512 } => show_hashes(firmware, sha512),
+        CliCommand::Fingerprint {
+            db,
+            record,
+            note,
+            json,
+        } => show_fingerprint(firmware, db, record, note, json),
+        CliCommand::Reconstruct {
+            size,
+            marker,
+            json,
+            output,
+        } => reconstruct(firmware, size, marker, json, output),
+        CliCommand::Revert { record, output } => revert(firmware, record, output),
+        CliCommand::ApplyDelta { delta, output } => apply_delta(firmware, delta, output),
+        CliCommand::Apply { plan, output } => apply_plan(firmware, plan, output, cipher),
+        #[cfg(feature = "tui")]
+        CliCommand::Tui => tui::run(&firmware),
     }
 }
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use mlx5fw::firmware::Firmware;
+use mlx5fw::structures::itoc::{ItocEntry, ItocEntryType};
+
+/// Name of the manifest dump-sections writes next to the section files
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Everything needed to put dumped sections back into an image bit-for-bit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The section files have byte-swapped 32-bit words
+    pub swap32: bool,
+    pub sections: Vec<ManifestSection>,
+}
+
+/// One ITOC entry with the file its content was dumped to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestSection {
+    /// Relative to the manifest
+    pub file: String,
+    /// The file holds decrypted content of an encrypted section
+    pub decrypted: bool,
+    /// Last dword of sections with an in-section CRC, which is not part of the file
+    pub crc_dword: Option<u32>,
+
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub size: usize,
+    pub zipped_image: bool,
+    pub cache_line_crc: bool,
+    pub load_address: u32,
+    pub entry_point: u32,
+    pub version: u16,
+    pub flash_addr: usize,
+    pub encrypted_section: bool,
+    pub crc: u8,
+    pub section_crc: u16,
+    pub itoc_entry_crc: u16,
+    /// Reserved bits of the entry, missing in manifests of older versions
+    #[serde(default)]
+    pub reserved: ManifestReserved,
+}
+
+/// The reserved fields of an ITOC entry, usually zero
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestReserved {
+    pub reserved0: [u8; 6],
+    pub reserved1: u8,
+    pub reserved2: u16,
+}
+
+impl ManifestSection {
+    pub fn new(entry: &ItocEntry, file: String, decrypted: bool, crc_dword: Option<u32>) -> Self {
+        Self {
+            file,
+            decrypted,
+            crc_dword,
+            entry_type: entry.entry_type.to_string(),
+            size: entry.size,
+            zipped_image: entry.zipped_image,
+            cache_line_crc: entry.cache_line_crc,
+            load_address: entry.load_address,
+            entry_point: entry.entry_point,
+            version: entry.version,
+            flash_addr: entry.flash_addr,
+            encrypted_section: entry.encrypted_section,
+            crc: entry.crc,
+            section_crc: entry.section_crc,
+            itoc_entry_crc: entry.itoc_entry_crc,
+            reserved: ManifestReserved {
+                reserved0: entry.reserved0,
+                reserved1: entry.reserved1,
+                reserved2: entry.reserved2,
+            },
+        }
+    }
+
+    /// The ITOC entry as it was dumped, including its possibly wrong CRCs
+    pub fn itoc_entry(&self) -> Result<ItocEntry> {
+        Ok(ItocEntry {
+            entry_type: self.entry_type.parse::<ItocEntryType>()?,
+            size: self.size,
+            zipped_image: self.zipped_image,
+            cache_line_crc: self.cache_line_crc,
+            load_address: self.load_address,
+            entry_point: self.entry_point,
+            reserved0: self.reserved.reserved0,
+            version: self.version,
+            flash_addr: self.flash_addr,
+            encrypted_section: self.encrypted_section,
+            reserved1: self.reserved.reserved1,
+            crc: self.crc,
+            section_crc: self.section_crc,
+            reserved2: self.reserved.reserved2,
+            itoc_entry_crc: self.itoc_entry_crc,
+        })
+    }
+}
+
+/// Where a code file written by dump-code came from, stored next to it with a `.json` suffix so
+/// loaders and import scripts need not correlate it with show-sections
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeInfo {
+    /// Name of the code file, in the same directory
+    pub file: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub load_address: u32,
+    pub entry_point: u32,
+    pub flash_addr: usize,
+    /// Size on flash, including cache-line CRCs
+    pub section_size: usize,
+    /// Size of the code file
+    pub code_size: usize,
+    pub cache_line_crc: bool,
+    /// The code file has byte-swapped 32-bit words
+    pub swap32: bool,
+    /// The code file holds decrypted content of an encrypted section
+    pub decrypted: bool,
+    /// `None` for sections without a section CRC
+    pub section_crc_valid: Option<bool>,
+    /// `None` for sections without cache-line CRCs
+    pub cache_line_crcs_valid: Option<bool>,
+    pub itoc_entry_crc_valid: bool,
+}
+
+impl CodeInfo {
+    pub fn new(firmware: &Firmware, entry: &ItocEntry, code_size: usize, swap32: bool) -> Self {
+        Self {
+            file: entry.code_file_name(),
+            entry_type: entry.entry_type.to_string(),
+            load_address: entry.load_address,
+            entry_point: entry.entry_point,
+            flash_addr: entry.flash_addr,
+            section_size: entry.size,
+            code_size,
+            cache_line_crc: entry.cache_line_crc,
+            swap32,
+            decrypted: entry.encrypted_section,
+            section_crc_valid: entry.section_crc_valid(firmware),
+            cache_line_crcs_valid: entry.cache_line_crcs_valid(firmware),
+            itoc_entry_crc_valid: entry.itoc_entry_crc_valid(),
+        }
+    }
+
+    /// Path of the metadata file for the code file in `dir`
+    pub fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.json", self.file))
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let path = self.path(dir);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+impl Manifest {
+    pub fn read(path: &Path) -> Result<Self> {
+        let manifest = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        serde_json::from_str(&manifest)
+            .with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::io::{Seek, SeekFrom, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::Firmware;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemSegment {
+    pub section_type: String,
+    pub load_address: u32,
+    pub size: usize,
+    pub entry_point: u32,
+    /// Offset of the segment in the flat binary
+    pub file_offset: u64,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+/// All code sections of an image laid out at their load addresses. The flat binary starts at
+/// `base`, the lowest load address, and everything not covered by a segment is zero.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemImage {
+    pub base: u32,
+    pub size: u64,
+    pub segments: Vec<MemSegment>,
+}
+
+impl MemImage {
+    pub fn from_firmware(firmware: &Firmware) -> Result<Self> {
+        let mut segments: Vec<MemSegment> = firmware
+            .code_sections()?
+            .iter()
+            .map(|entry| {
+                let data = firmware.section_code(entry);
+                MemSegment {
+                    section_type: entry.entry_type.to_string(),
+                    load_address: entry.load_address,
+                    size: data.len(),
+                    entry_point: entry.entry_point,
+                    file_offset: 0,
+                    data,
+                }
+            })
+            .collect();
+        if segments.is_empty() {
+            return Err(FirmwareError::NoCodeSections);
+        }
+        segments.sort_by_key(|segment| segment.load_address);
+
+        let base = segments[0].load_address;
+        let mut size = 0;
+        for segment in &mut segments {
+            segment.file_offset = (segment.load_address - base) as u64;
+            size = size.max(segment.file_offset + segment.size as u64);
+        }
+
+        Ok(Self {
+            base,
+            size,
+            segments,
+        })
+    }
+
+    /// Segments that overlap a segment with a lower load address
+    pub fn overlaps(&self) -> impl Iterator<Item = (&MemSegment, &MemSegment)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, segment)| {
+                self.segments[..i]
+                    .iter()
+                    .filter(move |other| {
+                        other.file_offset + other.size as u64 > segment.file_offset
+                    })
+                    .map(move |other| (other, segment))
+            })
+    }
+
+    /// Write the flat binary to `path`, leaving holes between segments, and the segment
+    /// description to `path` with `.json` appended
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = std::fs::File::create(path).map_err(FirmwareError::file(path))?;
+        file.set_len(self.size)?;
+        for segment in &self.segments {
+            file.seek(SeekFrom::Start(segment.file_offset))?;
+            file.write_all(&segment.data)?;
+        }
+
+        let mut json_path = path.as_os_str().to_owned();
+        json_path.push(".json");
+        std::fs::write(json_path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Longest file name most file systems accept, in bytes
+const MAX_FILE_NAME: usize = 255;
+
+/// Device names Windows reserves in every directory, with any extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turn `name` into a file name that can be created on Linux, macOS and Windows alike, so a
+/// dump can be copied between them. Characters Windows rejects become `_`, trailing dots and
+/// spaces are dropped, reserved device names get a `_` prefix and overlong names are cut.
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    name.truncate(name.trim_end_matches(['.', ' ']).len());
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        name.insert(0, '_');
+    }
+    if name.len() > MAX_FILE_NAME {
+        let end = (0..=MAX_FILE_NAME)
+            .rev()
+            .find(|&end| name.is_char_boundary(end))
+            .unwrap_or_default();
+        name.truncate(end);
+    }
+    if name.is_empty() {
+        name.push('_');
+    }
+    name
+}
+
+/// Create the directory a dump command writes to. An existing directory is an error unless
+/// `force` is set, then files already in it are overwritten.
+///
+/// On Windows the returned path is absolute and verbatim (`\\?\`), so files deep in the dump
+/// are not limited to `MAX_PATH`.
+pub fn create_output_dir(dir: &Path, force: bool) -> Result<PathBuf> {
+    match force && dir.is_dir() {
+        true => {}
+        false => std::fs::create_dir(dir).with_context(|| {
+            format!(
+                "Failed to create output directory {}, --force dumps into an existing one",
+                dir.display()
+            )
+        })?,
+    }
+    if cfg!(windows) {
+        return dir
+            .canonicalize()
+            .with_context(|| format!("Could not resolve {}", dir.display()));
+    }
+    Ok(dir.to_path_buf())
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::{Firmware, FirmwareStructure};
+
+/// Changed ranges closer than this are recorded as one range
+const MERGE_GAP: usize = 8;
+
+/// Byte ranges a write changed, with their original and new content. Offsets are file offsets,
+/// not relative to the image base.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchRecord {
+    pub size: usize,
+    pub ranges: Vec<PatchRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchRange {
+    pub offset: usize,
+    /// Hex encoded
+    pub original: String,
+    /// Hex encoded
+    pub patched: String,
+}
+
+impl PatchRecord {
+    pub fn diff(original: &[u8], patched: &[u8]) -> Result<Self> {
+        if original.len() != patched.len() {
+            return Err(FirmwareError::SizeMismatch {
+                what: "Patched image".to_string(),
+                expected: original.len(),
+                actual: patched.len(),
+            });
+        }
+
+        let mut changed: Vec<std::ops::Range<usize>> = vec![];
+        for offset in (0..original.len()).filter(|&i| original[i] != patched[i]) {
+            match changed.last_mut() {
+                Some(range) if offset - range.end <= MERGE_GAP => range.end = offset + 1,
+                _ => changed.push(offset..offset + 1),
+            }
+        }
+        Ok(Self {
+            size: original.len(),
+            ranges: changed
+                .into_iter()
+                .map(|range| PatchRange {
+                    offset: range.start,
+                    original: hex::encode(&original[range.clone()]),
+                    patched: hex::encode(&patched[range]),
+                })
+                .collect(),
+        })
+    }
+
+    /// Sidecar path for an image written to `path`
+    pub fn path_for(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".patch.json");
+        PathBuf::from(name)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn read(path: &Path) -> Result<Self> {
+        let record = std::fs::read(path).map_err(FirmwareError::file(path))?;
+        Ok(serde_json::from_slice(&record)?)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_json()?).map_err(FirmwareError::file(path))
+    }
+
+    /// Restore the original bytes. Fails without changing anything if a range does not hold
+    /// the patched bytes anymore.
+    pub fn revert(&self, firmware: &mut Firmware) -> Result<()> {
+        self.replace(firmware, "Image to revert", false)
+    }
+
+    /// Apply the record as a delta to the image it was recorded against. Fails without changing
+    /// anything if a range does not hold the original bytes.
+    pub fn apply(&self, firmware: &mut Firmware) -> Result<()> {
+        self.replace(firmware, "Image to patch", true)
+    }
+
+    /// Replace the patched by the original bytes, or the other way round for `forward`
+    fn replace(&self, firmware: &mut Firmware, what: &str, forward: bool) -> Result<()> {
+        if firmware.data().len() != self.size {
+            return Err(FirmwareError::SizeMismatch {
+                what: what.to_string(),
+                expected: self.size,
+                actual: firmware.data().len(),
+            });
+        }
+        let mut ranges = vec![];
+        for range in &self.ranges {
+            let mut from = hex::decode(&range.patched)?;
+            let mut to = hex::decode(&range.original)?;
+            if forward {
+                std::mem::swap(&mut from, &mut to);
+            }
+            let current = firmware.data().get(range.offset..range.offset + from.len());
+            if from.len() != to.len() || current != Some(&from[..]) {
+                return Err(FirmwareError::PatchMismatch {
+                    offset: range.offset,
+                });
+            }
+            let offset = range
+                .offset
+                .checked_sub(firmware.image_base())
+                .ok_or_else(|| {
+                    FirmwareError::InvalidArgument(format!(
+                        "Patch range at {:#x} lies before the image base {:#x}",
+                        range.offset,
+                        firmware.image_base()
+                    ))
+                })?;
+            ranges.push((offset, to));
+        }
+        for (offset, bytes) in ranges {
+            FirmwareStructure(offset, ()).write_bytes(firmware, &bytes)?;
+        }
+        Ok(())
+    }
+}
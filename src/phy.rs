@@ -0,0 +1,60 @@
+//! PHY microcode sections. Their internal header is undocumented, so they are split at runs of
+//! padding rather than by the header. The pieces are a guess at the per-core images, a parser
+//! of the header is still to be written.
+
+use crate::structures::itoc::ItocEntryType;
+
+/// Gaps shorter than this are taken to be part of a piece
+pub const DEFAULT_MIN_GAP: usize = 0x100;
+
+/// Contiguous data within a PHY microcode section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhyBlob {
+    /// Offset within the section
+    pub offset: usize,
+    pub size: usize,
+}
+
+pub fn is_phy_section(entry_type: &ItocEntryType) -> bool {
+    matches!(
+        entry_type,
+        ItocEntryType::PhyUcCode | ItocEntryType::PhyUcConsts | ItocEntryType::PciePhyUcCode
+    )
+}
+
+/// Split `content` at runs of at least `min_gap` 0x00 or 0xff bytes. Padding is trimmed off
+/// the blobs, an all-padding section has none.
+pub fn split_blobs(content: &[u8], min_gap: usize) -> Vec<PhyBlob> {
+    let min_gap = min_gap.max(1);
+    let mut blobs = vec![];
+    let mut start = None;
+    let mut i = 0;
+    while i < content.len() {
+        let fill = content[i];
+        let run = content[i..]
+            .iter()
+            .take_while(|&&byte| byte == fill)
+            .count();
+        let padding = (fill == 0x00 || fill == 0xff)
+            && (run >= min_gap || start.is_none() || i + run == content.len());
+        match (padding, start) {
+            (true, Some(offset)) => {
+                blobs.push(PhyBlob {
+                    offset,
+                    size: i - offset,
+                });
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+        i += run;
+    }
+    if let Some(offset) = start {
+        blobs.push(PhyBlob {
+            offset,
+            size: content.len() - offset,
+        });
+    }
+    blobs
+}
@@ -0,0 +1,156 @@
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use mlx5fw::cipher::SectionCipher;
+use mlx5fw::firmware::Firmware;
+
+/// A list of modifications applied in order to one image, read from YAML:
+///
+/// ```yaml
+/// operations:
+///   - replace-section: { index: 3, file: main_code.bin }
+///   - patch-va: { address: 0x100040, bytes: "00000000" }
+///   - set-psid: MT_0000000010
+///   - fix-crc
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plan {
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    /// Replace a section with plain content, relative paths are relative to the plan
+    ReplaceSection { index: usize, file: PathBuf },
+    /// Overwrite code at a load address with hex encoded bytes
+    PatchVa { address: u64, bytes: String },
+    /// Set the PSID in IMAGE_INFO
+    SetPsid(String),
+    /// Empty the FORBIDDEN_VERSIONS list
+    StripForbidden,
+    /// Recompute all CRCs
+    FixCrc,
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReplaceSection { index, file } => {
+                write!(f, "replace-section {} {}", index, file.display())
+            }
+            Self::PatchVa { address, bytes } => write!(f, "patch-va {:#x} {}", address, bytes),
+            Self::SetPsid(psid) => write!(f, "set-psid {}", psid),
+            Self::StripForbidden => write!(f, "strip-forbidden"),
+            Self::FixCrc => write!(f, "fix-crc"),
+        }
+    }
+}
+
+impl Plan {
+    pub fn read(path: &Path) -> Result<Self> {
+        let plan = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        serde_yaml::from_str(&plan).with_context(|| format!("Invalid plan {}", path.display()))
+    }
+
+    /// Run all operations on `firmware`, stopping at the first that fails
+    pub fn apply(
+        &self,
+        firmware: &mut Firmware,
+        dir: &Path,
+        cipher: Option<&dyn SectionCipher>,
+    ) -> Result<()> {
+        for (i, operation) in self.operations.iter().enumerate() {
+            operation
+                .apply(firmware, dir, cipher)
+                .with_context(|| format!("Operation {} ({}) failed", i, operation))?;
+            info!("Applied {}", operation);
+        }
+        Ok(())
+    }
+}
+
+impl Operation {
+    fn apply(
+        &self,
+        firmware: &mut Firmware,
+        dir: &Path,
+        cipher: Option<&dyn SectionCipher>,
+    ) -> Result<()> {
+        match self {
+            Self::ReplaceSection { index, file } => {
+                let itoc = firmware.itoc()?;
+                ensure!(*index < itoc.len(), "Section index out of range");
+                let itoc_entry = itoc[*index].clone();
+                let path = dir.join(file);
+                let content = std::fs::read(&path)
+                    .with_context(|| format!("Could not read {}", path.display()))?;
+                let content = if itoc_entry.cache_line_crc {
+                    firmware.cache_line_geometry().encode(&content)?
+                } else {
+                    content
+                };
+                crate::write_plain_section(firmware, itoc_entry, content, cipher)
+            }
+            Self::PatchVa { address, bytes } => {
+                patch_va(firmware, *address, &hex::decode(bytes)?, cipher)
+            }
+            Self::SetPsid(psid) => {
+                ensure!(psid.len() <= 16, "PSID {:?} is longer than 16 bytes", psid);
+                let mut image_info = firmware.image_info()?;
+                image_info.psid = [0x00; 16];
+                image_info.psid[..psid.len()].copy_from_slice(psid.as_bytes());
+                Ok(image_info.write_section(firmware)?)
+            }
+            Self::StripForbidden => crate::strip_forbidden(firmware),
+            Self::FixCrc => crate::fix_crcs(firmware, None),
+        }
+    }
+}
+
+/// Overwrite code at `address` within the code section loaded there, keeping its cache-line,
+/// section and ITOC entry CRCs valid
+fn patch_va(
+    firmware: &mut Firmware,
+    address: u64,
+    bytes: &[u8],
+    cipher: Option<&dyn SectionCipher>,
+) -> Result<()> {
+    let end = address.checked_add(bytes.len() as u64).with_context(|| {
+        format!(
+            "Patch of {:#x} bytes at {:#x} runs past the address space",
+            bytes.len(),
+            address
+        )
+    })?;
+    for itoc_entry in firmware.code_sections()? {
+        let content = crate::read_plain_section(firmware, &itoc_entry, cipher)?;
+        let stored = itoc_entry.content().read_bytes(firmware);
+        let mut code =
+            firmware.code_from_content(&itoc_entry, content.as_deref().unwrap_or(stored));
+        let start = itoc_entry.load_address as u64;
+        if address < start || end > start + code.len() as u64 {
+            continue;
+        }
+        ensure!(
+            content.is_some(),
+            "{} section is encrypted, a key file is needed to patch it",
+            itoc_entry.entry_type
+        );
+
+        let offset = (address - start) as usize;
+        code[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let content = if itoc_entry.cache_line_crc {
+            firmware.cache_line_geometry().encode(&code)?
+        } else {
+            code
+        };
+        return crate::write_plain_section(firmware, itoc_entry, content, cipher);
+    }
+    bail!("No code section is loaded at {:#x}..{:#x}", address, end)
+}
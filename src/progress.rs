@@ -0,0 +1,14 @@
+/// Progress of a long operation, counted in bytes
+pub trait Progress: Sync {
+    /// Set the total amount of work once it is known
+    fn set_total(&self, total: usize);
+    fn advance(&self, done: usize);
+}
+
+/// Discards all progress
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn set_total(&self, _total: usize) {}
+    fn advance(&self, _done: usize) {}
+}
@@ -0,0 +1,40 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use mlx5fw::progress::Progress;
+
+/// Progress bar with ETA on standard error, hidden when that is not a terminal
+pub struct ByteProgress(ProgressBar);
+
+impl ByteProgress {
+    pub fn new(message: &'static str, total: usize) -> Self {
+        let style =
+            ProgressStyle::with_template("{msg} [{wide_bar}] {bytes}/{total_bytes} ETA {eta}")
+                .unwrap()
+                .progress_chars("=> ");
+        Self(
+            ProgressBar::new(total as u64)
+                .with_style(style)
+                .with_message(message),
+        )
+    }
+
+    /// Run `f` with the bar hidden, for log output
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.0.suspend(f)
+    }
+}
+
+impl Progress for ByteProgress {
+    fn set_total(&self, total: usize) {
+        self.0.set_length(total as u64);
+    }
+
+    fn advance(&self, done: usize) {
+        self.0.inc(done as u64);
+    }
+}
+
+impl Drop for ByteProgress {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+    }
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::changelog::{self, SectionChange, SectionStatus};
+#[cfg(feature = "fs")]
+use crate::error::FirmwareError;
+use crate::error::Result;
+use crate::firmware::Firmware;
+use crate::structures::itoc::ItocEntryType;
+
+/// How a dumped image differs from the vendor release it claims to be. Device data (GUIDs, MACs,
+/// VSD, NV configuration) differs between cards and is not compared.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReleaseComparison {
+    pub psid: Option<String>,
+    pub release_psid: Option<String>,
+    pub fw_version: Option<String>,
+    pub release_fw_version: Option<String>,
+    /// `None` if either image has no boot2, e.g. FS5 images
+    pub boot2: Option<SectionStatus>,
+    /// Sections that differ from the release
+    pub sections: Vec<SectionChange>,
+}
+
+impl ReleaseComparison {
+    pub fn psid_matches(&self) -> bool {
+        self.psid.is_some() && self.psid == self.release_psid
+    }
+
+    /// Same PSID, identical boot2 and identical firmware sections
+    pub fn pristine(&self) -> bool {
+        self.psid_matches()
+            && self.boot2 != Some(SectionStatus::Changed)
+            && self.sections.is_empty()
+    }
+}
+
+fn image_info(firmware: &Firmware) -> (Option<String>, Option<String>) {
+    match firmware.image_info() {
+        Ok(image_info) => (
+            Some(image_info.psid()),
+            Some(image_info.fw_version.to_string()),
+        ),
+        Err(_) => (None, None),
+    }
+}
+
+fn boot2(firmware: &Firmware) -> Option<&[u8]> {
+    let boot2 = firmware.boot2().ok()?;
+    firmware.bytes(boot2.0, boot2.total_size()).ok()
+}
+
+/// Compare the sections of `dump` with the ones of the vendor image `release`, matching them by
+/// type and occurrence
+pub fn compare_release(dump: &Firmware, release: &Firmware) -> Result<ReleaseComparison> {
+    let (psid, fw_version) = image_info(dump);
+    let (release_psid, release_fw_version) = image_info(release);
+    let boot2 = match (boot2(dump), boot2(release)) {
+        (Some(boot2), Some(release_boot2)) if boot2 == release_boot2 => {
+            Some(SectionStatus::Unchanged)
+        }
+        (Some(_), Some(_)) => Some(SectionStatus::Changed),
+        _ => None,
+    };
+    let sections = changelog::changelog(release, dump)?
+        .sections
+        .into_iter()
+        .filter(|change| change.status != SectionStatus::Unchanged)
+        .filter(|change| {
+            change
+                .section_type
+                .parse::<ItocEntryType>()
+                .ok()
+                .is_none_or(|entry_type| !entry_type.is_device_data())
+        })
+        .collect();
+
+    Ok(ReleaseComparison {
+        psid,
+        release_psid,
+        fw_version,
+        release_fw_version,
+        boot2,
+        sections,
+    })
+}
+
+/// Digest of the parts of an image that are the same on every card running the release: boot2
+/// and every section except device data. Sections are hashed by type, size and content in ITOC
+/// order, so moving a section does not change the fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub sha256: String,
+    pub psid: Option<String>,
+    pub fw_version: Option<String>,
+}
+
+pub fn fingerprint(firmware: &Firmware) -> Result<Fingerprint> {
+    let mut hasher = Sha256::new();
+    if let Some(boot2) = boot2(firmware).filter(|_| !firmware.is_fs5()) {
+        hasher.update(b"boot2");
+        hasher.update((boot2.len() as u64).to_be_bytes());
+        hasher.update(boot2);
+    }
+    for entry in firmware.itoc()? {
+        if entry.entry_type.is_device_data() {
+            continue;
+        }
+        let content = entry.content().read_bytes(firmware);
+        hasher.update(entry.entry_type.to_string());
+        hasher.update((content.len() as u64).to_be_bytes());
+        hasher.update(content);
+    }
+    let (psid, fw_version) = image_info(firmware);
+    Ok(Fingerprint {
+        sha256: hex::encode(hasher.finalize()),
+        psid,
+        fw_version,
+    })
+}
+
+/// A fingerprint recorded in a `KnownImages` database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownImage {
+    #[serde(flatten)]
+    pub fingerprint: Fingerprint,
+    /// Free text, e.g. "vendor release"
+    pub note: Option<String>,
+}
+
+/// Local database of image fingerprints, a JSON file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownImages {
+    pub images: Vec<KnownImage>,
+}
+
+impl KnownImages {
+    /// An empty database if `path` does not exist yet
+    #[cfg(feature = "fs")]
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let images = std::fs::read(path).map_err(FirmwareError::file(path))?;
+        Ok(serde_json::from_slice(&images)?)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?).map_err(FirmwareError::file(path))
+    }
+
+    pub fn find(&self, fingerprint: &Fingerprint) -> Option<&KnownImage> {
+        self.images
+            .iter()
+            .find(|image| image.fingerprint.sha256 == fingerprint.sha256)
+    }
+
+    /// Add or replace the record of `fingerprint`
+    pub fn record(&mut self, fingerprint: Fingerprint, note: Option<String>) {
+        self.images
+            .retain(|image| image.fingerprint.sha256 != fingerprint.sha256);
+        self.images.push(KnownImage { fingerprint, note });
+    }
+}
@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::Firmware;
+use crate::structures::itoc::ItocEntry;
+
+/// How much of a section an incomplete dump contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Presence {
+    Complete,
+    Truncated,
+    Missing,
+}
+
+impl fmt::Display for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Presence::Complete => "complete",
+            Presence::Truncated => "truncated",
+            Presence::Missing => "missing",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SectionPresence {
+    pub index: usize,
+    pub entry_type: String,
+    pub flash_addr: usize,
+    pub size: usize,
+    /// Bytes of the section within the dump
+    pub present: usize,
+    pub presence: Presence,
+    /// Whether a complete section matches its CRC, `None` if it is incomplete or has no CRC
+    pub crc_ok: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReconstructReport {
+    pub dump_size: usize,
+    /// Size of the reconstructed image. Bytes from `dump_size` on were missing from the dump.
+    pub image_size: usize,
+    pub sections: Vec<SectionPresence>,
+    /// Why the ITOC could not be read to its end, e.g. because it is cut off itself
+    pub itoc_error: Option<String>,
+    /// Whether the dump ends with the DTOC. Device data is lost when the end of the flash is.
+    pub has_dtoc: bool,
+}
+
+impl ReconstructReport {
+    pub fn missing(&self) -> usize {
+        self.image_size - self.dump_size
+    }
+
+    pub fn count(&self, presence: Presence) -> usize {
+        self.sections
+            .iter()
+            .filter(|section| section.presence == presence)
+            .count()
+    }
+}
+
+fn section_presence(firmware: &Firmware, index: usize, entry: &ItocEntry) -> SectionPresence {
+    let end = entry.end().unwrap_or(usize::MAX);
+    let present = end.min(firmware.len()).saturating_sub(entry.flash_addr);
+    let presence = match present {
+        0 if entry.size > 0 => Presence::Missing,
+        present if present < entry.size => Presence::Truncated,
+        _ => Presence::Complete,
+    };
+    let crc_ok = (presence == Presence::Complete)
+        .then(|| entry.stored_section_crc(firmware))
+        .flatten()
+        .map(|crc| crc == entry.calc_section_crc(firmware));
+    SectionPresence {
+        index,
+        entry_type: entry.entry_type.to_string(),
+        flash_addr: entry.flash_addr,
+        size: entry.size,
+        present,
+        presence,
+        crc_ok,
+    }
+}
+
+/// Report which ITOC sections an incomplete dump contains, assuming it was cut off at its end
+pub fn analyze(firmware: &Firmware) -> Result<ReconstructReport> {
+    firmware.hwpointers()?;
+    let mut sections = vec![];
+    let mut itoc_error = None;
+    for (index, entry) in firmware.itoc_entries().truncated().enumerate() {
+        match entry {
+            Ok(entry) => sections.push(section_presence(firmware, index, &entry)),
+            Err(err) => itoc_error = Some(err.to_string()),
+        }
+    }
+    let image_size = sections
+        .iter()
+        .map(|section| section.flash_addr.saturating_add(section.size))
+        .fold(firmware.len(), usize::max);
+    Ok(ReconstructReport {
+        dump_size: firmware.len(),
+        image_size,
+        sections,
+        itoc_error,
+        has_dtoc: firmware.has_dtoc(),
+    })
+}
+
+/// Pad an incomplete dump with `marker` to `size` bytes, or to the end of its last section, so
+/// the rest of the tools can work on it. Sections reported missing or truncated keep the marker
+/// in place of their content.
+pub fn reconstruct(
+    firmware: &mut Firmware,
+    size: Option<usize>,
+    marker: &[u8],
+) -> Result<ReconstructReport> {
+    if marker.is_empty() {
+        return Err(FirmwareError::InvalidArgument(
+            "marker must not be empty".to_string(),
+        ));
+    }
+    let mut report = analyze(firmware)?;
+    if let Some(size) = size {
+        if size < report.dump_size {
+            return Err(FirmwareError::InvalidArgument(format!(
+                "size {size:#x} is smaller than the dump, {:#x} bytes",
+                report.dump_size
+            )));
+        }
+        report.image_size = size;
+    }
+    firmware.pad_to(report.image_size, marker);
+    Ok(report)
+}
@@ -0,0 +1,111 @@
+//! Expansion ROM (PXE, UEFI, CLP) versions. Each ROM in ROM_CODE carries an `mlxsign:` marker
+//! followed by three little-endian dwords:
+//!
+//! - product ID (31:16) and major version (15:0)
+//! - minor version (31:16) and subminor version (15:0)
+//! - PCI device ID (31:16), port (15:12), supported CPU architectures (11:8) and protocol (7:0)
+//!
+//! Only the major version is valid for product IDs below 0x10.
+
+use serde::Serialize;
+use std::fmt;
+
+pub const MAGIC: &[u8] = b"mlxsign:";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RomInfo {
+    /// Offset of the version dwords within ROM_CODE
+    pub offset: usize,
+    pub product_id: u16,
+    pub version: [u16; 3],
+    pub dev_id: u16,
+    /// 0 for port independent ROMs
+    pub port: u8,
+    pub cpu_arch: u8,
+    /// 0 for IB, 1 for Ethernet, 2 for VPI
+    pub protocol: u8,
+}
+
+impl RomInfo {
+    /// Every ROM whose marker is followed by its complete version dwords
+    pub fn parse(rom: &[u8]) -> Vec<Self> {
+        let dword = |offset: usize| u32::from_le_bytes(rom[offset..offset + 4].try_into().unwrap());
+        rom.windows(MAGIC.len())
+            .enumerate()
+            .filter(|(_, window)| *window == MAGIC)
+            .map(|(offset, _)| offset + MAGIC.len())
+            .filter(|offset| offset + 12 <= rom.len())
+            .map(|offset| {
+                let (first, second, third) = (dword(offset), dword(offset + 4), dword(offset + 8));
+                let product_id = (first >> 16) as u16;
+                let mut info = Self {
+                    offset,
+                    product_id,
+                    version: [first as u16, 0, 0],
+                    dev_id: 0,
+                    port: 0,
+                    cpu_arch: 0,
+                    protocol: 0,
+                };
+                if product_id >= 0x10 {
+                    info.version[1] = (second >> 16) as u16;
+                    info.version[2] = second as u16;
+                    info.dev_id = (third >> 16) as u16;
+                    info.port = (third >> 12 & 0xf) as u8;
+                    info.cpu_arch = (third >> 8 & 0xf) as u8;
+                    info.protocol = third as u8;
+                }
+                info
+            })
+            .collect()
+    }
+
+    pub fn product(&self) -> String {
+        match self.product_id {
+            0x01 => "CLP1".to_string(),
+            0x02 => "CLP2".to_string(),
+            0x10 => "PXE".to_string(),
+            0x11 => "UEFI".to_string(),
+            0x12 => "CLP".to_string(),
+            0x13 => "FCODE".to_string(),
+            0x14 => "UEFI Virtio net".to_string(),
+            0x15 => "UEFI Virtio blk".to_string(),
+            0x16 => "PXE Virtio net".to_string(),
+            0x21 => "NVME".to_string(),
+            id => format!("{:#x}", id),
+        }
+    }
+
+    pub fn cpu(&self) -> Option<String> {
+        match self.cpu_arch {
+            0 => None,
+            1 => Some("AMD64".to_string()),
+            2 => Some("AARCH64".to_string()),
+            3 => Some("AMD64,AARCH64".to_string()),
+            4 => Some("IA32".to_string()),
+            arch => Some(format!("unknown({})", arch)),
+        }
+    }
+}
+
+/// As flint prints it, e.g. `type=UEFI version=14.29.15 cpu=AMD64,AARCH64`
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "type={} ", self.product())?;
+        if self.product_id < 0x10 {
+            return write!(f, "version={}", self.version[0]);
+        }
+        let [major, minor, subminor] = self.version;
+        write!(f, "version={}.{}.{}", major, minor, subminor)?;
+        if self.dev_id != 0 {
+            write!(f, " devid={}", self.dev_id)?;
+        }
+        if self.port != 0 {
+            write!(f, " port={}", self.port)?;
+        }
+        if let Some(cpu) = self.cpu() {
+            write!(f, " cpu={}", cpu)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::corpus::collect_files;
+use crate::error::Result;
+use crate::firmware::Firmware;
+use crate::verify::{Profile, Severity};
+
+/// Image info and verification summary of one image below a scanned directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScanEntry {
+    /// Path relative to the scanned directory
+    pub path: PathBuf,
+    pub chip: Option<String>,
+    pub psid: Option<String>,
+    pub fw_version: Option<String>,
+    pub failures: usize,
+    pub warnings: usize,
+    /// Why the image could not be read, if it could not
+    pub error: Option<String>,
+}
+
+impl ScanEntry {
+    fn from_file(dir: &Path, path: &Path, profile: Profile) -> Result<Option<Self>> {
+        let mut firmware = Firmware::read(path)?;
+        if Firmware::find_image_base(firmware.data()).is_none() {
+            return Ok(None);
+        }
+        firmware.set_chip(firmware.detect_chip());
+
+        let mut entry = Self {
+            path: path.strip_prefix(dir).unwrap_or(path).to_path_buf(),
+            chip: firmware.chip().map(|chip| chip.to_string()),
+            psid: None,
+            fw_version: None,
+            failures: 0,
+            warnings: 0,
+            error: None,
+        };
+        match firmware.image_info() {
+            Ok(image_info) => {
+                entry.psid = Some(image_info.psid());
+                entry.fw_version = Some(image_info.fw_version.to_string());
+            }
+            Err(err) => entry.error = Some(err.to_string()),
+        }
+        for finding in firmware.parse().validate(profile) {
+            match finding.severity {
+                Severity::Fatal => entry.failures += 1,
+                Severity::Warning => entry.warnings += 1,
+            }
+        }
+        Ok(Some(entry))
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures == 0 && self.error.is_none()
+    }
+}
+
+/// Verify every image below `dir`, recursively. Files without the image magic are skipped.
+pub fn scan(dir: &Path, profile: Profile) -> Result<Vec<ScanEntry>> {
+    let mut files = vec![];
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let entries = files
+        .par_iter()
+        .map(|path| ScanEntry::from_file(dir, path, profile))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(entries.into_iter().flatten().collect())
+}
@@ -0,0 +1,251 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::Firmware;
+use crate::structures::itoc::{ItocEntry, ItocEntryType};
+
+/// Size of the key UUID preceding public keys and signatures
+const UUID_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicKeyInfo {
+    pub section_type: String,
+    pub bits: usize,
+    pub uuid: String,
+    pub exponent: u32,
+    /// SHA-256 of the modulus
+    pub fingerprint: String,
+}
+
+/// A signature section. Signatures are only checked structurally, not cryptographically.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignatureInfo {
+    pub section_type: String,
+    pub bits: usize,
+    /// UUID of the key the image was signed with
+    pub uuid: String,
+    pub crc_ok: bool,
+    /// Whether the signing key is among the image's public keys
+    pub key_present: bool,
+}
+
+/// How locked down an image is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecurityReport {
+    pub secure_boot: Option<bool>,
+    pub debug_fw: Option<bool>,
+    /// Challenge-response debug tokens
+    pub debug_tokens: Option<bool>,
+    pub encrypted_sections: Vec<String>,
+    pub signatures: Vec<SignatureInfo>,
+    pub public_keys: Vec<PublicKeyInfo>,
+    pub hashes_table: bool,
+}
+
+fn key_bits(entry_type: ItocEntryType) -> Option<usize> {
+    match entry_type {
+        ItocEntryType::ImageSignature256 | ItocEntryType::PublicKeys2048 => Some(2048),
+        ItocEntryType::ImageSignature512 | ItocEntryType::PublicKeys4096 => Some(4096),
+        _ => None,
+    }
+}
+
+/// The keys of a public key section, which holds records of exponent, UUID and modulus. Erased
+/// records are skipped, other sections have no keys.
+pub fn public_keys(firmware: &Firmware, entry: &ItocEntry) -> Vec<PublicKeyInfo> {
+    let Some(bits) = key_bits(entry.entry_type.clone()).filter(|_| {
+        matches!(
+            entry.entry_type,
+            ItocEntryType::PublicKeys2048 | ItocEntryType::PublicKeys4096
+        )
+    }) else {
+        return vec![];
+    };
+    let record_size = 4 + UUID_SIZE + bits / 8;
+    entry
+        .content()
+        .read_bytes(firmware)
+        .chunks_exact(record_size)
+        .filter(|record| record.iter().any(|&byte| byte != 0xff))
+        .map(|record| PublicKeyInfo {
+            section_type: entry.entry_type.to_string(),
+            bits,
+            uuid: hex::encode(&record[4..4 + UUID_SIZE]),
+            exponent: u32::from_be_bytes(record[..4].try_into().unwrap()),
+            fingerprint: hex::encode(Sha256::digest(&record[4 + UUID_SIZE..])),
+        })
+        .collect()
+}
+
+pub fn report(firmware: &Firmware) -> Result<SecurityReport> {
+    let itoc = firmware.itoc()?;
+    let image_info = firmware.image_info().ok();
+
+    let public_keys: Vec<PublicKeyInfo> = itoc
+        .iter()
+        .flat_map(|entry| public_keys(firmware, entry))
+        .collect();
+
+    let signatures = itoc
+        .iter()
+        .filter(|entry| {
+            matches!(
+                entry.entry_type,
+                ItocEntryType::ImageSignature256 | ItocEntryType::ImageSignature512
+            )
+        })
+        .map(|entry| {
+            let content = entry.content().read_bytes(firmware);
+            let uuid = hex::encode(content.get(..UUID_SIZE).unwrap_or_default());
+            SignatureInfo {
+                section_type: entry.entry_type.to_string(),
+                bits: key_bits(entry.entry_type.clone()).unwrap(),
+                key_present: public_keys.iter().any(|key| key.uuid == uuid),
+                uuid,
+                crc_ok: entry
+                    .stored_section_crc(firmware)
+                    .is_none_or(|crc| crc == entry.calc_section_crc(firmware)),
+            }
+        })
+        .collect();
+
+    Ok(SecurityReport {
+        secure_boot: image_info.as_ref().map(|image_info| image_info.secure_boot),
+        debug_fw: image_info.as_ref().map(|image_info| image_info.debug_fw),
+        debug_tokens: image_info.map(|image_info| image_info.cs_tokens_supported),
+        encrypted_sections: itoc
+            .iter()
+            .filter(|entry| entry.encrypted_section)
+            .map(|entry| entry.entry_type.to_string())
+            .collect(),
+        signatures,
+        public_keys,
+        hashes_table: itoc
+            .iter()
+            .any(|entry| entry.entry_type == ItocEntryType::HashesTable),
+    })
+}
+
+/// The anti-rollback counter in the HW pointers, `None` if erased. Devices refuse images with a
+/// lower counter than the one burnt into their fuses by the running image.
+pub fn security_version(firmware: &Firmware) -> Result<Option<u32>> {
+    let ptr = firmware.hwpointers()?.fw_security_version.ptr;
+    Ok((ptr != 0xffff_ffff).then_some(ptr as u32))
+}
+
+/// Whether a device running one image would accept another. Only the checks that can be made
+/// from the images are covered, not the device's fuses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DowngradeCheck {
+    pub running_version: String,
+    pub target_version: String,
+    /// The target version is lower than the running one
+    pub downgrade: bool,
+    /// The target version is listed in the running image's FORBIDDEN_VERSIONS
+    pub forbidden: bool,
+    pub running_security_version: Option<u32>,
+    pub target_security_version: Option<u32>,
+}
+
+impl DowngradeCheck {
+    /// The target has a lower security version than the running image. An image without one
+    /// counts as 0.
+    pub fn security_version_rollback(&self) -> bool {
+        self.target_security_version.unwrap_or(0) < self.running_security_version.unwrap_or(0)
+    }
+
+    pub fn accepted(&self) -> bool {
+        !self.forbidden && !self.security_version_rollback()
+    }
+}
+
+pub fn check_downgrade(running: &Firmware, target: &Firmware) -> Result<DowngradeCheck> {
+    let running_version = running.version()?;
+    let target_version = target.version()?;
+    let forbidden = match running.forbidden_versions() {
+        Ok(forbidden) => forbidden.versions.contains(&target_version),
+        Err(FirmwareError::MissingSection { .. }) => false,
+        Err(err) => return Err(err),
+    };
+    Ok(DowngradeCheck {
+        running_version: running_version.to_string(),
+        target_version: target_version.to_string(),
+        downgrade: target_version < running_version,
+        forbidden,
+        running_security_version: security_version(running)?,
+        target_security_version: security_version(target)?,
+    })
+}
+
+/// What `strip` removed from an image
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stripped {
+    /// Signature and public key sections, removed from the ITOC and erased
+    pub sections: Vec<ItocEntryType>,
+    /// IMAGE_INFO flags that were cleared
+    pub flags: Vec<&'static str>,
+    /// Modified sections listed in the HASHES_TABLE, whose digests are not updated
+    pub stale_digests: Vec<ItocEntryType>,
+}
+
+impl Stripped {
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty() && self.flags.is_empty()
+    }
+}
+
+/// Remove the signature and public key sections and clear the secure boot flags of IMAGE_INFO,
+/// for devices with secure boot fused off, where stale signatures only confuse tools. The ITOC
+/// and IMAGE_INFO CRC are updated, a HASHES_TABLE digest of IMAGE_INFO is not.
+pub fn strip(firmware: &mut Firmware) -> Result<Stripped> {
+    let mut stripped = Stripped::default();
+
+    let (removed, kept): (Vec<_>, Vec<_>) = firmware
+        .itoc()?
+        .into_iter()
+        .partition(|entry| key_bits(entry.entry_type.clone()).is_some());
+    if !removed.is_empty() {
+        for entry in &removed {
+            let shared = kept.iter().any(|other| {
+                other.flash_addr < entry.flash_addr + entry.size
+                    && entry.flash_addr < other.flash_addr + other.size
+            });
+            if !shared {
+                firmware.fill(entry.flash_addr, entry.size)?;
+            }
+            stripped.sections.push(entry.entry_type.clone());
+        }
+        let entries: Vec<ItocEntry> = kept.into_iter().map(|entry| entry.1).collect();
+        firmware.write_itoc(&entries)?;
+    }
+
+    let mut image_info = firmware.image_info()?;
+    let flags = &mut image_info.1;
+    for (flag, name) in [
+        (&mut flags.secure_boot, "secure_boot"),
+        (
+            &mut flags.signed_mlnx_nvconfig_files,
+            "signed_mlnx_nvconfig_files",
+        ),
+        (
+            &mut flags.signed_vendor_nvconfig_files,
+            "signed_vendor_nvconfig_files",
+        ),
+    ] {
+        if std::mem::take(flag) {
+            stripped.flags.push(name);
+        }
+    }
+    if !stripped.flags.is_empty() {
+        image_info.write_section(firmware)?;
+        let listed = firmware
+            .hashes_table()
+            .is_ok_and(|table| table.entry(&ItocEntryType::ImageInfo).is_some());
+        if listed {
+            stripped.stale_digests.push(ItocEntryType::ImageInfo);
+        }
+    }
+
+    Ok(stripped)
+}
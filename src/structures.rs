@@ -1,2 +1,12 @@
+pub mod bootversion;
+pub mod crdump;
+pub mod devinfo;
+pub mod forbidden;
+pub mod hashes;
+pub mod hwpointers;
+pub mod imageinfo;
 pub mod itoc;
-pub mod hwpointers;
\ No newline at end of file
+pub mod nvdata;
+pub mod programmable;
+pub mod version;
+pub mod vpd;
@@ -0,0 +1,47 @@
+use deku::prelude::*;
+
+/// Flash image generation, telling how the image past the HW pointers is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// ITOC found by searching, no HW pointers. Not supported.
+    Fs3,
+    /// HW pointers, boot2 and ITOC
+    Fs4,
+    /// HW pointers, boot components and ITOC. Detected, the boot components are not parsed yet.
+    Fs5,
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fs3 => "FS3",
+            Self::Fs4 => "FS4",
+            Self::Fs5 => "FS5",
+        })
+    }
+}
+
+/// The dword following the image magic
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct BootVersion {
+    pub image_format_version: u8,
+    pub reserved: u8,
+    pub major_version: u8,
+    pub minor_version: u8,
+}
+
+impl BootVersion {
+    /// Follows the image magic
+    pub const OFFSET: usize = 0x10;
+
+    /// `None` for erased and unknown format versions
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        match self.image_format_version {
+            0 => Some(ImageFormat::Fs3),
+            1 => Some(ImageFormat::Fs4),
+            2 => Some(ImageFormat::Fs5),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use deku::ctx::Endian;
+use deku::prelude::*;
+
+/// CRDUMP_MASK_DATA: crspace ranges left out of crdumps
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct CrDumpMask {
+    #[deku(bits = "32", assert = "*num_ranges <= Self::MAX_RANGES")]
+    pub num_ranges: usize,
+
+    #[deku(count = "num_ranges")]
+    pub ranges: Vec<CrSpaceRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
+pub struct CrSpaceRange {
+    #[deku(bits = "32")]
+    pub address: u32,
+    /// Size in bytes
+    #[deku(bits = "32")]
+    pub size: u32,
+}
+
+impl CrDumpMask {
+    /// Larger counts are rejected instead of allocated
+    pub const MAX_RANGES: usize = 0x10000;
+}
+
+impl CrSpaceRange {
+    /// Last masked address
+    pub fn end(&self) -> u64 {
+        (self.address as u64 + self.size as u64).saturating_sub(1)
+    }
+}
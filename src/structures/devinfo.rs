@@ -0,0 +1,91 @@
+use deku::ctx::Endian;
+use deku::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
+pub struct Uid {
+    #[deku(pad_bits_before = "8", bits = "8")]
+    pub num_allocated_msb: u8,
+    #[deku(bits = "8")]
+    pub step: u8,
+    #[deku(bits = "8")]
+    pub num_allocated: u8,
+
+    #[deku(pad_bytes_before = "4")]
+    pub uid: u64,
+}
+
+impl Uid {
+    pub fn count(&self) -> u16 {
+        ((self.num_allocated_msb as u16) << 8) | self.num_allocated as u16
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
+pub struct Uids {
+    pub guids: Uid,
+    pub macs: Uid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big", magic = b"mDevInfo\x23\x42\xca\xfa\xba\xca\xfe\x00")]
+pub struct DevInfo {
+    #[deku(bits = "16")]
+    pub major_version: u16,
+    #[deku(pad_bits_before = "8", bits = "8")]
+    pub minor_version: u8,
+
+    pub reserved0: [u8; 8],
+
+    #[deku(pad_bits_before = "16", bits = "16")]
+    pub vsd_vendor_id: u16,
+
+    pub vsd: [u8; 208],
+
+    pub reserved1: [u8; 16],
+
+    pub uids: Uids,
+
+    pub reserved2: [u8; 0xdc],
+
+    #[deku(pad_bits_before = "16", bits = "16", update = "self.calc_crc()")]
+    pub crc: u16,
+}
+
+impl DevInfo {
+    pub const SIZE: usize = 0x200;
+
+    pub fn calc_crc(&self) -> u16 {
+        let bytes = self.to_bytes().unwrap();
+        let crc = crate::crc::calc_crc16(0x0000, &bytes[..Self::SIZE - 4]);
+        crate::crc::calc_crc16(crc, &[0x00, 0x00])
+    }
+
+    pub fn vsd(&self) -> String {
+        String::from_utf8_lossy(&self.vsd)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct MfgInfo {
+    pub psid: [u8; 16],
+
+    #[deku(pad_bytes_before = "12", pad_bits_before = "16", bits = "8")]
+    pub major_version: u8,
+    #[deku(bits = "8")]
+    pub minor_version: u8,
+
+    pub uids: Uids,
+}
+
+impl MfgInfo {
+    pub fn psid(&self) -> String {
+        String::from_utf8_lossy(&self.psid)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
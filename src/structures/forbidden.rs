@@ -0,0 +1,18 @@
+use deku::prelude::*;
+
+use super::version::FwVersion;
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct ForbiddenVersions {
+    #[deku(bits = "32", assert = "*num_versions <= Self::MAX_VERSIONS")]
+    pub num_versions: usize,
+
+    #[deku(count = "num_versions")]
+    pub versions: Vec<FwVersion>,
+}
+
+impl ForbiddenVersions {
+    /// Larger counts are rejected instead of allocated
+    pub const MAX_VERSIONS: usize = 0x1000;
+}
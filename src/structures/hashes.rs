@@ -0,0 +1,60 @@
+use deku::ctx::Endian;
+use deku::prelude::*;
+use sha2::{Digest, Sha256, Sha512};
+
+use super::itoc::ItocEntryType;
+
+/// HASHES_TABLE (HTOC) of secure-boot images: a digest of the content of each listed section.
+/// The layout is not checked against a real image yet, so digests are verified but not written:
+/// updating them after a modification (`--update-hashes`) is still to be done.
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct HashesTable {
+    #[deku(bits = "32")]
+    pub version: u32,
+    #[deku(bits = "8")]
+    pub num_entries: usize,
+    #[deku(bits = "8")]
+    pub hash_type: u8,
+    /// Digest size in bytes, 32 for SHA-256 and 64 for SHA-512
+    #[deku(bits = "16")]
+    pub hash_size: usize,
+    pub reserved: [u8; 8],
+
+    #[deku(count = "num_entries")]
+    pub entries: Vec<HashesTableEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
+pub struct HashesTableEntry {
+    #[deku(bits = "8", pad_bits_after = "8")]
+    pub section_type: ItocEntryType,
+    /// Offset of the digest, relative to the start of the section
+    #[deku(bits = "16", pad_bytes_after = "4")]
+    pub hash_offset: usize,
+}
+
+impl HashesTable {
+    pub fn entry(&self, section_type: &ItocEntryType) -> Option<&HashesTableEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.section_type == *section_type)
+    }
+
+    /// Digest of `content` with the table's hash size, `None` for unknown hash sizes
+    pub fn calc_hash(&self, content: &[u8]) -> Option<Vec<u8>> {
+        match self.hash_size {
+            32 => Some(Sha256::digest(content).to_vec()),
+            64 => Some(Sha512::digest(content).to_vec()),
+            _ => None,
+        }
+    }
+}
+
+impl HashesTableEntry {
+    /// The stored digest within the section content, `None` if it lies outside the section
+    pub fn hash<'a>(&self, table: &HashesTable, section: &'a [u8]) -> Option<&'a [u8]> {
+        section.get(self.hash_offset..)?.get(..table.hash_size)
+    }
+}
@@ -2,21 +2,52 @@ use deku::ctx::Endian;
 use deku::prelude::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
-#[deku(endian = "big", ctx="_ctx_endian: Endian")]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
 pub struct HwPointer {
     #[deku(bits = "32")]
     pub ptr: usize,
-    #[deku(pad_bits_before = "16", bits="16")]
-    pub crc: u16
+    /// Kept so erased (all 0xff) pointers are written back unchanged
+    #[deku(bits = "16")]
+    pub reserved: u16,
+    #[deku(bits = "16", update = "self.updated_crc()")]
+    pub crc: u16,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
 #[deku(endian = "big")]
 pub struct HwPointers {
+    #[deku(update = "self.boot_record.updated()?")]
     pub boot_record: HwPointer,
+    #[deku(update = "self.boot2.updated()?")]
     pub boot2: HwPointer,
+    #[deku(update = "self.toc.updated()?")]
     pub toc: HwPointer,
+    #[deku(update = "self.tools.updated()?")]
     pub tools: HwPointer,
+    #[deku(update = "self.authentication_start.updated()?")]
+    pub authentication_start: HwPointer,
+    #[deku(update = "self.authentication_end.updated()?")]
+    pub authentication_end: HwPointer,
+    #[deku(update = "self.digest.updated()?")]
+    pub digest: HwPointer,
+    #[deku(update = "self.digest_recovery_key.updated()?")]
+    pub digest_recovery_key: HwPointer,
+    #[deku(update = "self.fw_window_start.updated()?")]
+    pub fw_window_start: HwPointer,
+    #[deku(update = "self.fw_window_end.updated()?")]
+    pub fw_window_end: HwPointer,
+    #[deku(update = "self.image_info_section.updated()?")]
+    pub image_info_section: HwPointer,
+    #[deku(update = "self.image_signature.updated()?")]
+    pub image_signature: HwPointer,
+    #[deku(update = "self.public_key.updated()?")]
+    pub public_key: HwPointer,
+    #[deku(update = "self.fw_security_version.updated()?")]
+    pub fw_security_version: HwPointer,
+    #[deku(update = "self.gcm_iv_delta.updated()?")]
+    pub gcm_iv_delta: HwPointer,
+    #[deku(update = "self.hmac_start.updated()?")]
+    pub hmac_start: HwPointer,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
@@ -25,7 +56,7 @@ pub struct Boot2 {
     #[deku(bits = "32")]
     pub header: u32,
 
-    #[deku(bits = "32")]
+    #[deku(bits = "32", assert = "*size <= Self::MAX_WORDS")]
     pub size: usize,
 
     #[deku(count = "size")]
@@ -34,6 +65,114 @@ pub struct Boot2 {
     #[deku(bits = "32")]
     pub dword0: u32,
 
-    #[deku(bits = "32")]
-    pub dword1: u32,
-}
\ No newline at end of file
+    /// Upper half of the trailer dword, kept so it is written back unchanged
+    #[deku(bits = "16")]
+    pub reserved: u16,
+    #[deku(bits = "16", update = "self.calc_crc()?")]
+    pub crc: u16,
+}
+impl Boot2 {
+    /// Larger sizes are rejected instead of allocated, boot2 is a few KB
+    pub const MAX_WORDS: usize = 0x100000;
+
+    /// Total size in bytes, including header and trailer
+    pub fn total_size(&self) -> usize {
+        (self.size + 4) * 4
+    }
+
+    /// Offset of the CRC dword relative to the start of boot2
+    pub fn crc_offset(&self) -> usize {
+        self.total_size() - 4
+    }
+
+    /// CRC over everything but the trailer dword. Fails if `data` does not hold `size` dwords.
+    pub fn calc_crc(&self) -> Result<u16, DekuError> {
+        if self.data.len() != self.size {
+            return Err(DekuError::InvalidParam(
+                format!(
+                    "boot2 size is {:#x} dwords, but it holds {:#x}",
+                    self.size,
+                    self.data.len()
+                )
+                .into(),
+            ));
+        }
+        let bytes = self.to_bytes()?;
+        let crc = crate::crc::calc_crc16(0x0000, &bytes[..self.crc_offset()]);
+        Ok(crate::crc::calc_crc16(crc, &[0x00, 0x00]))
+    }
+}
+
+impl HwPointers {
+    pub const SIZE: usize = 16 * 8;
+
+    /// All pointers with their names, in table order
+    pub fn pointers(&self) -> [(&'static str, &HwPointer); 16] {
+        [
+            ("boot_record", &self.boot_record),
+            ("boot2", &self.boot2),
+            ("toc", &self.toc),
+            ("tools", &self.tools),
+            ("authentication_start", &self.authentication_start),
+            ("authentication_end", &self.authentication_end),
+            ("digest", &self.digest),
+            ("digest_recovery_key", &self.digest_recovery_key),
+            ("fw_window_start", &self.fw_window_start),
+            ("fw_window_end", &self.fw_window_end),
+            ("image_info_section", &self.image_info_section),
+            ("image_signature", &self.image_signature),
+            ("public_key", &self.public_key),
+            ("fw_security_version", &self.fw_security_version),
+            ("gcm_iv_delta", &self.gcm_iv_delta),
+            ("hmac_start", &self.hmac_start),
+        ]
+    }
+
+    /// The authenticated and FW window regions, where both ends are set
+    pub fn signed_ranges(&self) -> Vec<(&'static str, std::ops::Range<usize>)> {
+        [
+            (
+                "authenticated",
+                &self.authentication_start,
+                &self.authentication_end,
+            ),
+            ("fw_window", &self.fw_window_start, &self.fw_window_end),
+        ]
+        .into_iter()
+        .filter(|(_, start, end)| start.is_set() && end.is_set() && start.ptr < end.ptr)
+        .map(|(name, start, end)| (name, start.ptr..end.ptr))
+        .collect()
+    }
+}
+
+impl HwPointer {
+    pub fn is_set(&self) -> bool {
+        self.ptr != 0x00000000 && self.ptr != 0xffffffff
+    }
+
+    /// Slot left erased on flash, neither a pointer nor a CRC was ever written
+    pub fn is_erased(&self) -> bool {
+        self.ptr == 0xffffffff && self.crc == 0xffff
+    }
+
+    pub fn calc_crc(&self) -> u16 {
+        let mut bytes = (self.ptr as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        crate::crc::calc_hwcrc(0x0000, &bytes)
+    }
+
+    /// The CRC `update` stores, erased slots keep theirs
+    fn updated_crc(&self) -> u16 {
+        match self.is_erased() {
+            true => self.crc,
+            false => self.calc_crc(),
+        }
+    }
+
+    /// A copy with the CRC updated, for updating the pointers as part of [`HwPointers`]
+    fn updated(&self) -> Result<Self, DekuError> {
+        let mut pointer = self.clone();
+        pointer.update()?;
+        Ok(pointer)
+    }
+}
@@ -0,0 +1,95 @@
+use deku::prelude::*;
+
+use super::version::FwVersion;
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct ImageInfo {
+    #[deku(bits = "1")]
+    pub mcc_en: bool,
+    #[deku(bits = "1")]
+    pub cs_tokens_supported: bool,
+    #[deku(bits = "1")]
+    pub frc_supported: bool,
+    #[deku(bits = "1")]
+    pub signed_mlnx_nvconfig_files: bool,
+    #[deku(bits = "1")]
+    pub signed_vendor_nvconfig_files: bool,
+    #[deku(bits = "1")]
+    pub debug_fw: bool,
+    #[deku(bits = "1")]
+    pub long_keys: bool,
+    #[deku(bits = "1")]
+    pub secure_boot: bool,
+    #[deku(bits = "8")]
+    pub major_version: u8,
+    #[deku(pad_bits_before = "8", bits = "8")]
+    pub minor_version: u8,
+
+    pub fw_version: FwVersion,
+
+    #[deku(bits = "8")]
+    pub build_seconds: u8,
+    #[deku(bits = "8")]
+    pub build_minutes: u8,
+    #[deku(bits = "8", pad_bits_after = "8")]
+    pub build_hour: u8,
+    #[deku(bits = "16")]
+    pub build_year: u16,
+    #[deku(bits = "8")]
+    pub build_month: u8,
+    #[deku(bits = "8")]
+    pub build_day: u8,
+
+    pub mic_version: FwVersion,
+
+    #[deku(pad_bytes_before = "4")]
+    pub pci_vendor_id: u16,
+    pub pci_device_id: u16,
+
+    pub reserved0: [u8; 12],
+
+    pub psid: [u8; 16],
+
+    #[deku(pad_bits_before = "16")]
+    pub vsd_vendor_id: u16,
+
+    pub vsd: [u8; 208],
+}
+
+impl ImageInfo {
+    pub fn psid(&self) -> String {
+        String::from_utf8_lossy(&self.psid)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    /// Build date, stored as BCD
+    pub fn release_date(&self) -> String {
+        format!(
+            "{:04x}-{:02x}-{:02x}",
+            self.build_year, self.build_month, self.build_day
+        )
+    }
+
+    pub fn security_attributes(&self) -> Vec<&'static str> {
+        [
+            (self.secure_boot, "secure-boot"),
+            (self.debug_fw, "debug-fw"),
+            (self.long_keys, "long-keys"),
+            (self.signed_mlnx_nvconfig_files, "signed-mlnx-nvconfig"),
+            (self.signed_vendor_nvconfig_files, "signed-vendor-nvconfig"),
+            (self.mcc_en, "mcc"),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| name)
+        .collect()
+    }
+
+    pub fn vsd(&self) -> String {
+        String::from_utf8_lossy(&self.vsd)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
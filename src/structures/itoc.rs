@@ -1,7 +1,9 @@
 use deku::ctx::{BitSize, Endian};
 use deku::prelude::*;
 
-use crate::firmware::FirmwareStructure;
+use crate::cacheline::CacheLineGeometry;
+use crate::error::{FirmwareError, Result};
+use crate::firmware::{Firmware, FirmwareStructure};
 
 #[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
 #[deku(
@@ -80,12 +82,36 @@ pub enum ItocEntryType {
     #[deku(id = 0xa4)]
     PublicKeys4096,
 
+    #[deku(id = 0xe0)]
+    MfgInfo,
+
+    #[deku(id = 0xe1)]
+    DevInfo,
+
+    #[deku(id = 0xe2)]
+    NvData1,
+
+    #[deku(id = 0xe3)]
+    VpdR0,
+
+    #[deku(id = 0xe4)]
+    NvData2,
+
+    #[deku(id = 0xe5)]
+    FwNvLog,
+
+    #[deku(id = 0xe6)]
+    NvData0,
+
     #[deku(id = 0xe9)]
     CrDumpMaskData,
 
     #[deku(id = 0xeb)]
     ProgrammableHwFw,
 
+    #[deku(id = 0xfa)]
+    HashesTable,
+
     #[deku(id_pat = "_")]
     Unknown(u8),
 }
@@ -116,14 +142,75 @@ impl std::fmt::Display for ItocEntryType {
             Self::ForbiddenVersions => write!(f, "FORBIDDEN_VERSIONS"),
             Self::ImageSignature512 => write!(f, "IMAGE_SIGNATURE_512"),
             Self::PublicKeys4096 => write!(f, "PUBLIC_KEYS_4096"),
+            Self::MfgInfo => write!(f, "MFG_INFO"),
+            Self::DevInfo => write!(f, "DEV_INFO"),
+            Self::NvData1 => write!(f, "NV_DATA1"),
+            Self::VpdR0 => write!(f, "VPD_R0"),
+            Self::NvData2 => write!(f, "NV_DATA2"),
+            Self::FwNvLog => write!(f, "FW_NV_LOG"),
+            Self::NvData0 => write!(f, "NV_DATA0"),
             Self::CrDumpMaskData => write!(f, "CRDUMP_MASK_DATA"),
             Self::ProgrammableHwFw => write!(f, "PROGRAMMABLE_HW_FW"),
+            Self::HashesTable => write!(f, "HASHES_TABLE"),
             ItocEntryType::Unknown(id) => write!(f, "UNKNOWN_SECTION_{:02x}", id),
         }
     }
 }
 
+impl std::str::FromStr for ItocEntryType {
+    type Err = FirmwareError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(id) = s.strip_prefix("UNKNOWN_SECTION_") {
+            return u8::from_str_radix(id, 16)
+                .map(Self::Unknown)
+                .map_err(|_| FirmwareError::UnknownSectionType(s.to_string()));
+        }
+        Self::KNOWN
+            .iter()
+            .find(|entry_type| entry_type.to_string().eq_ignore_ascii_case(s))
+            .cloned()
+            .ok_or_else(|| FirmwareError::UnknownSectionType(s.to_string()))
+    }
+}
+
 impl ItocEntryType {
+    pub const KNOWN: [ItocEntryType; 33] = [
+        Self::PciCode,
+        Self::MainCode,
+        Self::PcieLinkCode,
+        Self::IronPrepCode,
+        Self::PostIronBootCode,
+        Self::UpgradeCode,
+        Self::HwBootCfg,
+        Self::HwMainCfg,
+        Self::PhyUcCode,
+        Self::PhyUcConsts,
+        Self::PciePhyUcCode,
+        Self::ImageInfo,
+        Self::FwBootCfg,
+        Self::FwMainCfg,
+        Self::RomCode,
+        Self::ResetInfo,
+        Self::DbgFwIni,
+        Self::DbgFwParams,
+        Self::ImageSignature256,
+        Self::PublicKeys2048,
+        Self::ForbiddenVersions,
+        Self::ImageSignature512,
+        Self::PublicKeys4096,
+        Self::MfgInfo,
+        Self::DevInfo,
+        Self::NvData1,
+        Self::VpdR0,
+        Self::NvData2,
+        Self::FwNvLog,
+        Self::NvData0,
+        Self::CrDumpMaskData,
+        Self::ProgrammableHwFw,
+        Self::HashesTable,
+    ];
+
     pub fn is_code(&self) -> bool {
         matches!(
             *self,
@@ -135,6 +222,80 @@ impl ItocEntryType {
                 | ItocEntryType::UpgradeCode
         )
     }
+
+    pub fn is_nv_data(&self) -> bool {
+        matches!(
+            *self,
+            ItocEntryType::NvData0 | ItocEntryType::NvData1 | ItocEntryType::NvData2
+        )
+    }
+
+    pub fn is_device_data(&self) -> bool {
+        matches!(
+            *self,
+            ItocEntryType::MfgInfo
+                | ItocEntryType::DevInfo
+                | ItocEntryType::NvData1
+                | ItocEntryType::VpdR0
+                | ItocEntryType::NvData2
+                | ItocEntryType::FwNvLog
+                | ItocEntryType::NvData0
+        )
+    }
+}
+
+/// Where the CRC of a section is kept, selected by the `crc` field of its ITOC entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcMode {
+    /// `section_crc` of the ITOC entry
+    InItocEntry,
+    /// The section is not protected by a CRC
+    None,
+    /// The last dword of the section itself
+    InSection,
+    /// A `crc` value without known meaning. Such CRCs are neither checked nor updated.
+    Unknown(u8),
+}
+
+/// Header in front of the ITOC and DTOC entries: the table name, three fixed signature dwords,
+/// the table version and a CRC over the header
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct TocHeader {
+    /// "ITOC" or "DTOC"
+    pub name: [u8; 4],
+    pub signature: [u32; 3],
+    #[deku(bits = "8")]
+    pub version: u8,
+    pub reserved: [u8; 13],
+    #[deku(bits = "16", update = "self.calc_crc()")]
+    pub crc: u16,
+}
+
+impl TocHeader {
+    pub const SIGNATURE: [u32; 3] = [0x04081516, 0x2342cafa, 0xbacafe00];
+
+    pub fn new(name: &[u8; 4]) -> Self {
+        let mut header = Self {
+            name: *name,
+            signature: Self::SIGNATURE,
+            version: 0,
+            reserved: [0; 13],
+            crc: 0,
+        };
+        header.crc = header.calc_crc();
+        header
+    }
+
+    /// Whether the header carries `name` and the fixed signature
+    pub fn is_valid(&self, name: &[u8; 4]) -> bool {
+        &self.name == name && self.signature == Self::SIGNATURE
+    }
+
+    pub fn calc_crc(&self) -> u16 {
+        let bytes = self.to_bytes().unwrap();
+        crate::crc::calc_crc16(0x0000, &bytes[..0x1e])
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
@@ -155,7 +316,9 @@ pub struct ItocEntry {
     #[deku(bits = "32")]
     pub entry_point: u32, // param1
 
-    #[deku(pad_bytes_before = "4", pad_bits_before = "16", bits = "16")]
+    /// Reserved fields are kept so entries are written back unchanged
+    pub reserved0: [u8; 6],
+    #[deku(bits = "16")]
     pub version: u16,
 
     #[deku(bits = "32")]
@@ -163,27 +326,243 @@ pub struct ItocEntry {
 
     #[deku(bits = "1")]
     pub encrypted_section: bool,
+    #[deku(bits = "7")]
+    pub reserved1: u8,
 
-    #[deku(pad_bits_before = "7", bits = "8")]
+    #[deku(bits = "8")]
     pub crc: u8,
     #[deku(bits = "16")]
     pub section_crc: u16,
 
-    #[deku(
-        pad_bits_before = "16",
-        bits = "16",
-        update = "self.calc_itoc_entry_crc()"
-    )]
+    #[deku(bits = "16")]
+    pub reserved2: u16,
+    #[deku(bits = "16", update = "self.calc_itoc_entry_crc()")]
     pub itoc_entry_crc: u16,
 }
 
 impl ItocEntry {
+    /// Largest section the 24 bit `size` field can describe
+    pub const MAX_SIZE: usize = 0xff_ffff;
+
+    /// Boundary sections start and end on
+    pub const ALIGNMENT: usize = 4;
+
+    /// An entry for a plain section with its CRC in the entry. The section CRC is zero until
+    /// computed, see `with_content`. After changing fields, `update` recomputes the entry CRC.
+    pub fn new(entry_type: ItocEntryType, flash_addr: usize, size: usize) -> Result<Self> {
+        if size > Self::MAX_SIZE {
+            return Err(FirmwareError::FieldOverflow {
+                field: "Section size",
+                value: size,
+                max: Self::MAX_SIZE,
+            });
+        }
+        if flash_addr > u32::MAX as usize {
+            return Err(FirmwareError::FieldOverflow {
+                field: "Flash address",
+                value: flash_addr,
+                max: u32::MAX as usize,
+            });
+        }
+        let mut entry = Self {
+            entry_type,
+            size,
+            zipped_image: false,
+            cache_line_crc: false,
+            load_address: 0,
+            entry_point: 0,
+            reserved0: [0; 6],
+            version: 0,
+            flash_addr,
+            encrypted_section: false,
+            reserved1: 0,
+            crc: 0,
+            section_crc: 0,
+            reserved2: 0,
+            itoc_entry_crc: 0,
+        };
+        entry.itoc_entry_crc = entry.calc_itoc_entry_crc();
+        Ok(entry)
+    }
+
+    /// An entry for `content` placed at `flash_addr`, with its section CRC
+    pub fn with_content(
+        entry_type: ItocEntryType,
+        flash_addr: usize,
+        content: &[u8],
+    ) -> Result<Self> {
+        let mut entry = Self::new(entry_type, flash_addr, content.len())?;
+        entry.section_crc = Self::calc_content_crc(content);
+        entry.itoc_entry_crc = entry.calc_itoc_entry_crc();
+        Ok(entry)
+    }
+
+    /// CRC of the bytes covered by a section CRC, see `payload`
+    pub fn calc_content_crc(payload: &[u8]) -> u16 {
+        let crc = crate::crc::calc_crc16(0x0000, payload);
+        crate::crc::calc_crc16(crc, &[0x00, 0x00])
+    }
+
     pub fn calc_itoc_entry_crc(&self) -> u16 {
         let bytes = self.to_bytes().unwrap();
         crate::crc::calc_crc16(0x0000, &bytes[..0x1e])
     }
 
+    pub fn itoc_entry_crc_valid(&self) -> bool {
+        self.itoc_entry_crc == self.calc_itoc_entry_crc()
+    }
+
+    pub fn check_itoc_entry_crc(&self) -> Result<()> {
+        let expected = self.calc_itoc_entry_crc();
+        if self.itoc_entry_crc != expected {
+            return Err(FirmwareError::BadCrc {
+                section: format!("ITOC entry for {}", self.entry_type),
+                expected,
+                actual: self.itoc_entry_crc,
+            });
+        }
+        Ok(())
+    }
+
+    /// Every field in layout order with its value, for display
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let type_id = self.to_bytes().map_or(0, |bytes| bytes[0]);
+        let crc_mode = match self.crc_mode() {
+            CrcMode::InItocEntry => "in ITOC entry",
+            CrcMode::None => "none",
+            CrcMode::InSection => "in section",
+            CrcMode::Unknown(_) => "unknown",
+        };
+        vec![
+            (
+                "entry_type",
+                format!("{} ({:#04x})", self.entry_type, type_id),
+            ),
+            ("size", format!("{:#x}", self.size)),
+            ("zipped_image", self.zipped_image.to_string()),
+            ("cache_line_crc", self.cache_line_crc.to_string()),
+            ("load_address", format!("{:#010x}", self.load_address)),
+            ("entry_point", format!("{:#010x}", self.entry_point)),
+            ("version", format!("{:#06x}", self.version)),
+            ("flash_addr", format!("{:#010x}", self.flash_addr)),
+            ("encrypted_section", self.encrypted_section.to_string()),
+            ("crc", format!("{} ({})", self.crc, crc_mode)),
+            ("section_crc", format!("{:#06x}", self.section_crc)),
+            ("itoc_entry_crc", format!("{:#06x}", self.itoc_entry_crc)),
+        ]
+    }
+
+    /// Whether the stored section CRC matches the content, `None` for sections without one
+    pub fn section_crc_valid(&self, firmware: &Firmware) -> Option<bool> {
+        self.stored_section_crc(firmware)
+            .map(|crc| crc == self.calc_section_crc(firmware))
+    }
+
+    /// Whether every cache line CRC matches, `None` for sections without cache lines
+    pub fn cache_line_crcs_valid(&self, firmware: &Firmware) -> Option<bool> {
+        self.cache_line_crc
+            .then(|| self.cache_lines(firmware).all(|(_, _, crc_ok)| crc_ok))
+    }
+
+    /// Flash offset, code and CRC status of each complete cache line of the section, nothing
+    /// for sections without cache lines. The code is `data_size` bytes of the firmware's
+    /// cache line geometry, 0x40 by default.
+    pub fn cache_lines<'a>(
+        &self,
+        firmware: &'a Firmware,
+    ) -> impl Iterator<Item = (usize, &'a [u8], bool)> + 'a {
+        let content = match self.cache_line_crc {
+            true => self.content().read_bytes(firmware),
+            false => &[],
+        };
+        let flash_addr = self.flash_addr;
+        firmware
+            .cache_line_geometry()
+            .lines(content)
+            .map(move |line| (flash_addr + line.offset, line.data, line.crc_valid()))
+    }
+
+    /// Name of the file dump-code writes the section to
+    pub fn code_file_name(&self) -> String {
+        format!("{:08x}_{}", self.load_address, self.entry_type)
+    }
+
+    /// End of the section, `None` if `flash_addr + size` overflows
+    pub fn end(&self) -> Option<usize> {
+        self.flash_addr.checked_add(self.size)
+    }
+
     pub fn content(&self) -> FirmwareStructure<usize> {
         FirmwareStructure(self.flash_addr, self.size)
     }
+
+    pub fn crc_mode(&self) -> CrcMode {
+        match self.crc {
+            0 => CrcMode::InItocEntry,
+            1 => CrcMode::None,
+            2 => CrcMode::InSection,
+            mode => CrcMode::Unknown(mode),
+        }
+    }
+
+    /// Ways the entry breaks the alignment rules: sections start and end on dword boundaries
+    /// and code with cache lines is loaded at a cache line boundary
+    pub fn misalignments(&self, geometry: CacheLineGeometry) -> Vec<String> {
+        let mut misalignments = vec![];
+        if !self.flash_addr.is_multiple_of(Self::ALIGNMENT) {
+            misalignments.push(format!(
+                "flash address {:#x} is not dword aligned",
+                self.flash_addr
+            ));
+        }
+        if !self.size.is_multiple_of(Self::ALIGNMENT) {
+            misalignments.push(format!("size {:#x} is not a dword multiple", self.size));
+        }
+        let load_address = self.load_address as usize;
+        if self.entry_type.is_code()
+            && self.cache_line_crc
+            && !load_address.is_multiple_of(geometry.data_size)
+        {
+            misalignments.push(format!(
+                "load address {:#010x} is not aligned to the {:#x} byte cache line",
+                load_address, geometry.data_size
+            ));
+        }
+        misalignments
+    }
+
+    /// Round the flash address and size up to dword boundaries, returning whether either
+    /// changed. The entry CRC is left as it is.
+    pub fn align(&mut self) -> bool {
+        let (flash_addr, size) = (self.flash_addr, self.size);
+        self.flash_addr = flash_addr.next_multiple_of(Self::ALIGNMENT);
+        self.size = size.next_multiple_of(Self::ALIGNMENT);
+        (self.flash_addr, self.size) != (flash_addr, size)
+    }
+
+    /// The part of the section covered by its CRC, without the CRC dword of in-section CRCs
+    pub fn payload(&self) -> FirmwareStructure<usize> {
+        match self.crc_mode() {
+            CrcMode::InSection => FirmwareStructure(self.flash_addr, self.size.saturating_sub(4)),
+            _ => self.content(),
+        }
+    }
+
+    pub fn calc_section_crc(&self, firmware: &Firmware) -> u16 {
+        Self::calc_content_crc(self.payload().read_bytes(firmware))
+    }
+
+    /// The CRC the section is stored with, `None` if it has none
+    pub fn stored_section_crc(&self, firmware: &Firmware) -> Option<u16> {
+        match self.crc_mode() {
+            CrcMode::InItocEntry => Some(self.section_crc),
+            CrcMode::None | CrcMode::Unknown(_) => None,
+            CrcMode::InSection => {
+                let tail = firmware
+                    .bytes(self.flash_addr + self.size.checked_sub(4)?, 4)
+                    .ok()?;
+                Some(u32::from_be_bytes(tail.try_into().ok()?) as u16)
+            }
+        }
+    }
 }
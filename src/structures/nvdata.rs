@@ -0,0 +1,138 @@
+use deku::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct NvTlv {
+    #[deku(bits = "4")]
+    pub version: u8,
+    #[deku(bits = "2")]
+    pub priority: u8,
+    #[deku(bits = "1")]
+    pub rd_en: bool,
+    #[deku(bits = "1")]
+    pub over_en: bool,
+    #[deku(pad_bits_before = "12", bits = "12")]
+    pub length: usize,
+
+    #[deku(bits = "8")]
+    pub type_class: u8,
+    #[deku(bits = "24")]
+    pub type_index: u32,
+
+    #[deku(pad_bits_before = "16", bits = "16")]
+    pub crc: u16,
+
+    #[deku(count = "length")]
+    pub data: Vec<u8>,
+}
+
+impl NvTlv {
+    pub const HEADER_SIZE: usize = 0xc;
+
+    pub fn total_size(&self) -> usize {
+        Self::HEADER_SIZE + self.length.next_multiple_of(4)
+    }
+
+    pub fn known_type(&self) -> Option<&'static NvTlvType> {
+        KNOWN_TLV_TYPES.iter().find(|tlv_type| {
+            tlv_type.type_class == self.type_class && tlv_type.type_index == self.param_index()
+        })
+    }
+
+    /// Per-port and per-function classes keep the port/function number in bits 16..23
+    pub fn param_index(&self) -> u32 {
+        match self.type_class {
+            0x00 => self.type_index,
+            _ => self.type_index & 0xffff,
+        }
+    }
+
+    pub fn param_target(&self) -> Option<u8> {
+        match self.type_class {
+            0x00 => None,
+            _ => Some((self.type_index >> 16) as u8),
+        }
+    }
+
+    pub fn dword(&self, index: usize) -> Option<u32> {
+        let bytes = self.data.get(index * 4..index * 4 + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+impl std::fmt::Display for NvTlv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.known_type() {
+            Some(tlv_type) => write!(f, "{}", tlv_type.name)?,
+            None => write!(f, "TLV_{:02x}_{:06x}", self.type_class, self.type_index)?,
+        }
+        if let Some(target) = self.param_target() {
+            write!(f, "[{}]", target)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NvField {
+    pub name: &'static str,
+    pub dword: usize,
+    pub shift: u32,
+    pub width: u32,
+}
+
+impl NvField {
+    pub const fn new(name: &'static str, dword: usize, shift: u32, width: u32) -> Self {
+        Self {
+            name,
+            dword,
+            shift,
+            width,
+        }
+    }
+
+    pub fn decode(&self, tlv: &NvTlv) -> Option<u32> {
+        let mask = if self.width == 32 {
+            u32::MAX
+        } else {
+            (1 << self.width) - 1
+        };
+        Some((tlv.dword(self.dword)? >> self.shift) & mask)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NvTlvType {
+    pub name: &'static str,
+    pub type_class: u8,
+    pub type_index: u32,
+    pub fields: &'static [NvField],
+}
+
+pub const KNOWN_TLV_TYPES: &[NvTlvType] = &[
+    NvTlvType {
+        name: "PCI_CONF",
+        type_class: 0x03,
+        type_index: 0x80,
+        fields: &[
+            NvField::new("SRIOV_EN", 0, 31, 1),
+            NvField::new("NUM_OF_VFS", 0, 0, 16),
+        ],
+    },
+    NvTlvType {
+        name: "VPI_SETTINGS",
+        type_class: 0x01,
+        type_index: 0x12,
+        fields: &[NvField::new("LINK_TYPE", 0, 0, 2)],
+    },
+    NvTlvType {
+        name: "BOOT_SETTINGS",
+        type_class: 0x01,
+        type_index: 0x10,
+        fields: &[
+            NvField::new("BOOT_OPTION_ROM_EN", 0, 31, 1),
+            NvField::new("BOOT_VLAN_EN", 0, 30, 1),
+            NvField::new("BOOT_VLAN", 0, 0, 12),
+        ],
+    },
+];
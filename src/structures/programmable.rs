@@ -0,0 +1,42 @@
+use deku::ctx::Endian;
+use deku::prelude::*;
+
+/// Header of the PROGRAMMABLE_HW_FW section, followed by the PLD images it lists
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big")]
+pub struct ProgrammableHwFw {
+    #[deku(bits = "16")]
+    pub version: u16,
+    #[deku(bits = "16")]
+    pub num_images: usize,
+    pub reserved: [u8; 12],
+
+    #[deku(count = "num_images")]
+    pub images: Vec<PldImage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
+pub struct PldImage {
+    #[deku(bits = "32")]
+    pub pld_id: u32,
+    /// Relative to the start of the section
+    #[deku(bits = "32")]
+    pub offset: usize,
+    #[deku(bits = "32")]
+    pub size: usize,
+    #[deku(pad_bits_before = "16", bits = "16")]
+    pub crc: u16,
+}
+
+impl PldImage {
+    /// The image data within the section content, `None` if it lies outside the section
+    pub fn data<'a>(&self, section: &'a [u8]) -> Option<&'a [u8]> {
+        section.get(self.offset..)?.get(..self.size)
+    }
+
+    pub fn calc_crc(&self, section: &[u8]) -> Option<u16> {
+        let crc = crate::crc::calc_crc16(0x0000, self.data(section)?);
+        Some(crate::crc::calc_crc16(crc, &[0x00, 0x00]))
+    }
+}
@@ -0,0 +1,36 @@
+use deku::ctx::Endian;
+use deku::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, DekuRead, DekuWrite)]
+#[deku(endian = "big", ctx = "_ctx_endian: Endian")]
+pub struct FwVersion {
+    #[deku(bits = "16", pad_bits_after = "16")]
+    pub major: u16,
+    #[deku(bits = "16")]
+    pub minor: u16,
+    #[deku(bits = "16")]
+    pub subminor: u16,
+}
+
+impl std::fmt::Display for FwVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{:04}", self.major, self.minor, self.subminor)
+    }
+}
+
+impl std::str::FromStr for FwVersion {
+    type Err = crate::error::FirmwareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || crate::error::FirmwareError::InvalidVersion(s.to_string());
+        let parts: Vec<&str> = s.split('.').collect();
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            major: parts[0].parse().map_err(|_| invalid())?,
+            minor: parts[1].parse().map_err(|_| invalid())?,
+            subminor: parts[2].parse().map_err(|_| invalid())?,
+        })
+    }
+}
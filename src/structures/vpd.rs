@@ -0,0 +1,197 @@
+use crate::error::{FirmwareError, Result};
+
+const TAG_IDENTIFIER: u8 = 0x82;
+const TAG_READ_ONLY: u8 = 0x90;
+const TAG_READ_WRITE: u8 = 0x91;
+const TAG_END: u8 = 0x78;
+
+/// Keyword holding the checksum of the read-only area, followed by reserved space
+const CHECKSUM_KEYWORD: &str = "RV";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VpdKeyword {
+    pub keyword: String,
+    pub data: Vec<u8>,
+}
+
+impl VpdKeyword {
+    /// The data as text, for the keywords that hold text (serial number, part number, ...)
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data)
+            .ok()
+            .filter(|text| text.chars().all(|c| c.is_ascii_graphic() || c == ' '))
+    }
+}
+
+/// PCI Vital Product Data as stored in the VPD_R0 section: an identifier string, the read-only
+/// (VPD-R) and the read-write (VPD-W) keywords
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vpd {
+    pub identifier: String,
+    pub read_only: Vec<VpdKeyword>,
+    pub read_write: Vec<VpdKeyword>,
+}
+
+fn invalid(offset: usize, what: &str) -> FirmwareError {
+    FirmwareError::InvalidVpd {
+        offset,
+        what: what.to_string(),
+    }
+}
+
+fn parse_keywords(data: &[u8], base: usize) -> Result<Vec<VpdKeyword>> {
+    let mut keywords = vec![];
+    let mut offset = 0;
+    while offset < data.len() {
+        let header = data
+            .get(offset..offset + 3)
+            .ok_or_else(|| invalid(base + offset, "truncated keyword"))?;
+        let len = header[2] as usize;
+        let value = data
+            .get(offset + 3..offset + 3 + len)
+            .ok_or_else(|| invalid(base + offset, "keyword extends past its resource"))?;
+        keywords.push(VpdKeyword {
+            keyword: String::from_utf8_lossy(&header[..2]).to_string(),
+            data: value.to_vec(),
+        });
+        offset += 3 + len;
+    }
+    Ok(keywords)
+}
+
+fn push_keywords(bytes: &mut Vec<u8>, keywords: &[VpdKeyword]) {
+    for keyword in keywords {
+        bytes.extend_from_slice(keyword.keyword.as_bytes());
+        bytes.push(keyword.data.len() as u8);
+        bytes.extend_from_slice(&keyword.data);
+    }
+}
+
+fn push_resource(bytes: &mut Vec<u8>, tag: u8, data: &[u8]) {
+    bytes.push(tag);
+    bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(data);
+}
+
+impl Vpd {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut vpd = Self {
+            identifier: String::new(),
+            read_only: vec![],
+            read_write: vec![],
+        };
+        let mut offset = 0;
+        loop {
+            let tag = *data
+                .get(offset)
+                .ok_or_else(|| invalid(offset, "missing end tag"))?;
+            if tag == TAG_END {
+                return Ok(vpd);
+            }
+            let len = data
+                .get(offset + 1..offset + 3)
+                .map(|len| u16::from_le_bytes([len[0], len[1]]) as usize)
+                .ok_or_else(|| invalid(offset, "truncated resource"))?;
+            let body = data
+                .get(offset + 3..offset + 3 + len)
+                .ok_or_else(|| invalid(offset, "resource extends past the section"))?;
+            match tag {
+                TAG_IDENTIFIER => vpd.identifier = String::from_utf8_lossy(body).to_string(),
+                TAG_READ_ONLY => vpd.read_only = parse_keywords(body, offset + 3)?,
+                TAG_READ_WRITE => vpd.read_write = parse_keywords(body, offset + 3)?,
+                _ => return Err(invalid(offset, "unknown resource tag")),
+            }
+            offset += 3 + len;
+        }
+    }
+
+    /// Serialize with a recomputed RV checksum. The checksum makes all bytes up to and
+    /// including it sum to zero.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        push_resource(&mut bytes, TAG_IDENTIFIER, self.identifier.as_bytes());
+
+        let mut read_only = vec![];
+        push_keywords(&mut read_only, &self.read_only);
+        push_resource(&mut bytes, TAG_READ_ONLY, &read_only);
+        if let Some(checksum_offset) = self.checksum_offset() {
+            let sum = bytes[..checksum_offset]
+                .iter()
+                .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            bytes[checksum_offset] = sum.wrapping_neg();
+        }
+
+        if !self.read_write.is_empty() {
+            let mut read_write = vec![];
+            push_keywords(&mut read_write, &self.read_write);
+            push_resource(&mut bytes, TAG_READ_WRITE, &read_write);
+        }
+        bytes.push(TAG_END);
+        bytes
+    }
+
+    /// Offset of the RV checksum byte in `to_bytes`
+    fn checksum_offset(&self) -> Option<usize> {
+        let mut offset = 3 + self.identifier.len() + 3;
+        for keyword in &self.read_only {
+            if keyword.keyword == CHECKSUM_KEYWORD {
+                return (!keyword.data.is_empty()).then_some(offset + 3);
+            }
+            offset += 3 + keyword.data.len();
+        }
+        None
+    }
+
+    /// Whether the stored RV checksum of the serialized `data` is correct
+    pub fn checksum_valid(&self, data: &[u8]) -> bool {
+        self.checksum_offset().is_some_and(|offset| {
+            data.get(..=offset).is_some_and(|bytes| {
+                bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+            })
+        })
+    }
+
+    pub fn keyword(&self, keyword: &str) -> Option<&VpdKeyword> {
+        self.read_only
+            .iter()
+            .chain(&self.read_write)
+            .find(|entry| entry.keyword == keyword)
+    }
+
+    /// Set an existing keyword, or add it: Y* keywords to the read-write keywords before the
+    /// RW space, all others to the read-only keywords before the checksum
+    pub fn set(&mut self, keyword: &str, data: Vec<u8>) -> Result<()> {
+        if keyword.len() != 2 || keyword == CHECKSUM_KEYWORD || data.len() > 0xff {
+            return Err(FirmwareError::InvalidVpd {
+                offset: 0,
+                what: format!("cannot set keyword {:?} to {} bytes", keyword, data.len()),
+            });
+        }
+        let existing = self
+            .read_only
+            .iter_mut()
+            .chain(&mut self.read_write)
+            .find(|entry| entry.keyword == keyword);
+        match existing {
+            Some(entry) => entry.data = data,
+            None => {
+                let (keywords, before) = match keyword.starts_with('Y') {
+                    true => (&mut self.read_write, "RW"),
+                    false => (&mut self.read_only, CHECKSUM_KEYWORD),
+                };
+                let position = keywords
+                    .iter()
+                    .position(|entry| entry.keyword == before)
+                    .unwrap_or(keywords.len());
+                keywords.insert(
+                    position,
+                    VpdKeyword {
+                        keyword: keyword.to_string(),
+                        data,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
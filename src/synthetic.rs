@@ -0,0 +1,255 @@
+//! Small, valid FS4 images built from scratch, for tests and conformance runs without
+//! proprietary dumps
+
+use deku::prelude::*;
+use flate2::{write::ZlibEncoder, Compression};
+use std::io::Write;
+
+use crate::crc::{calc_crc16, calc_hwcrc};
+use crate::error::Result;
+use crate::firmware::Firmware;
+use crate::structures::itoc::{ItocEntry, ItocEntryType, TocHeader};
+
+fn section_crc(data: &[u8]) -> u16 {
+    calc_crc16(calc_crc16(0x0000, data), &[0x00, 0x00])
+}
+
+/// Builds small, valid FS4-style images from scratch
+pub struct ImageBuilder {
+    data: Vec<u8>,
+    itoc: Vec<ItocEntry>,
+    dtoc: Vec<ItocEntry>,
+}
+
+impl ImageBuilder {
+    pub const ITOC_OFFSET: usize = 0x5000;
+    pub const BOOT2_OFFSET: usize = 0x1000;
+
+    pub fn new(size: usize) -> Self {
+        let mut data = vec![0xff; size];
+        data[..0x10].copy_from_slice(&[
+            0x4d, 0x54, 0x46, 0x57, 0x8c, 0xdf, 0xd0, 0x00, 0xde, 0xad, 0x92, 0x70, 0x41, 0x54,
+            0xbe, 0xef,
+        ]);
+        Self {
+            data,
+            itoc: vec![],
+            dtoc: vec![],
+        }
+    }
+
+    pub fn put(&mut self, offset: usize, bytes: &[u8]) -> &mut Self {
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    pub fn boot2(&mut self, code: &[u32]) -> &mut Self {
+        let mut boot2 = vec![];
+        boot2.extend_from_slice(&0x12345678u32.to_be_bytes());
+        boot2.extend_from_slice(&(code.len() as u32).to_be_bytes());
+        for word in code {
+            boot2.extend_from_slice(&word.to_be_bytes());
+        }
+        boot2.extend_from_slice(&[0x00; 4]);
+        let crc = section_crc(&boot2);
+        boot2.extend_from_slice(&(crc as u32).to_be_bytes());
+        self.put(Self::BOOT2_OFFSET, &boot2)
+    }
+
+    fn entry(entry_type: ItocEntryType, flash_addr: usize, content: &[u8]) -> ItocEntry {
+        ItocEntry::with_content(entry_type, flash_addr, content).expect("synthetic section")
+    }
+
+    pub fn section(
+        &mut self,
+        entry_type: ItocEntryType,
+        flash_addr: usize,
+        content: &[u8],
+    ) -> &mut Self {
+        self.itoc.push(Self::entry(entry_type, flash_addr, content));
+        self.put(flash_addr, content)
+    }
+
+    pub fn code_section(
+        &mut self,
+        entry_type: ItocEntryType,
+        flash_addr: usize,
+        load_address: u32,
+        entry_point: u32,
+        code: &[u8],
+    ) -> &mut Self {
+        let mut content = vec![];
+        for cache_line in code.chunks(0x40) {
+            let mut cache_line = cache_line.to_vec();
+            cache_line.extend_from_slice(&[0x00, 0x00]);
+            cache_line.extend_from_slice(&calc_hwcrc(0x0000, &cache_line).to_le_bytes());
+            content.extend_from_slice(&cache_line);
+        }
+        let mut entry = Self::entry(entry_type, flash_addr, &content);
+        entry.cache_line_crc = true;
+        entry.load_address = load_address;
+        entry.entry_point = entry_point;
+        self.itoc.push(entry);
+        self.put(flash_addr, &content)
+    }
+
+    pub fn device_section(
+        &mut self,
+        entry_type: ItocEntryType,
+        flash_addr: usize,
+        content: &[u8],
+    ) -> &mut Self {
+        self.dtoc.push(Self::entry(entry_type, flash_addr, content));
+        self.put(flash_addr, content)
+    }
+
+    fn write_toc(
+        &mut self,
+        offset: usize,
+        signature: &[u8; 4],
+        entries: &[ItocEntry],
+    ) -> Result<()> {
+        self.put(offset, &TocHeader::new(signature).to_bytes()?);
+
+        for (i, entry) in entries.iter().enumerate() {
+            let mut entry = entry.clone();
+            entry.update()?;
+            self.put(offset + 0x20 * (i + 1), &entry.to_bytes()?);
+        }
+        Ok(())
+    }
+
+    pub fn build(&mut self) -> Result<Firmware> {
+        let toc = self.itoc.clone();
+        self.write_toc(Self::ITOC_OFFSET, b"ITOC", &toc)?;
+        if !self.dtoc.is_empty() {
+            let dtoc = self.dtoc.clone();
+            let offset = self.data.len() - 0x1000;
+            self.write_toc(offset, b"DTOC", &dtoc)?;
+        }
+
+        let pointers = [0x0, Self::BOOT2_OFFSET, Self::ITOC_OFFSET, 0x0];
+        for (i, ptr) in pointers.into_iter().enumerate() {
+            let mut pointer = (ptr as u32).to_be_bytes().to_vec();
+            pointer.extend_from_slice(&[0x00, 0x00]);
+            let crc = calc_hwcrc(0x0000, &pointer);
+            pointer.extend_from_slice(&crc.to_be_bytes());
+            self.put(0x18 + i * 8, &pointer);
+        }
+
+        Ok(Firmware::from_bytes(self.data.clone()))
+    }
+}
+
+fn image_info() -> Vec<u8> {
+    let mut image_info = vec![0x00; 0x400];
+    image_info[0x04..0x06].copy_from_slice(&16u16.to_be_bytes());
+    image_info[0x08..0x0a].copy_from_slice(&35u16.to_be_bytes());
+    image_info[0x0a..0x0c].copy_from_slice(&2000u16.to_be_bytes());
+    image_info[0x30..0x3d].copy_from_slice(b"MT_0000000010");
+    image_info
+}
+
+fn forbidden_versions() -> Vec<u8> {
+    let mut forbidden = vec![0x00; 0x40];
+    forbidden[0x03] = 2;
+    forbidden[0x04..0x06].copy_from_slice(&16u16.to_be_bytes());
+    forbidden[0x08..0x0a].copy_from_slice(&26u16.to_be_bytes());
+    forbidden[0x0a..0x0c].copy_from_slice(&1016u16.to_be_bytes());
+    forbidden[0x0c..0x0e].copy_from_slice(&16u16.to_be_bytes());
+    forbidden[0x10..0x12].copy_from_slice(&27u16.to_be_bytes());
+    forbidden[0x12..0x14].copy_from_slice(&2000u16.to_be_bytes());
+    forbidden
+}
+
+fn dbg_fw_ini() -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+    encoder.write_all(b"[general]\nlevel=3\n")?;
+    let mut ini = encoder.finish()?;
+    ini.resize(ini.len().next_multiple_of(4) + 0x40, 0x00);
+    Ok(ini)
+}
+
+fn uids(guid: u64, mac: u64) -> Vec<u8> {
+    let mut uids = vec![];
+    for uid in [guid, mac] {
+        uids.extend_from_slice(&[0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00]);
+        uids.extend_from_slice(&uid.to_be_bytes());
+    }
+    uids
+}
+
+fn mfg_info() -> Vec<u8> {
+    let mut mfg_info = vec![0x00; 0x100];
+    mfg_info[..13].copy_from_slice(b"MT_0000000010");
+    mfg_info[0x1e] = 0x01;
+    mfg_info[0x20..0x40].copy_from_slice(&uids(0x0002c90300001234, 0x0002c9001234));
+    mfg_info
+}
+
+fn dev_info() -> Vec<u8> {
+    let mut dev_info = vec![0x00; 0x200];
+    dev_info[..0x08].copy_from_slice(b"mDevInfo");
+    dev_info[0x08..0x10].copy_from_slice(&[0x23, 0x42, 0xca, 0xfa, 0xba, 0xca, 0xfe, 0x00]);
+    dev_info[0x11] = 0x02;
+    dev_info[0x20..0x27].copy_from_slice(b"VSDTEST");
+    dev_info[0x100..0x120].copy_from_slice(&uids(0x0002c90300001234, 0x0002c9001234));
+    let crc = section_crc(&dev_info[..0x1fc]);
+    dev_info[0x1fe..].copy_from_slice(&crc.to_be_bytes());
+    dev_info
+}
+
+fn nv_data() -> Vec<u8> {
+    let mut nv_data = vec![];
+    let tlvs: [(u32, &[u8]); 3] = [
+        (0x03000080, &[0x80, 0x00, 0x00, 0x08]),
+        (0x01010012, &[0x00, 0x00, 0x00, 0x02]),
+        (0x00001234, &[0xaa, 0xbb, 0xcc, 0xdd]),
+    ];
+    for (tlv_type, data) in tlvs {
+        nv_data.extend_from_slice(&(0x10000000 | data.len() as u32).to_be_bytes());
+        nv_data.extend_from_slice(&tlv_type.to_be_bytes());
+        nv_data.extend_from_slice(&[0x00; 4]);
+        nv_data.extend_from_slice(data);
+    }
+    nv_data.resize(0x100, 0xff);
+    nv_data
+}
+
+/// The synthetic images: `image` without device data, `flash` with device data and `corrupt`,
+/// `flash` with a bit flipped in MAIN_CODE
+pub fn images() -> Result<Vec<(&'static str, Firmware)>> {
+    let code: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+    let mut builder = ImageBuilder::new(0x40000);
+    builder
+        .boot2(&[0x11111111; 8])
+        .code_section(ItocEntryType::MainCode, 0x10000, 0x100000, 0x100040, &code)
+        .section(ItocEntryType::ImageInfo, 0x20000, &image_info())
+        .section(
+            ItocEntryType::HwBootCfg,
+            0x21000,
+            &[0x01, 0x02, 0x03, 0x04].repeat(16),
+        )
+        .section(
+            ItocEntryType::ForbiddenVersions,
+            0x22000,
+            &forbidden_versions(),
+        )
+        .section(ItocEntryType::DbgFwIni, 0x23000, &dbg_fw_ini()?);
+    let image = builder.build()?;
+
+    builder
+        .device_section(ItocEntryType::MfgInfo, 0x38000, &mfg_info())
+        .device_section(ItocEntryType::DevInfo, 0x39000, &dev_info())
+        .device_section(ItocEntryType::NvData0, 0x3a000, &nv_data());
+    let flash = builder.build()?;
+
+    let mut corrupt = flash.clone();
+    corrupt[0x10005] ^= 0x55;
+
+    Ok(vec![
+        ("image", image),
+        ("flash", flash),
+        ("corrupt", corrupt),
+    ])
+}
@@ -0,0 +1,144 @@
+use clap::ValueEnum;
+use std::io::IsTerminal;
+
+/// When to color output on standard output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// When standard output is a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Good,
+    Bad,
+    Dim,
+}
+
+impl Style {
+    fn sgr(self) -> Option<&'static str> {
+        match self {
+            Style::Plain => None,
+            Style::Good => Some("32"),
+            Style::Bad => Some("1;31"),
+            Style::Dim => Some("2"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    text: String,
+    style: Style,
+}
+
+impl Cell {
+    pub fn styled(text: impl Into<String>, style: Style) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self::styled(text, Style::Plain)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Self::styled(text, Style::Plain)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Rows padded to the widest cell of each column, under a header
+pub struct Table {
+    columns: Vec<(&'static str, Align)>,
+    rows: Vec<Vec<Cell>>,
+    color: bool,
+}
+
+impl Table {
+    pub fn new(columns: &[(&'static str, Align)], color: bool) -> Self {
+        Self {
+            columns: columns.to_vec(),
+            rows: vec![],
+            color,
+        }
+    }
+
+    /// Missing cells are left empty
+    pub fn row(&mut self, cells: Vec<Cell>) {
+        self.rows.push(cells);
+    }
+
+    /// The header line followed by one line per row
+    pub fn lines(&self) -> Vec<String> {
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, (header, _))| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.text.chars().count())
+                    .fold(header.len(), usize::max)
+            })
+            .collect();
+        let header = self
+            .columns
+            .iter()
+            .map(|&(header, _)| Cell::from(header))
+            .collect();
+        std::iter::once(&header)
+            .chain(&self.rows)
+            .map(|row| self.line(row, &widths))
+            .collect()
+    }
+
+    fn line(&self, row: &[Cell], widths: &[usize]) -> String {
+        let cells: Vec<String> = self
+            .columns
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (&(_, align), &width))| {
+                let cell = row.get(i).cloned().unwrap_or_else(|| Cell::from(""));
+                let padding = " ".repeat(width.saturating_sub(cell.text.chars().count()));
+                let text = match cell.style.sgr().filter(|_| self.color) {
+                    Some(sgr) => format!("\x1b[{}m{}\x1b[0m", sgr, cell.text),
+                    None => cell.text,
+                };
+                match align {
+                    Align::Left => text + &padding,
+                    Align::Right => padding + &text,
+                }
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{FirmwareError, Result};
+use crate::firmware::{Firmware, FirmwareStructure};
+use crate::structures::itoc::{ItocEntry, ItocEntryType};
+
+/// Section type of the tag, an id no vendor section is known to use
+pub const TAG_SECTION: ItocEntryType = ItocEntryType::Unknown(0xf0);
+
+/// Metadata embedded in a patched image, so the image can be recognized when it turns up on a
+/// card later
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    /// mlx5fw version that wrote the tag
+    pub tool_version: String,
+    /// When the tag was written, as given by the caller
+    pub date: String,
+    pub note: String,
+    /// SHA-256 of the image as it was before tagging
+    pub original_sha256: String,
+}
+
+impl Tag {
+    pub fn new(firmware: &Firmware, date: impl Into<String>, note: impl Into<String>) -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            date: date.into(),
+            note: note.into(),
+            original_sha256: hex::encode(Sha256::digest(firmware.data())),
+        }
+    }
+
+    /// The section content: compact JSON, padded with spaces to whole dwords
+    fn content(&self) -> Result<Vec<u8>> {
+        let mut content = serde_json::to_vec(self)?;
+        content.resize(content.len().next_multiple_of(4), b' ');
+        Ok(content)
+    }
+}
+
+/// The tag of an image, `None` if it has none
+pub fn read(firmware: &Firmware) -> Result<Option<Tag>> {
+    let entry = match firmware.itoc_entry(TAG_SECTION) {
+        Ok(entry) => entry,
+        Err(FirmwareError::MissingSection { .. }) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let content = entry.content().read_bytes(firmware);
+    Ok(Some(serde_json::from_slice(content.trim_ascii_end())?))
+}
+
+/// First sector-aligned range of `size` erased or filled bytes before the device data that no
+/// structure of the image claims
+fn erased_space(firmware: &Firmware, size: usize) -> Option<usize> {
+    let image = firmware.parse();
+    let end = firmware.device_data_offset().unwrap_or(firmware.len());
+    (0..end.saturating_sub(size))
+        .step_by(firmware.layout().sector_size)
+        .find(|&offset| {
+            image.overlap(offset, size).is_none()
+                && firmware[offset..offset + size]
+                    .iter()
+                    .all(|&byte| byte == 0xff || byte == firmware.fill_byte())
+        })
+}
+
+/// Embed `tag` as a section in erased space, replacing an existing tag
+pub fn write(firmware: &mut Firmware, tag: &Tag) -> Result<FirmwareStructure<ItocEntry>> {
+    let content = tag.content()?;
+    let mut entries: Vec<ItocEntry> = vec![];
+    for entry in firmware.itoc()? {
+        if entry.entry_type == TAG_SECTION {
+            firmware.fill(entry.flash_addr, entry.size)?;
+        } else {
+            entries.push(entry.1);
+        }
+    }
+    // The old tag must not be counted as taken while looking for space
+    firmware.write_itoc(&entries)?;
+
+    let flash_addr = erased_space(firmware, content.len()).ok_or(FirmwareError::NoSpace {
+        what: "the tag".to_string(),
+        size: content.len(),
+    })?;
+    entries.push(ItocEntry::with_content(TAG_SECTION, flash_addr, &content)?);
+    firmware.write_itoc(&entries)?;
+    FirmwareStructure(flash_addr, ()).write_bytes(firmware, &content)?;
+    firmware.itoc_entry(TAG_SECTION)
+}
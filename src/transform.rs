@@ -0,0 +1,34 @@
+use std::fmt;
+
+/// How a programmer scrambled the bytes of a flash dump. Both transforms are their own inverse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum InputTransform {
+    /// Bytes of every 32-bit word in reverse order
+    Swap32,
+    /// Bits of every byte in reverse order
+    ReverseBits,
+}
+
+impl InputTransform {
+    pub const ALL: [InputTransform; 2] = [InputTransform::Swap32, InputTransform::ReverseBits];
+
+    /// Apply the transform in place. Swapping leaves a trailing partial word as it is.
+    pub fn apply(self, data: &mut [u8]) {
+        match self {
+            InputTransform::Swap32 => data.chunks_exact_mut(4).for_each(|word| word.reverse()),
+            InputTransform::ReverseBits => {
+                data.iter_mut().for_each(|byte| *byte = byte.reverse_bits())
+            }
+        }
+    }
+}
+
+impl fmt::Display for InputTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            InputTransform::Swap32 => "word-swapped",
+            InputTransform::ReverseBits => "bit-reversed",
+        })
+    }
+}
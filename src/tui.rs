@@ -0,0 +1,233 @@
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use mlx5fw::firmware::Firmware;
+use mlx5fw::structures::{
+    crdump::CrDumpMask,
+    devinfo::{DevInfo, MfgInfo},
+    forbidden::ForbiddenVersions,
+    imageinfo::ImageInfo,
+    itoc::{ItocEntry, ItocEntryType},
+    programmable::ProgrammableHwFw,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Hexdump,
+    Decoded,
+}
+
+struct Section {
+    toc: &'static str,
+    entry: ItocEntry,
+    entry_crc_ok: bool,
+    /// `None` for sections without a CRC
+    section_crc_ok: Option<bool>,
+}
+
+struct Explorer<'a> {
+    firmware: &'a Firmware,
+    sections: Vec<Section>,
+    list: ListState,
+    view: View,
+    scroll: u16,
+}
+
+impl<'a> Explorer<'a> {
+    fn new(firmware: &'a Firmware) -> Self {
+        let image = firmware.parse();
+        let itoc = image.itoc.iter().map(|entry| ("ITOC", entry));
+        let dtoc = image.dtoc.iter().flatten().map(|entry| ("DTOC", entry));
+        let sections = itoc
+            .chain(dtoc)
+            .map(|(toc, entry)| Section {
+                toc,
+                entry: entry.1.clone(),
+                entry_crc_ok: entry.itoc_entry_crc_valid(),
+                section_crc_ok: firmware
+                    .bytes(entry.flash_addr, entry.size)
+                    .ok()
+                    .and_then(|_| entry.stored_section_crc(firmware))
+                    .map(|crc| crc == entry.calc_section_crc(firmware)),
+            })
+            .collect();
+        Self {
+            firmware,
+            sections,
+            list: ListState::default().with_selected(Some(0)),
+            view: View::Hexdump,
+            scroll: 0,
+        }
+    }
+
+    fn selected(&self) -> Option<&Section> {
+        self.sections.get(self.list.selected()?)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.list.select_next();
+                    self.scroll = 0;
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.list.select_previous();
+                    self.scroll = 0;
+                }
+                KeyCode::PageDown | KeyCode::Char(' ') => {
+                    self.scroll = self.scroll.saturating_add(16)
+                }
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(16),
+                KeyCode::Tab => {
+                    self.view = match self.view {
+                        View::Hexdump => View::Decoded,
+                        View::Decoded => View::Hexdump,
+                    };
+                    self.scroll = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Min(0)]).areas(frame.area());
+        let [info, content] =
+            Layout::vertical([Constraint::Length(9), Constraint::Min(0)]).areas(right);
+
+        let items: Vec<ListItem> = self
+            .sections
+            .iter()
+            .map(|section| {
+                let status = if !section.entry_crc_ok || section.section_crc_ok == Some(false) {
+                    '!'
+                } else {
+                    ' '
+                };
+                ListItem::new(format!(
+                    "{}{} {:#010x} {}",
+                    status, section.toc, section.entry.flash_addr, section.entry.entry_type
+                ))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" Sections "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, left, &mut self.list);
+
+        let Some(section) = self.selected() else {
+            return;
+        };
+        frame.render_widget(
+            Paragraph::new(Self::info_lines(section))
+                .block(Block::bordered().title(format!(" {} ", section.entry.entry_type))),
+            info,
+        );
+
+        let paragraph = match self.view {
+            // Only the visible lines, sections can be megabytes
+            View::Hexdump => Paragraph::new(self.hexdump(&section.entry, content.height as usize))
+                .block(Block::bordered().title(" Hexdump (Tab: decoded) ")),
+            View::Decoded => Paragraph::new(self.decoded(&section.entry))
+                .block(Block::bordered().title(" Decoded (Tab: hexdump) "))
+                .scroll((self.scroll, 0)),
+        };
+        frame.render_widget(paragraph, content);
+    }
+
+    fn info_lines(section: &Section) -> Vec<Line<'static>> {
+        let entry = &section.entry;
+        let crc_status = |ok: bool| if ok { "ok" } else { "BAD" };
+        vec![
+            Line::from(format!(
+                "Flash:        {:#010x}/{:#010x}",
+                entry.flash_addr, entry.size
+            )),
+            Line::from(format!(
+                "Load address: {:#010x}  entry point {:#010x}",
+                entry.load_address, entry.entry_point
+            )),
+            Line::from(format!(
+                "Flags:        zipped {}, cache-line CRC {}, encrypted {}",
+                entry.zipped_image, entry.cache_line_crc, entry.encrypted_section
+            )),
+            Line::from(format!("CRC mode:     {:?}", entry.crc_mode())),
+            Line::from(format!(
+                "Section CRC:  {}",
+                section.section_crc_ok.map_or("none", crc_status)
+            )),
+            Line::from(format!(
+                "Entry CRC:    {}",
+                crc_status(section.entry_crc_ok)
+            )),
+            Line::from(format!("Version:      {:#06x}", entry.version)),
+        ]
+    }
+
+    fn hexdump(&self, entry: &ItocEntry, height: usize) -> Vec<Line<'static>> {
+        let Ok(content) = self.firmware.bytes(entry.flash_addr, entry.size) else {
+            return vec![Line::from("Section lies outside the image")];
+        };
+        content
+            .chunks(16)
+            .enumerate()
+            .skip(self.scroll as usize)
+            .take(height)
+            .map(|(i, line)| Line::from(crate::hexdump_line(entry.flash_addr + i * 16, line)))
+            .collect()
+    }
+
+    fn decoded(&self, entry: &ItocEntry) -> Vec<Line<'static>> {
+        let firmware = self.firmware;
+        let decoded = match entry.entry_type {
+            ItocEntryType::ImageInfo => firmware
+                .read_section::<ImageInfo>(entry)
+                .map(|section| format!("{:#?}", section.1)),
+            ItocEntryType::ForbiddenVersions => firmware
+                .read_section::<ForbiddenVersions>(entry)
+                .map(|section| format!("{:#?}", section.1)),
+            ItocEntryType::ProgrammableHwFw => firmware
+                .read_section::<ProgrammableHwFw>(entry)
+                .map(|section| format!("{:#?}", section.1)),
+            ItocEntryType::CrDumpMaskData => firmware
+                .read_section::<CrDumpMask>(entry)
+                .map(|section| format!("{:#?}", section.1)),
+            ItocEntryType::DevInfo => firmware
+                .read_section::<DevInfo>(entry)
+                .map(|section| format!("{:#?}", section.1)),
+            ItocEntryType::MfgInfo => firmware
+                .read_section::<MfgInfo>(entry)
+                .map(|section| format!("{:#?}", section.1)),
+            ItocEntryType::DbgFwIni => firmware.dbg_fw_ini(),
+            _ => Ok(format!("No decoder for {}", entry.entry_type)),
+        };
+        decoded
+            .unwrap_or_else(|err| format!("Could not decode: {}", err))
+            .lines()
+            .map(|line| Line::from(line.to_string()))
+            .collect()
+    }
+}
+
+/// Browse the sections of `firmware` until the user quits
+pub fn run(firmware: &Firmware) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = Explorer::new(firmware).run(&mut terminal);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,551 @@
+use rayon::prelude::*;
+
+use crate::error::Result;
+use crate::firmware::{Firmware, FirmwareStructure};
+use crate::image::Image;
+use crate::progress::{NoProgress, Progress};
+use crate::structures::{
+    devinfo::DevInfo,
+    hashes::HashesTable,
+    hwpointers::HwPointers,
+    itoc::{ItocEntry, ItocEntryType, TocHeader},
+    programmable::ProgrammableHwFw,
+    version::FwVersion,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Profile {
+    /// Every check, every failure is fatal
+    Strict,
+    /// Only what the hardware validates while booting
+    BootRom,
+    /// The checks `mstflint verify` performs
+    Mstflint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Structure,
+    HwPointerCrc,
+    Boot2Crc,
+    ItocEntryCrc,
+    TocHeaderCrc,
+    /// The secondary ITOC differs from the primary one
+    SecondaryToc,
+    SectionCrc,
+    CacheLineCrc,
+    DtocEntryCrc,
+    DevInfoCrc,
+    PldCrc,
+    SectionHash,
+    /// A section version differs from the FW version, or IMAGE_INFO from the expected version
+    SectionVersion,
+    /// A section does not start or end on a dword boundary, or code is not cache line aligned
+    Alignment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Fatal,
+}
+
+impl Profile {
+    pub fn severity(&self, check: Check) -> Option<Severity> {
+        match (self, check) {
+            (Profile::Strict, Check::SectionVersion) => Some(Severity::Warning),
+            (_, Check::SectionVersion) => None,
+            (Profile::Strict, _) | (_, Check::Structure) => Some(Severity::Fatal),
+            (Profile::BootRom, Check::HwPointerCrc)
+            | (Profile::BootRom, Check::Boot2Crc)
+            | (Profile::BootRom, Check::ItocEntryCrc)
+            | (Profile::BootRom, Check::CacheLineCrc)
+            | (Profile::BootRom, Check::SectionHash) => Some(Severity::Fatal),
+            (Profile::BootRom, _) => None,
+            (Profile::Mstflint, Check::CacheLineCrc | Check::SecondaryToc | Check::Alignment) => {
+                Some(Severity::Warning)
+            }
+            (Profile::Mstflint, _) => Some(Severity::Fatal),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub check: Check,
+    pub severity: Severity,
+    pub offset: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "WARN",
+            Severity::Fatal => "FAIL",
+        };
+        write!(f, "{} {:#010x}: {}", severity, self.offset, self.message)
+    }
+}
+
+struct Verifier<'a> {
+    firmware: &'a Firmware,
+    profile: Profile,
+    findings: Vec<Finding>,
+    progress: &'a dyn Progress,
+    expected_version: Option<FwVersion>,
+}
+
+impl Verifier<'_> {
+    fn check(&mut self, check: Check, offset: usize, stored: u16, calculated: u16, what: String) {
+        if stored == calculated {
+            return;
+        }
+        if let Some(severity) = self.profile.severity(check) {
+            self.findings.push(Finding {
+                check,
+                severity,
+                offset,
+                message: format!(
+                    "{} CRC mismatch: stored {:#06x}, calculated {:#06x}",
+                    what, stored, calculated
+                ),
+            });
+        }
+    }
+
+    fn enabled(&self, check: Check) -> bool {
+        self.profile.severity(check).is_some()
+    }
+
+    fn verify_hwpointers(&mut self, hwpointers: &FirmwareStructure<HwPointers>) {
+        for (i, (name, pointer)) in hwpointers.pointers().into_iter().enumerate() {
+            if pointer.is_erased() {
+                continue;
+            }
+            self.check(
+                Check::HwPointerCrc,
+                hwpointers.0 + i * 8,
+                pointer.crc,
+                pointer.calc_crc(),
+                format!("HW pointer {}", name),
+            );
+        }
+    }
+
+    /// Checks of one section only, so sections can be checked in parallel
+    fn section(&self) -> Self {
+        Self {
+            firmware: self.firmware,
+            profile: self.profile,
+            findings: vec![],
+            progress: self.progress,
+            expected_version: self.expected_version,
+        }
+    }
+
+    fn verify_toc(&mut self, name: &str, toc: &[FirmwareStructure<ItocEntry>], entry_check: Check) {
+        let findings: Vec<Vec<Finding>> = toc
+            .par_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let mut verifier = self.section();
+                verifier.verify_section(name, i, entry, toc, entry_check);
+                self.progress.advance(entry.size);
+                verifier.findings
+            })
+            .collect();
+        self.findings.extend(findings.into_iter().flatten());
+    }
+
+    fn verify_toc_header(&mut self, name: &[u8; 4], header: &FirmwareStructure<TocHeader>) {
+        let what = String::from_utf8_lossy(name);
+        if !header.is_valid(name) {
+            self.structure_error(header.0, format!("{} header signature is invalid", what));
+        }
+        self.check(
+            Check::TocHeaderCrc,
+            header.0 + 0x1e,
+            header.crc,
+            header.calc_crc(),
+            format!("{} header", what),
+        );
+    }
+
+    /// The secondary ITOC must be an exact copy of the primary one
+    fn verify_secondary_toc(
+        &mut self,
+        primary: &[FirmwareStructure<ItocEntry>],
+        secondary: &[FirmwareStructure<ItocEntry>],
+    ) {
+        let Some(severity) = self.profile.severity(Check::SecondaryToc) else {
+            return;
+        };
+        let mut differences: Vec<(usize, String)> = primary
+            .iter()
+            .zip(secondary)
+            .enumerate()
+            .filter(|(_, (primary, secondary))| primary.1 != secondary.1)
+            .map(|(i, (primary, secondary))| {
+                (
+                    secondary.0,
+                    format!(
+                        "Secondary ITOC entry {} ({}) differs from the primary ({})",
+                        i, secondary.entry_type, primary.entry_type
+                    ),
+                )
+            })
+            .collect();
+        if primary.len() != secondary.len() {
+            let offset = secondary.last().map_or(0, |entry| entry.0 + 0x20);
+            differences.push((
+                offset,
+                format!(
+                    "Secondary ITOC has {} entries, the primary {}",
+                    secondary.len(),
+                    primary.len()
+                ),
+            ));
+        }
+        self.findings
+            .extend(differences.into_iter().map(|(offset, message)| Finding {
+                check: Check::SecondaryToc,
+                severity,
+                offset,
+                message,
+            }));
+    }
+
+    fn verify_section(
+        &mut self,
+        name: &str,
+        i: usize,
+        entry: &FirmwareStructure<ItocEntry>,
+        toc: &[FirmwareStructure<ItocEntry>],
+        entry_check: Check,
+    ) {
+        self.check(
+            entry_check,
+            entry.0,
+            entry.itoc_entry_crc,
+            entry.calc_itoc_entry_crc(),
+            format!("{} entry {} ({})", name, i, entry.entry_type),
+        );
+        if entry
+            .flash_addr
+            .checked_add(entry.size)
+            .is_none_or(|end| end > self.firmware.len())
+        {
+            self.structure_error(
+                entry.0,
+                format!(
+                    "{} section {} ({}) lies outside the image",
+                    name, i, entry.entry_type
+                ),
+            );
+            return;
+        }
+        if let Some(severity) = self.profile.severity(Check::Alignment) {
+            let geometry = self.firmware.cache_line_geometry();
+            for misalignment in entry.misalignments(geometry) {
+                self.findings.push(Finding {
+                    check: Check::Alignment,
+                    severity,
+                    offset: entry.0,
+                    message: format!(
+                        "{} section {} ({}): {}",
+                        name, i, entry.entry_type, misalignment
+                    ),
+                });
+            }
+        }
+        if let Some(stored) = entry.stored_section_crc(self.firmware) {
+            if self.enabled(Check::SectionCrc) {
+                self.check(
+                    Check::SectionCrc,
+                    entry.flash_addr,
+                    stored,
+                    entry.calc_section_crc(self.firmware),
+                    format!("{} section {} ({})", name, i, entry.entry_type),
+                );
+            }
+        }
+        if entry.cache_line_crc && self.enabled(Check::CacheLineCrc) {
+            self.verify_cache_lines(i, entry);
+        }
+        if entry.entry_type == ItocEntryType::ProgrammableHwFw && self.enabled(Check::PldCrc) {
+            self.verify_programmable_hw_fw(entry);
+        }
+        if entry.entry_type == ItocEntryType::HashesTable && self.enabled(Check::SectionHash) {
+            self.verify_hashes_table(entry, toc);
+        }
+    }
+
+    fn verify_cache_lines(&mut self, index: usize, entry: &ItocEntry) {
+        let geometry = self.firmware.cache_line_geometry();
+        let content = entry.content().read_bytes(self.firmware);
+        for (i, line) in geometry.lines(content).enumerate() {
+            self.check(
+                Check::CacheLineCrc,
+                entry.flash_addr + line.offset,
+                line.crc,
+                line.calc_crc(),
+                format!("ITOC section {} cache line {}", index, i),
+            );
+        }
+    }
+
+    fn verify_programmable_hw_fw(&mut self, entry: &ItocEntry) {
+        let programmable = match self.firmware.read_section::<ProgrammableHwFw>(entry) {
+            Ok(programmable) => programmable,
+            Err(err) => {
+                return self.structure_error(
+                    entry.flash_addr,
+                    format!("Could not parse PROGRAMMABLE_HW_FW: {}", err),
+                )
+            }
+        };
+        let section = entry.content().read_bytes(self.firmware);
+        for (i, image) in programmable.images.iter().enumerate() {
+            match image.calc_crc(section) {
+                Some(crc) => self.check(
+                    Check::PldCrc,
+                    entry.flash_addr + image.offset,
+                    image.crc,
+                    crc,
+                    format!("PLD image {} ({:#010x})", i, image.pld_id),
+                ),
+                None => self.structure_error(
+                    entry.flash_addr,
+                    format!("PLD image {} lies outside PROGRAMMABLE_HW_FW", i),
+                ),
+            }
+        }
+    }
+
+    fn verify_hashes_table(&mut self, entry: &ItocEntry, toc: &[FirmwareStructure<ItocEntry>]) {
+        let table = match self.firmware.read_section::<HashesTable>(entry) {
+            Ok(table) => table,
+            Err(err) => {
+                return self.structure_error(
+                    entry.flash_addr,
+                    format!("Could not parse HASHES_TABLE: {}", err),
+                )
+            }
+        };
+        let hashes = entry.content().read_bytes(self.firmware);
+        for hash_entry in &table.entries {
+            let Some(stored) = hash_entry.hash(&table, hashes) else {
+                self.structure_error(
+                    entry.flash_addr,
+                    format!(
+                        "{} digest lies outside HASHES_TABLE",
+                        hash_entry.section_type
+                    ),
+                );
+                continue;
+            };
+            let Some(section) = toc
+                .iter()
+                .find(|section| section.entry_type == hash_entry.section_type)
+                .and_then(|section| self.firmware.bytes(section.flash_addr, section.size).ok())
+            else {
+                self.structure_error(
+                    entry.flash_addr,
+                    format!(
+                        "HASHES_TABLE lists missing section {}",
+                        hash_entry.section_type
+                    ),
+                );
+                continue;
+            };
+            let Some(calculated) = table.calc_hash(section) else {
+                return self.structure_error(
+                    entry.flash_addr,
+                    format!("Unsupported HASHES_TABLE digest size {}", table.hash_size),
+                );
+            };
+            if stored != calculated.as_slice() {
+                if let Some(severity) = self.profile.severity(Check::SectionHash) {
+                    self.findings.push(Finding {
+                        check: Check::SectionHash,
+                        severity,
+                        offset: entry.flash_addr + hash_entry.hash_offset,
+                        message: format!(
+                            "{} digest mismatch: stored {}, calculated {}",
+                            hash_entry.section_type,
+                            hex::encode(stored),
+                            hex::encode(&calculated)
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    /// An explicitly expected version must match IMAGE_INFO, in every profile. Sections are
+    /// also compared by a heuristic with no cited source: the ITOC entry `version` seems to
+    /// hold the build (subminor) number of the release, so one from another release would give
+    /// away an image assembled from several. Sections with version 0 are taken as unversioned.
+    /// Section mismatches are never more than warnings.
+    fn verify_section_versions(&mut self, itoc: &[FirmwareStructure<ItocEntry>]) {
+        let image_info = self.firmware.image_info().ok();
+        if let (Some(expected), Some(image_info)) = (self.expected_version, &image_info) {
+            if image_info.fw_version != expected {
+                self.findings.push(Finding {
+                    check: Check::SectionVersion,
+                    severity: Severity::Fatal,
+                    offset: image_info.0 + 4,
+                    message: format!(
+                        "IMAGE_INFO has FW version {}, expected {}",
+                        image_info.fw_version, expected
+                    ),
+                });
+            }
+        }
+        if self.expected_version.is_none() && !self.enabled(Check::SectionVersion) {
+            return;
+        }
+        let Some(version) = self
+            .expected_version
+            .or(image_info.map(|image_info| image_info.fw_version))
+        else {
+            return;
+        };
+        self.findings.extend(
+            itoc.iter()
+                .enumerate()
+                .filter(|(_, entry)| entry.version != 0 && entry.version != version.subminor)
+                .map(|(i, entry)| Finding {
+                    check: Check::SectionVersion,
+                    severity: Severity::Warning,
+                    offset: entry.0 + 0x12,
+                    message: format!(
+                        "ITOC entry {} ({}) has section version {}, FW {} has {}",
+                        i, entry.entry_type, entry.version, version, version.subminor
+                    ),
+                }),
+        );
+    }
+
+    fn verify_dev_info(&mut self, dtoc: &[FirmwareStructure<ItocEntry>]) {
+        let Some(entry) = dtoc
+            .iter()
+            .find(|entry| entry.entry_type == ItocEntryType::DevInfo)
+        else {
+            return;
+        };
+        match self.firmware.read_section::<DevInfo>(entry) {
+            Ok(dev_info) => self.check(
+                Check::DevInfoCrc,
+                dev_info.0,
+                dev_info.crc,
+                dev_info.calc_crc(),
+                "DEV_INFO".to_string(),
+            ),
+            Err(err) => self.structure_error(
+                entry.flash_addr,
+                format!("Could not parse DEV_INFO: {}", err),
+            ),
+        }
+    }
+
+    fn structure_error(&mut self, offset: usize, message: String) {
+        self.findings.push(Finding {
+            check: Check::Structure,
+            severity: Severity::Fatal,
+            offset,
+            message,
+        });
+    }
+}
+
+pub fn validate(image: &Image, profile: Profile) -> Vec<Finding> {
+    validate_with_progress(image, profile, None, &NoProgress)
+}
+
+/// Validate, reporting the verified section bytes to `progress`. With `expected_version`, the
+/// image must be of that release, and sections are compared to it instead of to the one
+/// IMAGE_INFO names.
+pub fn validate_with_progress(
+    image: &Image,
+    profile: Profile,
+    expected_version: Option<FwVersion>,
+    progress: &dyn Progress,
+) -> Vec<Finding> {
+    let mut verifier = Verifier {
+        firmware: image.firmware,
+        profile,
+        findings: vec![],
+        progress,
+        expected_version,
+    };
+    let dtoc = image
+        .dtoc
+        .iter()
+        .flatten()
+        .filter(|_| verifier.enabled(Check::DtocEntryCrc));
+    progress.set_total(image.itoc.iter().chain(dtoc).map(|entry| entry.size).sum());
+
+    for error in &image.errors {
+        verifier.structure_error(error.offset, error.message.clone());
+    }
+
+    if let Some(hwpointers) = &image.hwpointers {
+        if verifier.enabled(Check::HwPointerCrc) {
+            verifier.verify_hwpointers(hwpointers);
+        }
+    }
+
+    if let Some(boot2) = &image.boot2 {
+        // Parsed boot2 always holds `size` dwords, so its CRC can be calculated
+        if let Ok(crc) = boot2.calc_crc() {
+            verifier.check(
+                Check::Boot2Crc,
+                boot2.0 + boot2.crc_offset(),
+                boot2.crc,
+                crc,
+                "boot2".to_string(),
+            );
+        }
+    }
+
+    if let Some(header) = &image.itoc_header {
+        verifier.verify_toc_header(b"ITOC", header);
+    }
+    verifier.verify_toc("ITOC", &image.itoc, Check::ItocEntryCrc);
+    verifier.verify_section_versions(&image.itoc);
+    if let Some(secondary) = &image.secondary_itoc {
+        verifier.verify_secondary_toc(&image.itoc, secondary);
+    }
+
+    if let Some(dtoc) = &image.dtoc {
+        if verifier.enabled(Check::DtocEntryCrc) {
+            verifier.verify_toc("DTOC", dtoc, Check::DtocEntryCrc);
+            if verifier.enabled(Check::DevInfoCrc) {
+                verifier.verify_dev_info(dtoc);
+            }
+        }
+    }
+
+    verifier.findings
+}
+
+pub fn verify(firmware: &Firmware, profile: Profile) -> Result<Vec<Finding>> {
+    Ok(firmware.parse().validate(profile))
+}
+
+pub fn verify_with_progress(
+    firmware: &Firmware,
+    profile: Profile,
+    expected_version: Option<FwVersion>,
+    progress: &dyn Progress,
+) -> Result<Vec<Finding>> {
+    Ok(validate_with_progress(
+        &firmware.parse(),
+        profile,
+        expected_version,
+        progress,
+    ))
+}
@@ -0,0 +1,926 @@
+//! Every command run against the synthetic images of `mlx5fw::synthetic`: `image` without
+//! device data, `flash` with device data and `corrupt` with a bit flipped in MAIN_CODE.
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().expect("temporary directory");
+        for (name, image) in mlx5fw::synthetic::images().expect("synthetic images") {
+            image
+                .write(dir.path().join(format!("{}.bin", name)))
+                .expect("write synthetic image");
+        }
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn read(&self, name: &str) -> Vec<u8> {
+        std::fs::read(self.path(name)).expect("read output")
+    }
+
+    /// mlx5fw on `image`, run in the fixture directory so outputs can be given as file names
+    fn mlx5fw(&self, image: &str) -> Command {
+        let mut command = cargo_bin_cmd!("mlx5fw");
+        command.current_dir(self.dir.path()).arg(image);
+        command
+    }
+
+    fn verifies(&self, image: &str) {
+        self.mlx5fw(image)
+            .arg("verify")
+            .assert()
+            .success()
+            .stdout("OK\n");
+    }
+}
+
+#[test]
+fn show_commands() {
+    let fixture = Fixture::new();
+    let commands: &[(&[&str], &str)] = &[
+        (&["show-sections"], "MAIN_CODE"),
+        (&["show-sections"], "ITOC at 0x00005000, DTOC at 0x0003f000"),
+        (&["show-sections", "--verbose"], "itoc_entry_crc"),
+        (&["query"], "FW Version:            16.35.2000"),
+        (&["graph"], "digraph image"),
+        (
+            &["show-code-map"],
+            "0x00100000-0x001001ff entry 0x00100040: MAIN_CODE",
+        ),
+        (&["whatis", "0x10010"], "loaded at 0x00100010"),
+        (&["whatis", "0x18000"], "gap 0x00010220-0x0001ffff"),
+        (&["whatis", "--va", "0x100045"], "flash offset 0x00010049"),
+        (&["inspect", "0x5000", "0x20"], "ITOC header"),
+        (&["info", "--section", "1"], "FW version:   16.35.2000"),
+        (&["info", "--section", "0"], "< ITOC section 0 (MAIN_CODE)"),
+        (&["info", "--dtoc", "--section", "2"], "NUM_OF_VFS"),
+        (&["security"], "Secure boot:   no"),
+        (&["coverage"], "header CRC"),
+        (&["show-nv"], "NUM_OF_VFS"),
+        (&["show-forbidden"], "16.26.1016\n16.27.2000\n"),
+        (&["show-dev-info"], "0x0002c90300001234"),
+        (&["hashes"], "section 0 MAIN_CODE"),
+        (&["strings"], "MAIN_CODE"),
+        (&["dump-boot2", "--info"], "(ok)"),
+        (&["compare-release", "image.bin"], "Pristine"),
+        (&["changelog", "image.bin"], "\"unchanged\""),
+    ];
+    for (args, expected) in commands {
+        fixture
+            .mlx5fw("flash.bin")
+            .args(*args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(*expected));
+    }
+    fixture
+        .mlx5fw("flash.bin")
+        .arg("lint")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn image_from_stdin() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("-")
+        .arg("show-sections")
+        .pipe_stdin(fixture.path("flash.bin"))
+        .expect("stdin")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("PSID MT_0000000010"));
+}
+
+#[test]
+fn verify_profiles() {
+    let fixture = Fixture::new();
+    for image in ["image.bin", "flash.bin"] {
+        fixture.verifies(image);
+    }
+    for profile in ["strict", "boot-rom"] {
+        fixture
+            .mlx5fw("corrupt.bin")
+            .args(["verify", "--profile", profile])
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains("cache line"));
+    }
+    fixture
+        .mlx5fw("corrupt.bin")
+        .args(["verify", "--profile", "mstflint"])
+        .assert()
+        .stdout(predicate::str::contains("WARN"));
+
+    // MAIN_CODE claims to be from build 2001 of FW 16.35.2000
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["hexpatch", "--at", "0x5032", "--bytes", "07d1", "mixed.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("mixed.bin")
+        .args(["fix-crc", "mixed.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("mixed.bin")
+        .arg("verify")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("has section version 2001"));
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["verify", "--expected-version", "16.35.2000"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("mixed.bin")
+        .args([
+            "verify",
+            "--profile",
+            "boot-rom",
+            "--expected-version",
+            "16.35.2000",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("WARN 0x00005032"));
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["verify", "--expected-version", "16.35.2001"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "FAIL 0x00020004: IMAGE_INFO has FW version 16.35.2000",
+        ));
+}
+
+#[test]
+fn missing_sections() {
+    let fixture = Fixture::new();
+    let commands: &[(&[&str], &str)] = &[
+        (&["show-crdump-mask"], "No CRDUMP_MASK_DATA section"),
+        (&["show-nv-log"], "No FW_NV_LOG section"),
+        (&["show-vpd"], "No VPD_R0 section"),
+        (&["set-vpd", "SN", "1234", "out.bin"], "No VPD_R0 section"),
+        (&["split-phy", "phy"], "No PHY microcode sections"),
+        (&["dumpphy", "phy"], "No PHY microcode sections"),
+    ];
+    for (args, expected) in commands {
+        fixture
+            .mlx5fw("flash.bin")
+            .args(*args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(*expected));
+    }
+    fixture
+        .mlx5fw("image.bin")
+        .arg("show-dev-info")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn truncated_nv_tlv() {
+    let fixture = Fixture::new();
+    // First TLV of NV_DATA0 claiming more data than the section holds
+    let mut image = fixture.read("flash.bin");
+    image[0x3a000..0x3a004].copy_from_slice(&0x10000ff0u32.to_be_bytes());
+    std::fs::write(fixture.path("bad.bin"), image).unwrap();
+
+    fixture
+        .mlx5fw("bad.bin")
+        .arg("show-nv")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "TLV at 0x3a000 extends past the end of the NV_DATA0 section",
+        ));
+}
+
+#[test]
+fn out_of_range_arguments() {
+    let fixture = Fixture::new();
+    let commands: &[(&[&str], &str)] = &[
+        (
+            &[
+                "hexpatch",
+                "--at",
+                "0xffffffffffffffff",
+                "--bytes",
+                "00",
+                "out.bin",
+            ],
+            "overflows",
+        ),
+        (
+            &["--cache-line-size", "0", "fix-crc", "out.bin"],
+            "at least one",
+        ),
+    ];
+    for (args, expected) in commands {
+        fixture
+            .mlx5fw("flash.bin")
+            .args(*args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(*expected));
+    }
+}
+
+#[test]
+fn modifications_keep_images_valid() {
+    let fixture = Fixture::new();
+    let commands: &[&[&str]] = &[
+        &["patch", "--strip-forbidden"],
+        &["sanitize"],
+        &["set-guids", "0x0002c90300005678"],
+        &["set-macs", "0x0002c9005678"],
+        &["remove-section", "3"],
+        &["fix-hwpointers"],
+        &["fix-crc"],
+        &["trim"],
+        &["trim", "--strip-device-data"],
+        &["expand", "--size", "512K"],
+        &["hexpatch", "--at", "0x20010", "--bytes", "dead"],
+    ];
+    for args in commands {
+        fixture
+            .mlx5fw("flash.bin")
+            .args(*args)
+            .arg("out.bin")
+            .assert()
+            .success();
+        fixture.verifies("out.bin");
+    }
+}
+
+#[test]
+fn modifications_take_effect() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["trim", "--strip-device-data", "out.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("out.bin")
+        .arg("show-sections")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("FW 16.35.2000"))
+        .stdout(predicate::str::contains("no DTOC"));
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["patch", "--strip-forbidden", "out.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("out.bin")
+        .arg("show-forbidden")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("16.26.1016").not());
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["set-guids", "0x0002c90300005678", "out.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("out.bin")
+        .arg("show-dev-info")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0x0002c90300005678"));
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["remove-section", "3", "out.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("out.bin")
+        .arg("show-sections")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("FORBIDDEN_VERSIONS").not());
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["--fill", "0x00", "remove-section", "3", "out.bin"])
+        .assert()
+        .success();
+    assert!(fixture.read("out.bin")[0x22000..0x22040]
+        .iter()
+        .all(|&byte| byte == 0x00));
+    fixture
+        .mlx5fw("image.bin")
+        .args(["--fill", "0", "expand", "--size", "512K", "out.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("out.bin").last(), Some(&0x00));
+    fixture
+        .mlx5fw("image.bin")
+        .args(["--fill", "0x100", "expand", "--size", "512K", "out.bin"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn fix_crc_repairs_corruption() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("corrupt.bin")
+        .args(["fix-crc", "fixed.bin"])
+        .assert()
+        .success();
+    fixture.verifies("fixed.bin");
+}
+
+#[test]
+fn lint_blocks_writes() {
+    let fixture = Fixture::new();
+    // Entry point of MAIN_CODE outside all code sections
+    let mut image = fixture.read("flash.bin");
+    image[0x5020 + 0x08..0x5020 + 0x0c].copy_from_slice(&0x00200000u32.to_be_bytes());
+    std::fs::write(fixture.path("bad.bin"), image).unwrap();
+
+    fixture
+        .mlx5fw("bad.bin")
+        .arg("lint")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("outside all code sections"));
+    fixture
+        .mlx5fw("bad.bin")
+        .args(["fix-crc", "out.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failing lint"));
+    cargo_bin_cmd!("mlx5fw")
+        .current_dir(fixture.dir.path())
+        .args(["--force", "bad.bin", "fix-crc", "out.bin"])
+        .assert()
+        .success();
+
+    // DBG_FW_INI, ITOC entry 4, one byte short of a dword multiple
+    let mut image = fixture.read("flash.bin");
+    image[0x50a0 + 0x03] = 0x5b;
+    std::fs::write(fixture.path("misaligned.bin"), image).unwrap();
+    fixture
+        .mlx5fw("misaligned.bin")
+        .arg("lint")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "DBG_FW_INI: size 0x5b is not a dword multiple",
+        ));
+    fixture
+        .mlx5fw("misaligned.bin")
+        .args(["verify", "--profile", "mstflint"])
+        .assert()
+        .stdout(predicate::str::contains("WARN 0x000050a0"));
+}
+
+#[test]
+fn dump_and_pack_round_trip() {
+    let fixture = Fixture::new();
+    let flash = fixture.read("flash.bin");
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["dump-sections", "sections"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["pack-sections", "sections/manifest.json", "out.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("out.bin"), flash);
+
+    // A section size that is not a dword multiple is rounded up again, with new CRCs
+    let mut manifest: serde_json::Value =
+        serde_json::from_slice(&fixture.read("sections/manifest.json")).expect("manifest");
+    manifest["sections"][4]["size"] = 0x5b.into();
+    manifest["sections"][4]["section_crc"] = 0.into();
+    std::fs::write(fixture.path("sections/manifest.json"), manifest.to_string())
+        .expect("write manifest");
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["pack-sections", "sections/manifest.json", "out.bin"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("not dword aligned"));
+    assert_eq!(fixture.read("out.bin"), flash);
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["dump-code", "code"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("code/00100000_MAIN_CODE").len(), 0x200);
+    let code_info: serde_json::Value =
+        serde_json::from_slice(&fixture.read("code/00100000_MAIN_CODE.json")).expect("code info");
+    assert_eq!(code_info["entry_point"], 0x100040);
+    assert_eq!(code_info["flash_addr"], 0x10000);
+    assert_eq!(code_info["cache_line_crcs_valid"], true);
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["dump-code", "code"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--force"));
+    fixture
+        .mlx5fw("flash.bin")
+        .args([
+            "--force",
+            "dump-sections",
+            "--name-template",
+            "{index}:{type}",
+            "code",
+        ])
+        .assert()
+        .success();
+    assert!(fixture.path("code/0_MAIN_CODE").exists());
+    assert!(fixture.path("code/00100000_MAIN_CODE").exists());
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["pack-code", "code", "out.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("out.bin"), flash);
+    // Code must fill whole cache lines
+    let mut code = fixture.read("code/00100000_MAIN_CODE");
+    code.pop();
+    std::fs::write(fixture.path("code/00100000_MAIN_CODE"), code).unwrap();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["pack-code", "code", "out.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Code to frame into cache lines is 0x1ff bytes, expected 0x200",
+        ));
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["dump-boot2", "boot2.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["replace-boot2", "boot2.bin", "out.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("out.bin"), flash);
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["dump-dbg-ini", "fw.ini"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("fw.ini"), b"[general]\nlevel=3\n");
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["replace-dbg-ini", "fw.ini", "out.bin"])
+        .assert()
+        .success();
+    fixture.verifies("out.bin");
+}
+
+#[test]
+fn device_data_backup_and_restore() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["backup-dev-data", "dev.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["sanitize", "sanitized.bin"])
+        .assert()
+        .success();
+    assert_ne!(fixture.read("sanitized.bin"), fixture.read("flash.bin"));
+    fixture
+        .mlx5fw("sanitized.bin")
+        .args(["restore-dev-data", "dev.bin", "out.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("out.bin"), fixture.read("flash.bin"));
+
+    // A backup of another board is only restored with --force
+    let mut backup: serde_json::Value =
+        serde_json::from_slice(&fixture.read("dev.bin")).expect("backup");
+    backup["psid"] = "MT_0000000099".into();
+    std::fs::write(fixture.path("other.json"), backup.to_string()).expect("write backup");
+    fixture
+        .mlx5fw("sanitized.bin")
+        .args(["restore-devdata", "other.json", "out.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Backup is of PSID MT_0000000099"));
+    fixture
+        .mlx5fw("sanitized.bin")
+        .args(["--force", "restore-devdata", "other.json", "out.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("out.bin"), fixture.read("flash.bin"));
+}
+
+#[test]
+fn code_exports() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["dump-mem", "mem.bin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MAIN_CODE"));
+    assert_eq!(fixture.read("mem.bin").len(), 0x200);
+
+    for format in ["json", "ghidra", "idc"] {
+        fixture
+            .mlx5fw("flash.bin")
+            .args(["export-ghidra", "--format", format, "export"])
+            .assert()
+            .success();
+        assert!(!fixture.read("export").is_empty());
+    }
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["export-ld", "code.ld"])
+        .assert()
+        .success();
+    let script = String::from_utf8(fixture.read("code.ld")).expect("UTF-8 script");
+    assert!(script.contains("MAIN_CODE (rx) : ORIGIN = 0x00100000, LENGTH = 0x200"));
+    assert!(script.contains("MAIN_CODE_entry = 0x00100040;"));
+}
+
+#[test]
+fn replace_section_from_stdin() {
+    let fixture = Fixture::new();
+    std::fs::write(fixture.path("content.bin"), [0x5a; 0x40]).unwrap();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["replace-section", "2", "content.bin", "file.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["replace-section", "2", "-", "stdin.bin"])
+        .write_stdin([0x5a; 0x40])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("file.bin"), fixture.read("stdin.bin"));
+    assert_ne!(fixture.read("file.bin"), fixture.read("flash.bin"));
+    fixture.verifies("stdin.bin");
+
+    std::fs::write(fixture.path("ini.txt"), b"[fw]\n").unwrap();
+    fixture
+        .mlx5fw("file.bin")
+        .args(["replace-section", "4", "ini.txt", "chained.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["replace-section", "--section", "2=content.bin"])
+        .args(["--section", "4=ini.txt", "multi.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("multi.bin"), fixture.read("chained.bin"));
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["replace-section", "--section", "2=content.bin"])
+        .args(["--section", "2=ini.txt", "twice.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("replaced twice"));
+}
+
+#[test]
+fn dry_run_writes_nothing() {
+    let fixture = Fixture::new();
+    std::fs::write(fixture.path("content.bin"), [0x5a; 0x40]).unwrap();
+    cargo_bin_cmd!("mlx5fw")
+        .current_dir(fixture.dir.path())
+        .args(["--dry-run", "flash.bin", "replace-section", "2"])
+        .args(["content.bin", "out.bin"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::contains("Dry run, not writing out.bin"))
+        .stderr(predicate::str::contains("0x00021000 +0x40 bytes"))
+        .stderr(predicate::str::contains("HW_BOOT_CFG #0 changed"));
+    assert!(!fixture.path("out.bin").exists());
+}
+
+#[test]
+fn patch_records_and_deltas() {
+    let fixture = Fixture::new();
+    let hexpatch = ["hexpatch", "--at", "0x20010", "--bytes", "dead"];
+
+    cargo_bin_cmd!("mlx5fw")
+        .current_dir(fixture.dir.path())
+        .args(["--patch-record", "flash.bin"])
+        .args(hexpatch)
+        .arg("patched.bin")
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("patched.bin")
+        .args(["revert", "patched.bin.patch.json", "reverted.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("reverted.bin"), fixture.read("flash.bin"));
+
+    // A resized image has no patch record, and nothing is written
+    cargo_bin_cmd!("mlx5fw")
+        .current_dir(fixture.dir.path())
+        .args(["--patch-record", "flash.bin", "trim", "trimmed.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Patched image is"));
+    assert!(!fixture.path("trimmed.bin").exists());
+
+    cargo_bin_cmd!("mlx5fw")
+        .current_dir(fixture.dir.path())
+        .args(["--emit-delta", "flash.bin"])
+        .args(hexpatch)
+        .arg("delta.json")
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["apply-delta", "delta.json", "applied.bin"])
+        .assert()
+        .success();
+    assert_eq!(fixture.read("applied.bin"), fixture.read("patched.bin"));
+}
+
+#[test]
+fn apply_plan() {
+    let fixture = Fixture::new();
+    std::fs::write(
+        fixture.path("plan.yaml"),
+        "operations:\n  - set-psid: MT_0000000099\n  - strip-forbidden\n  - fix-crc\n",
+    )
+    .unwrap();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["apply", "plan.yaml", "out.bin"])
+        .assert()
+        .success();
+    fixture.verifies("out.bin");
+    fixture
+        .mlx5fw("out.bin")
+        .arg("query")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MT_0000000099"));
+
+    std::fs::write(
+        fixture.path("overflow.yaml"),
+        "operations:\n  - patch-va:\n      address: 0xffffffffffffffff\n      bytes: dead\n",
+    )
+    .unwrap();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["apply", "overflow.yaml", "out.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("runs past the address space"));
+}
+
+#[test]
+fn fingerprint_database() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("image.bin")
+        .args(["fingerprint", "--db", "known.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Not in the database"));
+    fixture
+        .mlx5fw("image.bin")
+        .args(["fingerprint", "--db", "known.json", "--record"])
+        .args(["--note", "vendor release"])
+        .assert()
+        .success();
+    // Device data does not take part in the fingerprint
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["fingerprint", "--db", "known.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Matches vendor release 16.35.2000 for PSID MT_0000000010",
+        ));
+}
+
+#[test]
+fn corpus_and_scan() {
+    let fixture = Fixture::new();
+    let dir = fixture.dir.path().to_str().unwrap();
+    cargo_bin_cmd!("mlx5fw")
+        .args(["corpus", "index", dir])
+        .assert()
+        .success();
+    cargo_bin_cmd!("mlx5fw")
+        .args(["corpus", "find", dir, "--psid", "MT_0000000010"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("flash.bin"));
+    cargo_bin_cmd!("mlx5fw")
+        .args(["scan", dir])
+        .assert()
+        .stdout(predicate::str::contains("corrupt.bin"));
+}
+
+#[test]
+fn reconstruct_truncated_dump() {
+    let fixture = Fixture::new();
+    let flash = fixture.read("flash.bin");
+    std::fs::write(fixture.path("dump.bin"), &flash[..0x20100]).expect("write dump");
+    fixture
+        .mlx5fw("dump.bin")
+        .args(["reconstruct", "--size", &flash.len().to_string(), "out.bin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "complete (0x220 bytes present, CRC ok)",
+        ))
+        .stdout(predicate::str::contains("truncated (0x100 bytes present)"))
+        .stdout(predicate::str::contains("device data is missing"));
+    let out = fixture.read("out.bin");
+    assert_eq!(out.len(), flash.len());
+    assert_eq!(out[..0x20100], flash[..0x20100]);
+    assert!(out[0x20100..].iter().all(|&byte| byte == 0xff));
+}
+
+#[test]
+fn downgrade_to_forbidden_version() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["check-downgrade", "image.bin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("OK\n"));
+
+    let mut old = mlx5fw::firmware::Firmware::from_bytes(fixture.read("image.bin"));
+    let mut image_info = old.image_info().expect("IMAGE_INFO");
+    image_info.fw_version = "16.27.2000".parse().expect("version");
+    image_info.write(&mut old).expect("write IMAGE_INFO");
+    let mut entry = old
+        .itoc_entry(mlx5fw::structures::itoc::ItocEntryType::ImageInfo)
+        .expect("IMAGE_INFO entry");
+    entry.update_section_crc(&mut old).expect("update CRC");
+    old.write(fixture.path("old.bin")).expect("write old image");
+
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["check-downgrade", "old.bin"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "16.35.2000 -> 16.27.2000 (downgrade)",
+        ))
+        .stdout(predicate::str::contains(
+            "16.27.2000 is a forbidden version",
+        ));
+}
+
+#[test]
+fn strip_security() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["strip-security", "out.bin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No signatures"));
+
+    // Set the IMAGE_INFO secure_boot flag
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["hexpatch", "--at", "0x20000", "--bytes", "01", "secure.bin"])
+        .assert()
+        .success();
+    fixture
+        .mlx5fw("secure.bin")
+        .arg("security")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Secure boot:   yes"));
+    fixture
+        .mlx5fw("secure.bin")
+        .args(["strip-security", "out.bin"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Cleared IMAGE_INFO secure_boot"));
+    fixture
+        .mlx5fw("out.bin")
+        .arg("security")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Secure boot:   no"));
+    fixture.verifies("out.bin");
+}
+
+#[test]
+fn tag_and_show_tag() {
+    let fixture = Fixture::new();
+    fixture
+        .mlx5fw("flash.bin")
+        .arg("show-tag")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no tag"));
+    fixture
+        .mlx5fw("flash.bin")
+        .args(["tag", "--note", "experiment 42", "tagged.bin"])
+        .assert()
+        .success();
+    fixture.verifies("tagged.bin");
+    fixture
+        .mlx5fw("tagged.bin")
+        .arg("showtag")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Note:            experiment 42"));
+
+    // Tagging again replaces the tag
+    fixture
+        .mlx5fw("tagged.bin")
+        .args(["tag", "--note", "experiment 43", "tagged.bin"])
+        .assert()
+        .success();
+    let output = fixture
+        .mlx5fw("tagged.bin")
+        .args(["show-tag", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let tag: serde_json::Value = serde_json::from_slice(&output).expect("tag JSON");
+    assert_eq!(tag["note"], "experiment 43");
+    fixture
+        .mlx5fw("tagged.bin")
+        .arg("show-sections")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("UNKNOWN_SECTION_f0").count(1));
+}
+
+#[test]
+fn transformed_dumps() {
+    let fixture = Fixture::new();
+    let flash = fixture.read("flash.bin");
+    let mut swapped = flash.clone();
+    swapped.chunks_exact_mut(4).for_each(|word| word.reverse());
+    std::fs::write(fixture.path("swapped.bin"), &swapped).expect("write swapped dump");
+    let reversed: Vec<u8> = flash.iter().map(|byte| byte.reverse_bits()).collect();
+    std::fs::write(fixture.path("reversed.bin"), &reversed).expect("write reversed dump");
+
+    fixture
+        .mlx5fw("swapped.bin")
+        .arg("verify")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Input is word-swapped"));
+    fixture
+        .mlx5fw("reversed.bin")
+        .args(["fix-crc", "out.bin"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Input is bit-reversed"));
+    assert_eq!(fixture.read("out.bin"), flash);
+
+    cargo_bin_cmd!("mlx5fw")
+        .current_dir(fixture.dir.path())
+        .args(["--input-transform", "swap32", "flash.bin", "verify"])
+        .assert()
+        .failure();
+}
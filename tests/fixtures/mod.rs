@@ -0,0 +1,106 @@
+//! Real images for the corpus tests, downloaded on first use and cached under target/fixtures,
+//! so regressions against them are caught without committing firmware binaries. Needs `curl`,
+//! and `unzip` for zip archives.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const IMAGES: &str = include_str!("images.txt");
+
+/// `target/fixtures`, next to the temporary directory cargo provides to integration tests
+fn cache_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .parent()
+        .expect("target directory")
+        .join("fixtures")
+}
+
+fn sha256(path: &Path) -> Option<String> {
+    std::fs::read(path)
+        .ok()
+        .map(|data| hex::encode(Sha256::digest(data)))
+}
+
+fn run(command: &mut Command) {
+    let status = command
+        .status()
+        .unwrap_or_else(|err| panic!("{:?}: {}", command, err));
+    assert!(status.success(), "{:?}: {}", command, status);
+}
+
+/// Download `url` to `path` unless it is cached there already, and check its hash
+fn fetch(url: &str, expected: &str, path: &Path) {
+    if sha256(path).as_deref() == Some(expected) {
+        return;
+    }
+    let partial = path.with_extension("part");
+    run(Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(&partial)
+        .arg(url));
+    let actual = sha256(&partial).unwrap_or_default();
+    assert_eq!(actual, expected, "SHA-256 of {}", url);
+    std::fs::rename(&partial, path).expect("rename download");
+}
+
+fn bin_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir).expect("read fixture directory") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(bin_files(&path));
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("bin"))
+        {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// The listed images, downloading those not cached yet. Zip archives are unpacked next to them.
+pub fn images() -> Vec<PathBuf> {
+    let dir = cache_dir();
+    std::fs::create_dir_all(&dir).expect("create fixture directory");
+    let mut images = vec![];
+    for line in IMAGES.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (expected, url) = line
+            .split_once(char::is_whitespace)
+            .unwrap_or_else(|| panic!("expected a SHA-256 and a URL: {}", line));
+        let (expected, url) = (expected.to_ascii_lowercase(), url.trim());
+        let name = url.rsplit('/').next().unwrap_or_default();
+        let path = dir.join(format!("{}-{}", &expected[..16.min(expected.len())], name));
+        fetch(url, &expected, &path);
+        if !name.to_ascii_lowercase().ends_with(".zip") {
+            images.push(path);
+            continue;
+        }
+        let unpacked = path.with_extension("d");
+        if !unpacked.is_dir() {
+            let partial = path.with_extension("d.part");
+            let _ = std::fs::remove_dir_all(&partial);
+            run(Command::new("unzip")
+                .args(["-q", "-o"])
+                .arg(&path)
+                .arg("-d")
+                .arg(&partial));
+            std::fs::rename(&partial, &unpacked).expect("rename unpacked archive");
+        }
+        images.extend(bin_files(&unpacked));
+    }
+    if images.is_empty() {
+        eprintln!("No images from tests/fixtures/images.txt, the corpus tests check none");
+    }
+    images
+}
@@ -0,0 +1,181 @@
+//! Deku structures must serialize back to the bytes they were parsed from. Set `MLX5FW_CORPUS` to
+//! a directory of images, or enable the `fixtures` feature to download the images listed in
+//! tests/fixtures/images.txt (none so far), to also check the headers of real firmware.
+
+#[cfg(feature = "fixtures")]
+mod fixtures;
+
+#[cfg(not(feature = "fixtures"))]
+mod fixtures {
+    pub fn images() -> Vec<std::path::PathBuf> {
+        vec![]
+    }
+}
+
+use deku::prelude::*;
+use proptest::prelude::*;
+use std::fmt::Debug;
+
+use mlx5fw::firmware::Firmware;
+use mlx5fw::structures::hwpointers::{Boot2, HwPointers};
+use mlx5fw::structures::itoc::{ItocEntry, ItocEntryType};
+
+/// Parse `bytes` as a whole and check that they serialize back unchanged
+fn round_trip<'a, T>(bytes: &'a [u8]) -> T
+where
+    T: DekuContainerRead<'a> + DekuContainerWrite + Debug,
+{
+    let ((rest, bit_offset), value) = T::from_bytes((bytes, 0)).expect("parse");
+    assert!(rest.is_empty() && bit_offset == 0, "{:?} left bytes", value);
+    assert_eq!(value.to_bytes().expect("serialize"), bytes, "{:?}", value);
+    value
+}
+
+// MAIN_CODE entry with cache line CRCs
+const ITOC_ENTRY: &str = concat!(
+    "03000220", "40100000", "00100040", "00000000", "00000000", "00010000", "00007cca", "00003b08",
+);
+
+// Boot record, boot2 and ITOC pointers, the other pointers erased
+const HW_POINTERS: &str = concat!(
+    "000000000000000000001000000067eb00005000000058560000000000000000",
+    "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+    "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+    "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+);
+
+const BOOT2: &str = concat!(
+    "1234567800000008111111111111111111111111111111111111111111111111",
+    "11111111111111110000000000007c9c",
+);
+
+#[test]
+fn itoc_entry_vector() {
+    let entry: ItocEntry = round_trip(&hex::decode(ITOC_ENTRY).unwrap());
+    assert_eq!(entry.entry_type, ItocEntryType::MainCode);
+    assert_eq!(entry.size, 0x220);
+    assert!(entry.cache_line_crc && !entry.zipped_image);
+    assert_eq!(entry.load_address, 0x100000);
+    assert_eq!(entry.entry_point, 0x100040);
+    assert_eq!(entry.flash_addr, 0x10000);
+    assert_eq!(entry.section_crc, 0x7cca);
+    assert!(entry.itoc_entry_crc_valid());
+}
+
+#[test]
+fn hw_pointers_vector() {
+    let hwpointers: HwPointers = round_trip(&hex::decode(HW_POINTERS).unwrap());
+    assert_eq!(hwpointers.boot2.ptr, 0x1000);
+    assert_eq!(hwpointers.boot2.crc, hwpointers.boot2.calc_crc());
+    assert_eq!(hwpointers.toc.ptr, 0x5000);
+    assert!(hwpointers.hmac_start.is_erased());
+}
+
+#[test]
+fn boot2_vector() {
+    let boot2: Boot2 = round_trip(&hex::decode(BOOT2).unwrap());
+    assert_eq!(boot2.size, 8);
+    assert_eq!(boot2.total_size(), 0x30);
+    assert_eq!(boot2.crc, boot2.calc_crc().unwrap());
+
+    let mut truncated = boot2.clone();
+    truncated.data.pop();
+    assert!(truncated.calc_crc().is_err());
+}
+
+#[test]
+fn itoc_entry_known_types() {
+    let (_, template) = ItocEntry::from_bytes((&hex::decode(ITOC_ENTRY).unwrap(), 0)).unwrap();
+    for entry_type in ItocEntryType::KNOWN {
+        let bytes = ItocEntry {
+            entry_type: entry_type.clone(),
+            ..template.clone()
+        }
+        .to_bytes()
+        .unwrap();
+        let entry: ItocEntry = round_trip(&bytes);
+        assert_eq!(entry.entry_type, entry_type);
+    }
+}
+
+fn corpus_images() -> Vec<(String, Firmware)> {
+    let corpus: Vec<std::path::PathBuf> = match std::env::var("MLX5FW_CORPUS") {
+        Ok(dir) => std::fs::read_dir(dir)
+            .expect("read corpus directory")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .collect(),
+        Err(_) => vec![],
+    };
+    corpus
+        .into_iter()
+        .chain(fixtures::images())
+        .map(|path| {
+            let data = std::fs::read(&path).unwrap();
+            (path.display().to_string(), Firmware::from_bytes(data))
+        })
+        .collect()
+}
+
+#[test]
+fn corpus_headers() {
+    for (name, firmware) in corpus_images() {
+        let Ok(hwpointers) = firmware.hwpointers() else {
+            continue;
+        };
+        let bytes = firmware.bytes(hwpointers.0, 0x80).unwrap();
+        assert_eq!(
+            hwpointers.to_bytes().unwrap(),
+            bytes,
+            "{} HW pointers",
+            name
+        );
+        // FS5 images have no boot2
+        if let Some(boot2) = firmware.boot2().ok().filter(|_| !firmware.is_fs5()) {
+            let bytes = firmware.bytes(boot2.0, boot2.total_size()).unwrap();
+            assert!(boot2.to_bytes().unwrap() == bytes, "{} boot2", name);
+        }
+        let entries = firmware
+            .itoc()
+            .unwrap_or_default()
+            .into_iter()
+            .chain(firmware.dtoc().unwrap_or_default());
+        for entry in entries {
+            let bytes = firmware.bytes(entry.0, 0x20).unwrap();
+            assert!(
+                entry.to_bytes().unwrap() == bytes,
+                "{} {} entry at {:#x}",
+                name,
+                entry.entry_type,
+                entry.0
+            );
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn itoc_entry_bytes(bytes in prop::array::uniform32(any::<u8>())) {
+        round_trip::<ItocEntry>(&bytes);
+    }
+
+    #[test]
+    fn hw_pointers_bytes(bytes in prop::collection::vec(any::<u8>(), 0x80)) {
+        round_trip::<HwPointers>(&bytes);
+    }
+
+    #[test]
+    fn boot2_bytes(
+        header in any::<u32>(),
+        data in prop::collection::vec(any::<u32>(), 0..64),
+        dword0 in any::<u32>(),
+        trailer in any::<u32>(),
+    ) {
+        let mut bytes = header.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        data.iter().for_each(|word| bytes.extend_from_slice(&word.to_be_bytes()));
+        bytes.extend_from_slice(&dword0.to_be_bytes());
+        bytes.extend_from_slice(&trailer.to_be_bytes());
+        round_trip::<Boot2>(&bytes);
+    }
+}